@@ -0,0 +1,256 @@
+use {
+    crate::{
+        collect_by_key, dump,
+        gpu::{self, Gpu, BATCH_WORDS},
+        index_by_page_offset,
+        logging::log,
+        page_offset,
+        progress::{get_progress_bar, ParallelProgressIterator},
+        regions::Regions,
+        sample::Sampling,
+        script::Script,
+        PageIndex, RBaseTraits,
+    },
+    rayon::{
+        iter::{
+            Either, IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator,
+            ParallelIterator,
+        },
+        slice::ParallelSlice,
+    },
+};
+
+/* Fill patterns and canaries, which are far more common than any genuine pointer with the same value */
+const SENTINELS: [u32; 4] = [0xffffffff, 0xdeadbeef, 0xcafebabe, 0xaaaaaaaa];
+
+/* The sentinels as words of the given number of bits, repeating them to fill 64-bit words and
+truncating them for 24-bit words */
+pub fn sentinels(bits: u32) -> impl Iterator<Item = u64> {
+    SENTINELS.into_iter().map(move |sentinel| {
+        let word = (sentinel as u64) << 32 | sentinel as u64;
+        word & (u64::MAX >> (u64::BITS - bits))
+    })
+}
+
+/* Pointers are normally aligned to their size, but 24-bit pointers (e.g. in tables on the 65C816)
+are packed at any byte offset. This is the alignment used unless --align is given. */
+pub const fn pointer_alignment(size: usize) -> usize {
+    match size {
+        3 => 1,
+        size => size,
+    }
+}
+
+/* Decode each word at a multiple of the given alignment (only in the given regions) as a pointer,
+yielding the file offset and value of those which are non-zero and within the address range. Any
+trailing bytes too few to form a word are ignored. The words are split into work units of the given
+size in bytes, if any, otherwise rayon splits them adaptively. They are decoded on the GPU, if one is
+in use (and words are aligned to their size, as it expects). */
+#[allow(clippy::too_many_arguments)]
+pub fn get_pointers<'a, T: RBaseTraits<T, N> + 'a, const N: usize>(
+    bytes: &'a [u8],
+    read_address_bytes: fn([u8; N]) -> T,
+    align: usize,
+    pointer_mask: Option<T>,
+    address_range: Option<(T, T)>,
+    script: Option<&'a Script>,
+    regions: Option<&'a Regions>,
+    chunk_size: Option<usize>,
+) -> impl ParallelIterator<Item = (usize, T)> + 'a {
+    let pointers = match gpu::get().filter(|_gpu| align == N) {
+        Some(gpu) => Either::Left(
+            get_pointers_on_gpu(gpu, bytes, read_address_bytes, pointer_mask, address_range)
+                .into_par_iter()
+                .filter(move |&(file_offset, _address)| {
+                    regions.is_none_or(|regions| regions.contains(file_offset))
+                }),
+        ),
+        None => Either::Right(get_pointers_on_cpu(
+            bytes,
+            read_address_bytes,
+            align,
+            pointer_mask,
+            address_range,
+            regions,
+            chunk_size,
+        )),
+    };
+    pointers.filter(move |&(_file_offset, address)| {
+        script.is_none_or(|script| script.accept_pointer(address.into()))
+    })
+}
+
+fn get_pointers_on_cpu<'a, T: RBaseTraits<T, N> + 'a, const N: usize>(
+    bytes: &'a [u8],
+    read_address_bytes: fn([u8; N]) -> T,
+    align: usize,
+    pointer_mask: Option<T>,
+    address_range: Option<(T, T)>,
+    regions: Option<&'a Regions>,
+    chunk_size: Option<usize>,
+) -> impl ParallelIterator<Item = (usize, T)> + 'a {
+    let progress_bar = get_progress_bar("Finding addresses", bytes.len() / align);
+    let (min_len, max_len) = match chunk_size {
+        Some(chunk_size) => ((chunk_size / align).max(1), (chunk_size / align).max(1)),
+        None => (1, usize::MAX),
+    };
+    bytes
+        .par_windows(N)
+        .step_by(align)
+        .enumerate()
+        .with_min_len(min_len)
+        .with_max_len(max_len)
+        .progress_with(progress_bar)
+        .filter(move |&(idx, _bytes)| regions.is_none_or(|regions| regions.contains(idx * align)))
+        .map(move |(idx, bytes)| (idx * align, read_address_bytes(bytes.try_into().unwrap())))
+        .map(move |(file_offset, address)| match pointer_mask {
+            Some(mask) => (file_offset, address & mask),
+            None => (file_offset, address),
+        })
+        .filter(|&(_file_offset, address)| address != T::default())
+        .filter(move |&(_file_offset, address)| match address_range {
+            Some((start, end)) => address >= start && address <= end,
+            None => true,
+        })
+}
+
+/* The GPU decodes each word given the shift of each of its bytes, which are found by decoding words
+with a single byte set */
+fn get_pointers_on_gpu<T: RBaseTraits<T, N>, const N: usize>(
+    gpu: &Gpu,
+    bytes: &[u8],
+    read_address_bytes: fn([u8; N]) -> T,
+    pointer_mask: Option<T>,
+    address_range: Option<(T, T)>,
+) -> Vec<(usize, T)> {
+    let shifts: Vec<u32> = (0..N)
+        .map(|idx| {
+            let mut word = [0; N];
+            word[idx] = 1;
+            Into::<u64>::into(read_address_bytes(word)).trailing_zeros()
+        })
+        .collect();
+    let mask = pointer_mask.map_or(u64::MAX, Into::into);
+    let range = address_range.map_or((0, u64::MAX), |(start, end)| (start.into(), end.into()));
+    let batches: Vec<&[u8]> = bytes[..bytes.len() / N * N]
+        .chunks(BATCH_WORDS * N)
+        .collect();
+    let progress_bar = get_progress_bar("Finding addresses", batches.len());
+    let mut pointers = Vec::new();
+    for (batch, words) in batches.into_iter().enumerate() {
+        pointers.extend(
+            gpu.pointers(words, N, &shifts, mask, range)
+                .into_iter()
+                .map(|idx| {
+                    let file_offset = (batch * BATCH_WORDS + idx) * N;
+                    let word = bytes[file_offset..file_offset + N].try_into().unwrap();
+                    let address = read_address_bytes(word);
+                    match pointer_mask {
+                        Some(mask) => (file_offset, address & mask),
+                        None => (file_offset, address),
+                    }
+                }),
+        );
+        progress_bar.inc(1);
+    }
+    pointers
+}
+
+/* Number of rows of the histogram of address prefixes */
+const PREFIX_ROWS: usize = 8;
+
+/* Number of high bits of an address forming its prefix, enough to tell apart the address spaces an
+image might reference (e.g. flash at 0x08xxxxxx, RAM at 0x80xxxxxx or peripherals at 0xffxxxxxx) */
+fn prefix_bits(bits: u32) -> u32 {
+    match bits {
+        64 => 24,
+        _ => 8,
+    }
+}
+
+/* Log how many of the addresses have each of the most common prefixes, so users can see which
+address spaces the image references and choose filters (e.g. --require-prefix) accordingly */
+fn log_prefixes<T: RBaseTraits<T, N>, const N: usize>(addresses: &[T]) {
+    let shift = T::BITS - prefix_bits(T::BITS);
+    let prefixes = collect_by_key(
+        addresses
+            .par_iter()
+            .map(|&address| (Into::<u64>::into(address) >> shift, 1)),
+        |count, other| *count += other,
+    );
+    let mut prefixes: Vec<(u64, usize)> = prefixes.into_iter().collect();
+    prefixes.sort_unstable_by(|(p1, c1), (p2, c2)| c2.cmp(c1).then(p1.cmp(p2)));
+    let digits = (prefix_bits(T::BITS) / 4) as usize;
+    let wildcard = "x".repeat((shift / 4) as usize);
+    for (prefix, count) in prefixes.iter().take(PREFIX_ROWS) {
+        log!(
+            "Prefix: 0x{:0digits$x}{wildcard}: {count} addresses ({:.2}%)",
+            prefix,
+            100.0 * *count as f64 / addresses.len() as f64
+        );
+    }
+    if prefixes.len() > PREFIX_ROWS {
+        let others: usize = prefixes[PREFIX_ROWS..]
+            .iter()
+            .map(|(_prefix, count)| count)
+            .sum();
+        log!(
+            "Prefix: {} others: {others} addresses ({:.2}%)",
+            prefixes.len() - PREFIX_ROWS,
+            100.0 * others as f64 / addresses.len() as f64
+        );
+    }
+}
+
+/* Collect the distinct addresses among the pointers found, along with the number of them before any
+were sampled, writing the pointers to a file first if given one */
+pub fn get_addresses_by_page_offset<T: RBaseTraits<T, N>, const N: usize>(
+    pointers: impl ParallelIterator<Item = (usize, T)>,
+    dump_pointers: Option<&str>,
+    max_addresses: usize,
+    sampling: Sampling,
+) -> Result<(PageIndex<T>, usize), String> {
+    let addresses = match dump_pointers {
+        Some(path) => {
+            let pointers: Vec<(usize, T)> = pointers.collect();
+            dump::pointers(path, &pointers).map_err(|e| format!("{path}: {e}"))?;
+            log!("Wrote: {} pointers to {}", pointers.len(), path);
+            collect_by_key(
+                pointers
+                    .into_par_iter()
+                    .map(|(_file_offset, address)| (address, ())),
+                |_, _| {},
+            )
+        }
+        None => collect_by_key(
+            pointers.map(|(_file_offset, address)| (address, ())),
+            |_, _| {},
+        ),
+    };
+    log!("Found: {:?} addresses", addresses.len());
+
+    /* Index each address by its page offset */
+    let addresses: Vec<T> = addresses
+        .into_par_iter()
+        .map(|(address, ())| address)
+        .collect();
+    log_prefixes(&addresses);
+    if addresses.len() > max_addresses {
+        log!(
+            "Sampling: {} of {} addresses ({})",
+            max_addresses,
+            addresses.len(),
+            sampling.strategy
+        );
+    }
+    let found = addresses.len();
+    let addresses = sampling.sample(addresses, max_addresses);
+    let progress_bar = get_progress_bar("Indexing addresses", addresses.len());
+    let index = index_by_page_offset(
+        addresses.into_par_iter().progress_with(progress_bar),
+        |&address| page_offset(address),
+        sampling,
+        "addresses",
+    );
+    Ok((index, found))
+}
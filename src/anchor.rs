@@ -0,0 +1,131 @@
+/* Users who already know where a string lives in memory (e.g. from a debugger or a crash log) can
+give it as an anchor. The base address must then put one of the occurrences of the string in the
+image at that address, so any candidates which don't are discarded. Likewise, they may know the
+address of something they have found in the image by hand (e.g. a reset vector), which fixes the
+base address outright. */
+use {
+    crate::args::parse_number,
+    regex::bytes::Regex,
+    std::fmt::{Display, Formatter, Result},
+};
+
+#[derive(Clone, Debug)]
+pub struct Anchor {
+    pub text: String,
+    pub address: u64,
+}
+
+impl Display for Anchor {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "\"{}\"=0x{:x}", self.text, self.address)
+    }
+}
+
+/* A string and the address at which it is known to be, e.g. "Booting Linux"=0xc0801234. The string is
+everything before the last =, and may be quoted. */
+pub fn parse_anchor(s: &str) -> std::result::Result<Anchor, String> {
+    let (text, address) = s
+        .rsplit_once('=')
+        .ok_or("expected a string and an address separated by =")?;
+    let text = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .unwrap_or(text);
+    if text.is_empty() {
+        return Err("the string is empty".to_string());
+    }
+    Ok(Anchor {
+        text: text.to_string(),
+        address: parse_number(address).map_err(|e| e.to_string())?,
+    })
+}
+
+impl Anchor {
+    /* The file offsets of each occurrence of the string */
+    pub fn offsets(&self, bytes: &[u8]) -> Vec<usize> {
+        Regex::new(&regex::escape(&self.text))
+            .unwrap()
+            .find_iter(bytes)
+            .map(|m| m.start())
+            .collect()
+    }
+
+    /* The base addresses which put an occurrence of the string at its address */
+    pub fn bases(&self, bytes: &[u8]) -> Vec<u64> {
+        self.offsets(bytes)
+            .into_iter()
+            .filter_map(|offset| self.address.checked_sub(offset as u64))
+            .collect()
+    }
+}
+
+/* A file offset and the address at which it is known to be loaded */
+#[derive(Clone, Copy, Debug)]
+pub struct AnchorOffset {
+    pub offset: u64,
+    pub address: u64,
+}
+
+impl Display for AnchorOffset {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "0x{:x}=0x{:x}", self.offset, self.address)
+    }
+}
+
+/* A file offset and address separated by =, e.g. 0x100=0x08000100 */
+pub fn parse_anchor_offset(s: &str) -> std::result::Result<AnchorOffset, String> {
+    let (offset, address) = s
+        .split_once('=')
+        .ok_or("expected a file offset and an address separated by =")?;
+    let anchor = AnchorOffset {
+        offset: parse_number(offset).map_err(|e| e.to_string())?,
+        address: parse_number(address).map_err(|e| e.to_string())?,
+    };
+    if anchor.base().is_none() {
+        return Err(format!(
+            "the address is below the file offset 0x{:x}",
+            anchor.offset
+        ));
+    }
+    Ok(anchor)
+}
+
+impl AnchorOffset {
+    /* The base address which loads the file offset at its address */
+    pub fn base(&self) -> Option<u64> {
+        self.address.checked_sub(self.offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quoted_strings_containing_equals() {
+        let anchor = parse_anchor("\"a=b\"=0xc0801234").unwrap();
+        assert_eq!(anchor.text, "a=b");
+        assert_eq!(anchor.address, 0xc080_1234);
+        assert_eq!(anchor.to_string(), "\"a=b\"=0xc0801234");
+        assert!(parse_anchor("\"\"=0x1000").is_err());
+        assert!(parse_anchor("Booting Linux").is_err());
+    }
+
+    #[test]
+    fn finds_the_bases_putting_the_string_at_its_address() {
+        let anchor = parse_anchor("Booting=0x80002000").unwrap();
+        let mut bytes = vec![0; 0x3000];
+        bytes[0x100..0x107].copy_from_slice(b"Booting");
+        bytes[0x2800..0x2807].copy_from_slice(b"Booting");
+        assert_eq!(anchor.offsets(&bytes), [0x100, 0x2800]);
+        assert_eq!(anchor.bases(&bytes), [0x8000_1f00, 0x7fff_f800]);
+    }
+
+    #[test]
+    fn parses_offsets_below_their_address() {
+        let anchor = parse_anchor_offset("0x100=0x08000100").unwrap();
+        assert_eq!(anchor.base(), Some(0x0800_0000));
+        assert_eq!(anchor.to_string(), "0x100=0x8000100");
+        assert!(parse_anchor_offset("0x200=0x100").is_err());
+    }
+}
@@ -0,0 +1,103 @@
+/* An Android boot image is a header followed by the kernel, the ramdisk and (before version 3) an
+optional second stage, each padded to the page size given in the header. Only the kernel is of
+interest, and versions 0 to 2 of the header also give the address at which it is loaded. */
+use std::ops::Range;
+
+const MAGIC: &[u8] = b"ANDROID!";
+
+/* Versions 3 and 4 have a fixed page size, and no load addresses */
+const FIXED_PAGE_SIZE: usize = 0x1000;
+const MAX_VERSION: u32 = 4;
+
+pub struct BootImage {
+    pub version: u32,
+    pub kernel: Range<usize>,
+    pub ramdisk: Range<usize>,
+    pub kernel_address: Option<u64>,
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let word = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(word.try_into().unwrap()))
+}
+
+pub fn parse(bytes: &[u8]) -> Option<BootImage> {
+    if !bytes.starts_with(MAGIC) {
+        return None;
+    }
+    /* Version 0 headers predate the version field, which was unused so may hold anything */
+    let version = Some(read_u32(bytes, 40)?)
+        .filter(|&version| version <= MAX_VERSION)
+        .unwrap_or(0);
+    let kernel_size = read_u32(bytes, 8)? as usize;
+    let (page_size, ramdisk_size, kernel_address) = match version {
+        3.. => (FIXED_PAGE_SIZE, read_u32(bytes, 12)? as usize, None),
+        _ => (
+            read_u32(bytes, 36)? as usize,
+            read_u32(bytes, 16)? as usize,
+            Some(read_u32(bytes, 12)? as u64),
+        ),
+    };
+    if !page_size.is_power_of_two() || kernel_size == 0 {
+        return None;
+    }
+    let kernel = page_size..page_size.checked_add(kernel_size)?;
+    let ramdisk_start = kernel.end.checked_next_multiple_of(page_size)?;
+    let ramdisk = ramdisk_start..ramdisk_start.checked_add(ramdisk_size)?;
+    if kernel.end > bytes.len() {
+        return None;
+    }
+    Some(BootImage {
+        version,
+        kernel,
+        ramdisk,
+        kernel_address,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* A boot image header, of the given words following the magic */
+    fn header(words: &[(usize, u32)]) -> Vec<u8> {
+        let mut bytes = vec![0; 0x4000];
+        bytes[..MAGIC.len()].copy_from_slice(MAGIC);
+        for &(offset, word) in words {
+            bytes[offset..offset + 4].copy_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn parses_a_version_2_header_with_its_load_address() {
+        let bytes = header(&[
+            (8, 0x1234),
+            (12, 0x8000_8000),
+            (16, 0x100),
+            (36, 0x800),
+            (40, 2),
+        ]);
+        let image = parse(&bytes).unwrap();
+        assert_eq!(image.version, 2);
+        assert_eq!(image.kernel, 0x800..0x1a34);
+        assert_eq!(image.ramdisk, 0x2000..0x2100);
+        assert_eq!(image.kernel_address, Some(0x8000_8000));
+    }
+
+    #[test]
+    fn parses_a_version_3_header_without_a_load_address() {
+        let bytes = header(&[(8, 0x1000), (12, 0x200), (40, 3)]);
+        let image = parse(&bytes).unwrap();
+        assert_eq!(image.version, 3);
+        assert_eq!(image.kernel, 0x1000..0x2000);
+        assert_eq!(image.ramdisk, 0x2000..0x2200);
+        assert_eq!(image.kernel_address, None);
+    }
+
+    #[test]
+    fn ignores_a_kernel_beyond_the_end_of_the_file() {
+        let bytes = header(&[(8, 0x8000), (36, 0x800)]);
+        assert!(parse(&bytes).is_none());
+    }
+}
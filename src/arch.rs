@@ -0,0 +1,390 @@
+use {
+    crate::{
+        progress::{get_progress_bar, ParallelProgressIterator},
+        Endian, Size,
+    },
+    rayon::{
+        iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator},
+        slice::ParallelSlice,
+    },
+    std::fmt::{Display, Formatter, Result},
+};
+
+/* Number of offsets each worker scans for signatures (a multiple of every alignment) */
+const CHUNK_SIZE: usize = 0x10000;
+
+/* Minimum score (hits above those expected from random data) to report an architecture */
+const MIN_SCORE: f64 = 32.0;
+
+/* Common instruction encodings (typically prologues and returns) which occur far more frequently
+in code than in random data. The mask selects the fixed bits of the encoding. */
+struct Signature {
+    value: u32,
+    mask: u32,
+}
+
+const fn sig(value: u32, mask: u32) -> Signature {
+    Signature { value, mask }
+}
+
+struct Isa {
+    name: &'static str,
+    size: Size,
+    /* Endianness of the data, if implied by that of the instructions */
+    endian: Option<Endian>,
+    alignment: usize,
+    read_word: fn([u8; 4]) -> u32,
+    signatures: &'static [Signature],
+}
+
+const ARM: &[Signature] = &[
+    sig(0xe12fff1e, 0xffffffff), /* bx lr */
+    sig(0xe92d4000, 0xffff4000), /* push {..., lr} */
+    sig(0xe8bd8000, 0xffff8000), /* pop {..., pc} */
+    sig(0xe59f0000, 0xffff0000), /* ldr rX, [pc, #N] */
+    sig(0xe1a00000, 0xffff0ff0), /* mov rX, rY */
+];
+
+const THUMB: &[Signature] = &[
+    sig(0x00004770, 0x0000ffff), /* bx lr */
+    sig(0x0000b500, 0x0000ff00), /* push {..., lr} */
+    sig(0x0000bd00, 0x0000ff00), /* pop {..., pc} */
+];
+
+const AARCH64: &[Signature] = &[
+    sig(0xd65f03c0, 0xffffffff), /* ret */
+    sig(0xd503201f, 0xffffffff), /* nop */
+    sig(0xd503233f, 0xffffffff), /* paciasp */
+    sig(0x910003fd, 0xffffffff), /* mov x29, sp */
+    sig(0xa9807bfd, 0xffc07fff), /* stp x29, x30, [sp, #-N]! */
+];
+
+const MIPS: &[Signature] = &[
+    sig(0x03e00008, 0xffffffff), /* jr ra */
+    sig(0x27bd0000, 0xffff0000), /* addiu sp, sp, N */
+    sig(0xafbf0000, 0xffff0000), /* sw ra, N(sp) */
+    sig(0x8fbf0000, 0xffff0000), /* lw ra, N(sp) */
+];
+
+const MIPS64: &[Signature] = &[
+    sig(0x03e00008, 0xffffffff), /* jr ra */
+    sig(0x67bd0000, 0xffff0000), /* daddiu sp, sp, N */
+    sig(0xffbf0000, 0xffff0000), /* sd ra, N(sp) */
+    sig(0xdfbf0000, 0xffff0000), /* ld ra, N(sp) */
+];
+
+const POWERPC: &[Signature] = &[
+    sig(0x4e800020, 0xffffffff), /* blr */
+    sig(0x7c0802a6, 0xffffffff), /* mflr r0 */
+    sig(0x7c0803a6, 0xffffffff), /* mtlr r0 */
+    sig(0x94210000, 0xffff0000), /* stwu r1, N(r1) */
+];
+
+const POWERPC64: &[Signature] = &[
+    sig(0x4e800020, 0xffffffff), /* blr */
+    sig(0x7c0802a6, 0xffffffff), /* mflr r0 */
+    sig(0x7c0803a6, 0xffffffff), /* mtlr r0 */
+    sig(0xf8210001, 0xffff0003), /* stdu r1, N(r1) */
+];
+
+const RISCV: &[Signature] = &[
+    sig(0x00008067, 0xffffffff), /* ret */
+    sig(0x00112023, 0x01fff07f), /* sw ra, N(sp) */
+    sig(0x00012083, 0x000ff07f), /* lw ra, N(sp) */
+];
+
+const RISCV64: &[Signature] = &[
+    sig(0x00008067, 0xffffffff), /* ret */
+    sig(0x00113023, 0x01fff07f), /* sd ra, N(sp) */
+    sig(0x00013083, 0x000ff07f), /* ld ra, N(sp) */
+];
+
+/* x86 instructions are matched as a sequence of bytes, hence are read big-endian */
+const X86: &[Signature] = &[
+    sig(0x5589e500, 0xffffff00), /* push ebp; mov ebp, esp */
+    sig(0x558bec00, 0xffffff00), /* push ebp; mov ebp, esp */
+    sig(0x83ec0000, 0xffff0000), /* sub esp, N */
+];
+
+const X86_64: &[Signature] = &[
+    sig(0x554889e5, 0xffffffff), /* push rbp; mov rbp, rsp */
+    sig(0x4883ec00, 0xffffff00), /* sub rsp, N */
+    sig(0x48895c24, 0xffffffff), /* mov [rsp + N], rbx */
+];
+
+const fn isa(
+    name: &'static str,
+    size: Size,
+    endian: Option<Endian>,
+    alignment: usize,
+    read_word: fn([u8; 4]) -> u32,
+    signatures: &'static [Signature],
+) -> Isa {
+    Isa {
+        name,
+        size,
+        endian,
+        alignment,
+        read_word,
+        signatures,
+    }
+}
+
+/* The endianness of the data is that of the instructions, except for AArch64 whose instructions are
+always little-endian, even when its data isn't. RISC-V and x86 are little-endian throughout. */
+const ISAS: &[Isa] = &[
+    isa(
+        "ARM (little-endian)",
+        Size::Bits32,
+        Some(Endian::Little),
+        4,
+        u32::from_le_bytes,
+        ARM,
+    ),
+    isa(
+        "ARM (big-endian)",
+        Size::Bits32,
+        Some(Endian::Big),
+        4,
+        u32::from_be_bytes,
+        ARM,
+    ),
+    isa(
+        "Thumb (little-endian)",
+        Size::Bits32,
+        Some(Endian::Little),
+        2,
+        u32::from_le_bytes,
+        THUMB,
+    ),
+    isa(
+        "AArch64",
+        Size::Bits64,
+        None,
+        4,
+        u32::from_le_bytes,
+        AARCH64,
+    ),
+    isa(
+        "MIPS (little-endian)",
+        Size::Bits32,
+        Some(Endian::Little),
+        4,
+        u32::from_le_bytes,
+        MIPS,
+    ),
+    isa(
+        "MIPS (big-endian)",
+        Size::Bits32,
+        Some(Endian::Big),
+        4,
+        u32::from_be_bytes,
+        MIPS,
+    ),
+    isa(
+        "MIPS64 (little-endian)",
+        Size::Bits64,
+        Some(Endian::Little),
+        4,
+        u32::from_le_bytes,
+        MIPS64,
+    ),
+    isa(
+        "MIPS64 (big-endian)",
+        Size::Bits64,
+        Some(Endian::Big),
+        4,
+        u32::from_be_bytes,
+        MIPS64,
+    ),
+    isa(
+        "PowerPC (little-endian)",
+        Size::Bits32,
+        Some(Endian::Little),
+        4,
+        u32::from_le_bytes,
+        POWERPC,
+    ),
+    isa(
+        "PowerPC (big-endian)",
+        Size::Bits32,
+        Some(Endian::Big),
+        4,
+        u32::from_be_bytes,
+        POWERPC,
+    ),
+    isa(
+        "PowerPC64 (little-endian)",
+        Size::Bits64,
+        Some(Endian::Little),
+        4,
+        u32::from_le_bytes,
+        POWERPC64,
+    ),
+    isa(
+        "PowerPC64 (big-endian)",
+        Size::Bits64,
+        Some(Endian::Big),
+        4,
+        u32::from_be_bytes,
+        POWERPC64,
+    ),
+    isa(
+        "RISC-V",
+        Size::Bits32,
+        Some(Endian::Little),
+        4,
+        u32::from_le_bytes,
+        RISCV,
+    ),
+    isa(
+        "RISC-V64",
+        Size::Bits64,
+        Some(Endian::Little),
+        4,
+        u32::from_le_bytes,
+        RISCV64,
+    ),
+    isa(
+        "x86",
+        Size::Bits32,
+        Some(Endian::Little),
+        1,
+        u32::from_be_bytes,
+        X86,
+    ),
+    isa(
+        "x86-64",
+        Size::Bits64,
+        Some(Endian::Little),
+        1,
+        u32::from_be_bytes,
+        X86_64,
+    ),
+];
+
+pub struct Detected {
+    pub name: &'static str,
+    pub size: Size,
+    pub endian: Endian,
+    pub score: f64,
+}
+
+impl Display for Detected {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(
+            f,
+            "{} ({}, {}-endian data, score: {:.0})",
+            self.name, self.size, self.endian, self.score
+        )
+    }
+}
+
+/* Count the occurrences of each signature, at every aligned offset. Each worker counts those starting
+in its own range of offsets, but reads past its end, so no word straddling two ranges is missed. */
+fn hits(bytes: &[u8], isa: &Isa) -> Vec<usize> {
+    let starts = bytes.len().saturating_sub(3);
+    (0..starts.div_ceil(CHUNK_SIZE))
+        .into_par_iter()
+        .map(|chunk| {
+            let mut hits = vec![0; isa.signatures.len()];
+            let start = chunk * CHUNK_SIZE;
+            for offset in (start..starts.min(start + CHUNK_SIZE)).step_by(isa.alignment) {
+                let word = (isa.read_word)(bytes[offset..offset + 4].try_into().unwrap());
+                for (idx, signature) in isa.signatures.iter().enumerate() {
+                    if word & signature.mask == signature.value {
+                        hits[idx] += 1;
+                    }
+                }
+            }
+            hits
+        })
+        .reduce(
+            || vec![0; isa.signatures.len()],
+            |a, b| a.iter().zip(b).map(|(a, b)| a + b).collect(),
+        )
+}
+
+/* Score how much more often the signatures occur than would be expected in random data */
+fn score(bytes: &[u8], isa: &Isa) -> f64 {
+    let hits = hits(bytes, isa);
+
+    let positions = (bytes.len() / isa.alignment) as f64;
+    isa.signatures
+        .iter()
+        .zip(hits)
+        .map(|(signature, hits)| {
+            let expected = positions / 2f64.powi(signature.mask.count_ones() as i32);
+            (hits as f64 - expected).max(0.0)
+        })
+        .sum()
+}
+
+/* Data is more likely to contain small integers than large ones, so whichever byte order yields
+more of them is likely to be the byte order of the image (when the instructions don't tell) */
+fn data_endian(bytes: &[u8]) -> Endian {
+    let (little, big) = bytes
+        .par_chunks_exact(4)
+        .map(|word| {
+            let word: [u8; 4] = word.try_into().unwrap();
+            let small = |value: u32| usize::from(value != 0 && value < 0x10000);
+            (
+                small(u32::from_le_bytes(word)),
+                small(u32::from_be_bytes(word)),
+            )
+        })
+        .reduce(|| (0, 0), |(l1, b1), (l2, b2)| (l1 + l2, b1 + b2));
+    if big > little {
+        Endian::Big
+    } else {
+        Endian::Little
+    }
+}
+
+pub fn detect(bytes: &[u8]) -> Option<Detected> {
+    let progress_bar = get_progress_bar("Detecting architecture", ISAS.len());
+    let (isa, score) = ISAS
+        .par_iter()
+        .progress_with(progress_bar)
+        .map(|isa| (isa, score(bytes, isa)))
+        .max_by(|(_, s1), (_, s2)| s1.total_cmp(s2))?;
+
+    if score < MIN_SCORE {
+        return None;
+    }
+
+    Some(Detected {
+        name: isa.name,
+        size: isa.size,
+        endian: isa.endian.unwrap_or_else(|| data_endian(bytes)),
+        score,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn isa(name: &str) -> &'static Isa {
+        ISAS.iter().find(|isa| isa.name == name).unwrap()
+    }
+
+    #[test]
+    fn hits_words_straddling_a_chunk() {
+        let mut bytes = vec![0; 2 * CHUNK_SIZE];
+        /* bx lr in the last halfword of the first chunk */
+        bytes[CHUNK_SIZE - 2..CHUNK_SIZE].copy_from_slice(&[0x70, 0x47]);
+        assert_eq!(hits(&bytes, isa("Thumb (little-endian)")), vec![1, 0, 0]);
+        /* push ebp; mov ebp, esp starting in the last byte of the first chunk */
+        bytes[CHUNK_SIZE - 1..CHUNK_SIZE + 3].copy_from_slice(&[0x55, 0x89, 0xe5, 0x00]);
+        assert_eq!(hits(&bytes, isa("x86")), vec![1, 0, 0]);
+    }
+
+    #[test]
+    fn hits_nothing_in_fewer_bytes_than_a_word() {
+        assert_eq!(
+            hits(&[0x70, 0x47], isa("Thumb (little-endian)")),
+            vec![0, 0, 0]
+        );
+    }
+}
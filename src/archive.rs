@@ -0,0 +1,264 @@
+/* Firmware is often distributed in archives (zip files and tarballs, which may be gzip compressed),
+so their members can be scanned in place rather than having to be extracted first. Each member which
+is a regular file and whose name matches a glob is scanned as if it were a file of its own. In the
+glob, * and ? match any characters except /, while ** matches any characters at all.
+
+Where each member is stored is found when listing them, so it can be read without reading the archive
+from the start again. A compressed tarball is decompressed to a temporary file once, which is deleted
+once all of its members have been scanned. */
+use {
+    flate2::read::GzDecoder,
+    regex::Regex,
+    std::{
+        fs::File,
+        io::{copy, BufReader, Read, Seek, SeekFrom},
+        path::Path,
+        sync::Arc,
+    },
+    tar::Archive,
+    tempfile::{Builder, NamedTempFile},
+    zip::ZipArchive,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn format(path: &str) -> Option<Format> {
+    let path = path.to_ascii_lowercase();
+    if path.ends_with(".zip") {
+        Some(Format::Zip)
+    } else if path.ends_with(".tar") {
+        Some(Format::Tar)
+    } else if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+        Some(Format::TarGz)
+    } else {
+        None
+    }
+}
+
+pub fn is_archive(path: &str) -> bool {
+    format(path).is_some()
+}
+
+/* A regex matching the same names as the glob */
+fn glob_regex(glob: &str) -> Regex {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                regex.push_str(".*");
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).unwrap()
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/* A member of an archive which is a regular file, and where it is stored */
+#[derive(Clone)]
+pub struct Member {
+    pub name: String,
+    location: Location,
+}
+
+#[derive(Clone)]
+enum Location {
+    /* The index of the member in a zip file */
+    Zip(usize),
+    /* The offset and length of the member in a tarball, or in the decompressed copy of one */
+    Tar {
+        copy: Option<Arc<NamedTempFile>>,
+        offset: u64,
+        len: u64,
+    },
+}
+
+/* The regular files in a tarball (which is decompressed to a temporary file first if need be) */
+fn tar_members(path: &str, format: Format) -> Result<Vec<Member>, String> {
+    let copy = match format {
+        Format::TarGz => {
+            let mut decompressed = Builder::new()
+                .prefix("rbase-")
+                .suffix(".tar")
+                .tempfile()
+                .map_err(|e| format!("temporary file: {e}"))?;
+            copy(
+                &mut GzDecoder::new(open(Path::new(path))?),
+                &mut decompressed,
+            )
+            .map_err(|e| format!("{path}: {e}"))?;
+            Some(Arc::new(decompressed))
+        }
+        _ => None,
+    };
+    let tarball = copy.as_ref().map_or(Path::new(path), |copy| copy.path());
+    let mut archive = Archive::new(open(tarball)?);
+    let mut members = Vec::new();
+    for entry in archive
+        .entries_with_seek()
+        .map_err(|e| format!("{path}: {e}"))?
+    {
+        let entry = entry.map_err(|e| format!("{path}: {e}"))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry
+            .path()
+            .map_err(|e| format!("{path}: {e}"))?
+            .to_string_lossy()
+            .into_owned();
+        members.push(Member {
+            name,
+            location: Location::Tar {
+                copy: copy.clone(),
+                offset: entry.raw_file_position(),
+                len: entry.size(),
+            },
+        });
+    }
+    Ok(members)
+}
+
+/* The regular files in the archive which match the glob, in the order they are stored */
+pub fn members(path: &str, glob: &str) -> Result<Vec<Member>, String> {
+    let regex = glob_regex(glob);
+    let mut members = match format(path) {
+        Some(Format::Zip) => {
+            let mut archive =
+                ZipArchive::new(open(Path::new(path))?).map_err(|e| format!("{path}: {e}"))?;
+            let mut members = Vec::new();
+            for idx in 0..archive.len() {
+                let member = archive
+                    .by_index_raw(idx)
+                    .map_err(|e| format!("{path}: {e}"))?;
+                if member.is_file() {
+                    members.push(Member {
+                        name: member.name().to_string(),
+                        location: Location::Zip(idx),
+                    });
+                }
+            }
+            members
+        }
+        Some(format) => tar_members(path, format)?,
+        None => return Err(format!("{path}: not a zip file or tarball")),
+    };
+    members.retain(|member| regex.is_match(&member.name));
+    Ok(members)
+}
+
+/* The contents of a member of the archive */
+pub fn read(path: &str, member: &Member) -> Result<Vec<u8>, String> {
+    let name = &member.name;
+    let mut bytes = Vec::new();
+    match &member.location {
+        Location::Zip(idx) => {
+            let mut archive =
+                ZipArchive::new(open(Path::new(path))?).map_err(|e| format!("{path}: {e}"))?;
+            archive
+                .by_index(*idx)
+                .map_err(|e| format!("{path}: {name}: {e}"))?
+                .read_to_end(&mut bytes)
+                .map_err(|e| format!("{path}: {name}: {e}"))?;
+        }
+        Location::Tar { copy, offset, len } => {
+            let tarball = copy.as_ref().map_or(Path::new(path), |copy| copy.path());
+            let mut reader = open(tarball)?;
+            reader
+                .seek(SeekFrom::Start(*offset))
+                .and_then(|_| reader.take(*len).read_to_end(&mut bytes))
+                .map_err(|e| format!("{path}: {name}: {e}"))?;
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        flate2::{write::GzEncoder, Compression},
+        std::io::Write,
+        zip::{write::SimpleFileOptions, ZipWriter},
+    };
+
+    const MEMBERS: [(&str, &[u8]); 3] = [
+        ("readme.txt", b"not firmware"),
+        ("fw/a.bin", b"first image"),
+        ("fw/sub/b.bin", b"second image"),
+    ];
+
+    fn tar_bytes() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in MEMBERS {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn read_all(path: &str, glob: &str) -> Vec<(String, Vec<u8>)> {
+        members(path, glob)
+            .unwrap()
+            .iter()
+            .map(|member| (member.name.clone(), read(path, member).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn glob_matches_within_or_across_directories() {
+        assert!(glob_regex("fw/*.bin").is_match("fw/a.bin"));
+        assert!(!glob_regex("fw/*.bin").is_match("fw/sub/b.bin"));
+        assert!(glob_regex("**.bin").is_match("fw/sub/b.bin"));
+        assert!(glob_regex("fw/?.bin").is_match("fw/a.bin"));
+        assert!(!glob_regex("fw/?.bin").is_match("fw/ab.bin"));
+        assert!(glob_regex("a+b.bin").is_match("a+b.bin"));
+    }
+
+    #[test]
+    fn members_of_each_format_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+
+        std::fs::write(path("t.tar"), tar_bytes()).unwrap();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&tar_bytes()).unwrap();
+        std::fs::write(path("t.tar.gz"), encoder.finish().unwrap()).unwrap();
+        let mut writer = ZipWriter::new(File::create(path("t.zip")).unwrap());
+        for (name, data) in MEMBERS {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let expected: Vec<(String, Vec<u8>)> = MEMBERS
+            .iter()
+            .map(|(name, data)| (name.to_string(), data.to_vec()))
+            .collect();
+        for name in ["t.tar", "t.tar.gz", "t.zip"] {
+            assert_eq!(read_all(&path(name), "**"), expected, "{name}");
+            assert_eq!(read_all(&path(name), "fw/*"), expected[1..2], "{name}");
+        }
+        assert!(members(&path("t.tar.bz2"), "**").is_err());
+    }
+}
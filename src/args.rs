@@ -0,0 +1,1307 @@
+use {
+    crate::{
+        anchor::{parse_anchor, parse_anchor_offset, Anchor, AnchorOffset},
+        arch,
+        format::FormatOption,
+        heuristic::HeuristicKind,
+        profile,
+        refine::DisasmArch,
+        sample::{SampleStrategy, Sampling},
+        script::parse_script,
+        strings::Terminated,
+        PAGE_SIZE,
+    },
+    clap::{error::ErrorKind, CommandFactory, Parser},
+    regex::bytes::Regex,
+    std::{
+        env,
+        fmt::{Display, Formatter, Result},
+        num::{ParseFloatError, ParseIntError},
+        time::Duration,
+    },
+};
+
+#[derive(Clone, Copy)]
+pub enum Size {
+    Bits24,
+    Bits32,
+    Bits64,
+}
+
+impl Display for Size {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            Size::Bits24 => write!(f, "24-bit"),
+            Size::Bits32 => write!(f, "32-bit"),
+            Size::Bits64 => write!(f, "64-bit"),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+impl Display for Endian {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            Endian::Little => write!(f, "little"),
+            Endian::Big => write!(f, "big"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum AddressRange {
+    Prefix { value: u64, digits: u32 },
+    Range { start: u64, end: u64 },
+}
+
+impl AddressRange {
+    /* Inclusive bounds of the range for an address of the given number of bits */
+    pub fn bounds(&self, bits: u32) -> (u64, u64) {
+        match *self {
+            AddressRange::Prefix { value, digits } => {
+                let shift = bits.saturating_sub(digits * 4);
+                if shift == 0 {
+                    (value, value)
+                } else {
+                    let start = value << shift;
+                    (start, start | (u64::MAX >> (64 - shift)))
+                }
+            }
+            AddressRange::Range { start, end } => (start, end),
+        }
+    }
+}
+
+impl Display for AddressRange {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            AddressRange::Prefix { value, digits } => {
+                write!(f, "0x{:0width$x}", value, width = *digits as usize)
+            }
+            AddressRange::Range { start, end } => write!(f, "0x{:x}..=0x{:x}", start, end),
+        }
+    }
+}
+
+fn parse_charset(s: &str) -> std::result::Result<String, String> {
+    Regex::new(&format!("[{s}]")).map_err(|e| e.to_string())?;
+    Ok(s.to_string())
+}
+
+fn parse_regex(s: &str) -> std::result::Result<String, String> {
+    Regex::new(s).map_err(|e| e.to_string())?;
+    Ok(s.to_string())
+}
+
+fn parse_bank_size(s: &str) -> std::result::Result<u64, String> {
+    let bank_size = parse_number(s).map_err(|e| e.to_string())?;
+    if !bank_size.is_power_of_two() {
+        return Err(format!("bank size 0x{bank_size:x} is not a power of two"));
+    }
+    Ok(bank_size)
+}
+
+fn parse_align(s: &str) -> std::result::Result<usize, String> {
+    let align = parse_nonzero(s)?;
+    if !align.is_power_of_two() {
+        return Err(format!("alignment {align} is not a power of two"));
+    }
+    Ok(align)
+}
+
+fn parse_page_size(s: &str) -> std::result::Result<u64, String> {
+    let page_size = parse_number(s).map_err(|e| e.to_string())?;
+    if !page_size.is_power_of_two() || page_size < PAGE_SIZE as u64 {
+        return Err(format!(
+            "page size 0x{page_size:x} is not a power of two of at least 0x{PAGE_SIZE:x}"
+        ));
+    }
+    Ok(page_size)
+}
+
+fn parse_chunk_size(s: &str) -> std::result::Result<u64, String> {
+    let chunk_size = parse_number(s).map_err(|e| e.to_string())?;
+    if chunk_size == 0 {
+        return Err("chunk size must be non-zero".to_string());
+    }
+    Ok(chunk_size)
+}
+
+fn parse_count(s: &str) -> std::result::Result<usize, String> {
+    let value = parse_number(s).map_err(|e| e.to_string())?;
+    usize::try_from(value).map_err(|e| e.to_string())
+}
+
+fn parse_nonzero(s: &str) -> std::result::Result<usize, String> {
+    let value = parse_count(s)?;
+    if value == 0 {
+        return Err("must be non-zero".to_string());
+    }
+    Ok(value)
+}
+
+fn parse_ratio(s: &str) -> std::result::Result<f64, String> {
+    let ratio: f64 = s.parse().map_err(|e: ParseFloatError| e.to_string())?;
+    if ratio.is_nan() || ratio < 1.0 {
+        return Err(format!("ratio {ratio} is below 1"));
+    }
+    Ok(ratio)
+}
+
+fn parse_percent(s: &str) -> std::result::Result<f64, String> {
+    let pct: f64 = s.parse().map_err(|e: ParseFloatError| e.to_string())?;
+    if !(0.0..=100.0).contains(&pct) {
+        return Err(format!("percentage {pct} is not between 0 and 100"));
+    }
+    Ok(pct)
+}
+
+/* A number of seconds, or of minutes or hours with an m or h suffix (e.g. 90, 30m or 2h) */
+fn parse_duration(s: &str) -> std::result::Result<Duration, String> {
+    let (number, unit) = match s.char_indices().last() {
+        Some((idx, 'h')) => (&s[..idx], 3600),
+        Some((idx, 'm')) => (&s[..idx], 60),
+        Some((idx, 's')) => (&s[..idx], 1),
+        _ => (s, 1),
+    };
+    let number = parse_number(number).map_err(|e| e.to_string())?;
+    match number {
+        0 => Err("duration must be non-zero".to_string()),
+        _ => Ok(Duration::from_secs(number.saturating_mul(unit))),
+    }
+}
+
+fn parse_address_range(s: &str) -> std::result::Result<AddressRange, String> {
+    if let Some((start, end)) = s.split_once("..") {
+        let start = parse_number(start).map_err(|e| e.to_string())?;
+        let end = match end.strip_prefix('=') {
+            Some(end) => parse_number(end).map_err(|e| e.to_string())?,
+            None => parse_number(end)
+                .map_err(|e| e.to_string())?
+                .checked_sub(1)
+                .ok_or("range end must be non-zero")?,
+        };
+        if start > end {
+            return Err(format!("range start 0x{start:x} is above end 0x{end:x}"));
+        }
+        Ok(AddressRange::Range { start, end })
+    } else {
+        let s = s.replace('_', "");
+        let hex = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .unwrap_or(&s);
+        let value = u64::from_str_radix(hex, 16).map_err(|e| e.to_string())?;
+        Ok(AddressRange::Prefix {
+            value,
+            digits: hex.len() as u32,
+        })
+    }
+}
+
+/* A range of file offsets, end exclusive */
+#[derive(Clone, Copy, Debug)]
+pub struct FileRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Display for FileRange {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "0x{:x}..0x{:x}", self.start, self.end)
+    }
+}
+
+/* A range of file offsets, either START..END or START..=END */
+fn parse_file_range(s: &str) -> std::result::Result<FileRange, String> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or("expected a range of file offsets, e.g. 0x10000..0x20000")?;
+    let start = parse_number(start).map_err(|e| e.to_string())? as usize;
+    let end = match end.strip_prefix('=') {
+        Some(end) => (parse_number(end).map_err(|e| e.to_string())? as usize).saturating_add(1),
+        None => parse_number(end).map_err(|e| e.to_string())? as usize,
+    };
+    if start >= end {
+        return Err(format!("range start 0x{start:x} isn't below end 0x{end:x}"));
+    }
+    Ok(FileRange { start, end })
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Slack {
+    Bytes(u64),
+    Percent(f64),
+}
+
+impl Slack {
+    /* Number of bytes beyond the end of an image of the given length which pointers may land */
+    pub fn bytes(&self, image_length: usize) -> u64 {
+        match *self {
+            Slack::Bytes(bytes) => bytes,
+            Slack::Percent(pct) => (image_length as f64 * pct / 100.0) as u64,
+        }
+    }
+}
+
+impl Display for Slack {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match self {
+            Slack::Bytes(bytes) => write!(f, "0x{:x} bytes", bytes),
+            Slack::Percent(pct) => write!(f, "{}%", pct),
+        }
+    }
+}
+
+fn parse_slack(s: &str) -> std::result::Result<Slack, String> {
+    match s.strip_suffix('%') {
+        Some(pct) => {
+            let pct: f64 = pct.parse().map_err(|e: ParseFloatError| e.to_string())?;
+            if pct < 0.0 {
+                return Err(format!("slack {pct}% is negative"));
+            }
+            Ok(Slack::Percent(pct))
+        }
+        None => Ok(Slack::Bytes(parse_number(s).map_err(|e| e.to_string())?)),
+    }
+}
+
+/* The size of each page of a raw NAND dump, and of the out-of-band bytes which follow it */
+#[derive(Clone, Copy, Debug)]
+pub struct NandLayout {
+    pub page_size: usize,
+    pub oob_size: usize,
+}
+
+impl Display for NandLayout {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}:{}", self.page_size, self.oob_size)
+    }
+}
+
+fn parse_nand_layout(s: &str) -> std::result::Result<NandLayout, String> {
+    let (page_size, oob_size) = s
+        .split_once(':')
+        .ok_or("expected page_size:oob_size (e.g. 2048:64)")?;
+    let page_size = parse_number(page_size).map_err(|e| e.to_string())? as usize;
+    let oob_size = parse_number(oob_size).map_err(|e| e.to_string())? as usize;
+    if page_size == 0 {
+        return Err("page size must be non-zero".to_string());
+    }
+    Ok(NandLayout {
+        page_size,
+        oob_size,
+    })
+}
+
+#[derive(Parser, Clone, Debug)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    #[arg(
+        help = "Names of the files to process, or directories of them (scanned in parallel if more than one)",
+        required = true
+    )]
+    pub filenames: Vec<String>,
+
+    #[arg(
+        long = "profile",
+        help = "Apply a named bundle of options (router-mips-be, stm32, aarch64-kernel or one defined in the config file), overridden by any given"
+    )]
+    pub profile: Option<String>,
+
+    #[arg(
+        long = "24",
+        help = "File is 24-bit",
+        conflicts_with_all = ["is_32bit", "is_64bit"]
+    )]
+    pub is_24bit: bool,
+
+    #[arg(
+        long = "32",
+        help = "File is 32-bit (default)",
+        conflicts_with_all = ["is_24bit", "is_64bit"]
+    )]
+    pub is_32bit: bool,
+
+    #[arg(
+        long = "64",
+        help = "File is 64-bit",
+        conflicts_with_all = ["is_24bit", "is_32bit"]
+    )]
+    pub is_64bit: bool,
+
+    #[arg(
+        long = "little",
+        help = "File is little-endian (default)",
+        conflicts_with = "is_big_endian"
+    )]
+    pub is_little_endian: bool,
+
+    #[arg(
+        long = "big",
+        help = "File is big-endian",
+        conflicts_with = "is_little_endian"
+    )]
+    pub is_big_endian: bool,
+
+    #[arg(
+        long = "align",
+        help = "Only read pointers at multiples of this many bytes (default: the size of an address, or 1 for 24-bit)",
+        value_parser = parse_align
+    )]
+    pub align: Option<usize>,
+
+    #[arg(
+        long = "page-size",
+        help = "Only consider base addresses which are multiples of this page size (a power of two, e.g. 0x10000 for images loaded at 64 KiB boundaries)",
+        default_value = "0x1000",
+        value_parser = parse_page_size
+    )]
+    pub page_size: u64,
+
+    #[arg(
+        long = "detect",
+        help = "Guess the architecture from how often common instruction encodings (e.g. prologues and returns) occur, using its size and endianness unless given"
+    )]
+    pub detect: bool,
+
+    #[arg(
+        long = "member",
+        help = "Only scan the members of zip files and tarballs whose names match this glob (e.g. 'firmware/*.bin')",
+        default_value = "**"
+    )]
+    pub member: String,
+
+    #[arg(
+        long = "no-mmap",
+        help = "Read the file into memory rather than mapping it (e.g. for network filesystems or files being written to)"
+    )]
+    pub no_mmap: bool,
+
+    #[arg(
+        long = "keep-headers",
+        help = "Don't skip vendor headers (e.g. uImage, TRX) at the start of the file"
+    )]
+    pub keep_headers: bool,
+
+    #[arg(
+        long = "sections",
+        help = "Lay out an ELF or PE file as it is loaded, so offsets are relative virtual addresses and the base found is the address it is linked at"
+    )]
+    pub sections: bool,
+
+    #[arg(
+        long = "nand-layout",
+        help = "Strip the out-of-band bytes from a raw NAND dump with this page and out-of-band size (e.g. 2048:64)",
+        value_parser = parse_nand_layout
+    )]
+    pub nand_layout: Option<NandLayout>,
+
+    #[arg(
+        long = "chunk-size",
+        help = "Size of the work units into which the file is split when searching for strings and addresses (e.g. 0x100000), by default roughly one per thread",
+        value_parser = parse_chunk_size
+    )]
+    pub chunk_size: Option<u64>,
+
+    #[arg(
+        long = "single-thread",
+        help = "Run on a single thread, so the results and diagnostic output are reproducible (e.g. for debugging or profiling)",
+        conflicts_with = "jobs"
+    )]
+    pub single_thread: bool,
+
+    #[arg(
+        short = 'j',
+        long = "jobs",
+        help = "Number of threads to use, by default one per CPU",
+        value_parser = parse_nonzero
+    )]
+    pub jobs: Option<usize>,
+
+    #[arg(
+        long = "gpu",
+        help = "Find strings and addresses on the GPU if there is one, treating the charset as a set of bytes (requires the gpu feature)"
+    )]
+    pub gpu: bool,
+
+    #[arg(
+        long = "numa",
+        help = "Bind each thread to the CPUs of one NUMA node, dividing the threads between the nodes (Linux only)"
+    )]
+    pub numa: bool,
+
+    #[arg(
+        long = "timeout",
+        help = "Stop correlating once this long has passed (e.g. 90, 30m or 2h), reporting partial results and exiting with code 2",
+        value_parser = parse_duration
+    )]
+    pub timeout: Option<Duration>,
+
+    #[arg(
+        long = "max-bytes",
+        help = "Only analyse the first this many bytes of the image, for a quick look at a huge one (the result is marked as partial)",
+        value_parser = parse_number
+    )]
+    pub max_bytes: Option<u64>,
+
+    #[arg(
+        long = "max-bytes-stride",
+        help = "Take the bytes analysed with --max-bytes from evenly spaced slices throughout the image, rather than its start",
+        requires = "max_bytes"
+    )]
+    pub max_bytes_stride: bool,
+
+    #[arg(long = "max", help = "Maximum string length", default_value = "1024", value_parser = parse_nonzero)]
+    pub max_string_length: usize,
+
+    #[arg(long = "min", help = "Minimum string length", default_value = "10", value_parser = parse_nonzero)]
+    pub min_string_length: usize,
+
+    #[arg(
+        long = "charset",
+        help = "Characters which may form a string, as the body of a regex character class",
+        default_value = "[:print:][:space:]",
+        value_parser = parse_charset
+    )]
+    pub charset: String,
+
+    #[arg(
+        long = "string-regex",
+        help = "Regex matching a string, overrides --charset, --min, --max (which is still used as the maximum match length) and --allow-mid-string",
+        value_parser = parse_regex
+    )]
+    pub string_regex: Option<String>,
+
+    #[arg(
+        long = "no-null-required",
+        help = "Don't require strings to be NUL terminated (e.g. for padded or length-prefixed strings)"
+    )]
+    pub no_null_required: bool,
+
+    #[arg(
+        long = "allow-mid-string",
+        help = "Allow strings to start in the middle of a longer run of characters"
+    )]
+    pub allow_mid_string: bool,
+
+    #[arg(
+        long = "string-include",
+        help = "Only use strings matching this regex (e.g. ^[A-Z_]+: to only use log messages)",
+        value_parser = parse_regex
+    )]
+    pub string_include: Option<String>,
+
+    #[arg(
+        long = "string-exclude",
+        help = "Don't use strings matching this regex (e.g. ^[A-Za-z0-9+/=]{40,}$ to skip Base64 blocks)",
+        value_parser = parse_regex
+    )]
+    pub string_exclude: Option<String>,
+
+    #[arg(
+        long = "weight-words",
+        help = "Weight strings containing dictionary words more heavily"
+    )]
+    pub weight_words: bool,
+
+    #[arg(
+        long = "wordlist",
+        help = "File of words (one per line) to use as the dictionary, implies --weight-words"
+    )]
+    pub wordlist: Option<String>,
+
+    #[arg(
+        long = "weight-format-strings",
+        help = "Weight strings containing printf-style format specifiers (e.g. %s or %08x) more heavily"
+    )]
+    pub weight_format_strings: bool,
+
+    #[arg(
+        long = "weight-paths",
+        help = "Weight strings containing file paths (e.g. /etc/passwd or C:\\Windows) or URLs more heavily"
+    )]
+    pub weight_paths: bool,
+
+    #[arg(
+        long = "heuristics",
+        help = "Comma separated list of heuristics used to find targets referenced by pointers",
+        value_delimiter = ',',
+        default_value = "strings"
+    )]
+    pub heuristics: Vec<HeuristicKind>,
+
+    #[arg(
+        long = "script",
+        help = "Rhai script defining score_string, accept_pointer and/or score_candidate hooks (requires the script feature)",
+        value_parser = parse_script
+    )]
+    pub script: Option<String>,
+
+    #[arg(
+        long = "enrich",
+        help = "Map the version banners found to CPE identifiers, using a database file of tab separated regex and CPE template pairs"
+    )]
+    pub enrich: Option<String>,
+
+    #[arg(
+        long = "dump-matched-strings",
+        help = "Write the strings referenced at the chosen base address to this file (gzip compressed if it ends in .gz)"
+    )]
+    pub dump_matched_strings: Option<String>,
+
+    #[arg(
+        long = "dump-pointers",
+        help = "Write the file offset and value of each candidate pointer to this file (gzip compressed if it ends in .gz)"
+    )]
+    pub dump_pointers: Option<String>,
+
+    #[arg(
+        long = "heatmap",
+        help = "Write an image of the density of strings (red) and pointers (green) in each region of the file to this PNG file"
+    )]
+    pub heatmap: Option<String>,
+
+    #[arg(
+        long = "plot-data",
+        help = "Write the score of each candidate base address and a summary of their distribution to this file (gzip compressed if it ends in .gz)"
+    )]
+    pub plot_data: Option<String>,
+
+    #[arg(
+        long = "emit-emu-map",
+        help = "Write a memory map of the image at the chosen base address, with guessed permissions, for emulators to this JSON file (or YAML if it ends in .yaml or .yml)"
+    )]
+    pub emit_emu_map: Option<String>,
+
+    #[arg(
+        long = "emit-qiling",
+        help = "Write a Qiling profile for loading the image at the chosen base address to this file"
+    )]
+    pub emit_qiling: Option<String>,
+
+    #[arg(
+        long = "emit-avatar2",
+        help = "Write an avatar2 script for loading the image at the chosen base address to this file"
+    )]
+    pub emit_avatar2: Option<String>,
+
+    #[arg(
+        long = "log-file",
+        help = "Write diagnostic output to this file, only printing the progress and the result"
+    )]
+    pub log_file: Option<String>,
+
+    #[arg(
+        short = 's',
+        long = "max-strings",
+        help = "Maximum number of strings (or other targets of each heuristic) to sample",
+        default_value = "100000",
+        value_parser = parse_nonzero
+    )]
+    pub max_strings: usize,
+
+    #[arg(
+        short = 'a',
+        long = "max-addresses",
+        help = "Maximum number of addresses to sample",
+        default_value = "1000000",
+        value_parser = parse_nonzero
+    )]
+    pub max_addresses: usize,
+
+    #[arg(
+        long = "max-per-page",
+        help = "Maximum number of strings (or other targets) and of addresses at each page offset, so no one page offset can dominate",
+        value_parser = parse_nonzero
+    )]
+    pub max_per_page: Option<usize>,
+
+    #[arg(
+        long = "keep-outlier-pages",
+        help = "Don't sample down page offsets with far more strings or addresses than is typical (e.g. from tables of fixed size records)"
+    )]
+    pub keep_outlier_pages: bool,
+
+    #[arg(
+        long = "sample-strategy",
+        help = "How to choose which strings and addresses to keep when there are more than the maximum",
+        default_value = "stride"
+    )]
+    pub sample_strategy: SampleStrategy,
+
+    #[arg(
+        long = "sample-seed",
+        help = "Seed for the reservoir sample strategy",
+        default_value = "0",
+        value_parser = parse_number
+    )]
+    pub sample_seed: u64,
+
+    #[arg(
+        long = "min-hits",
+        help = "Minimum number of times a candidate base address must occur",
+        default_value = "2",
+        value_parser = parse_count
+    )]
+    pub min_hits: usize,
+
+    #[arg(
+        long = "permutations",
+        help = "Number of permutation tests used to estimate the confidence in the result",
+        default_value = "0",
+        value_parser = parse_count
+    )]
+    pub permutations: usize,
+
+    #[arg(
+        long = "bootstrap",
+        help = "Number of random subsamples of the targets re-correlated to estimate how stable the best candidate is",
+        default_value = "0",
+        value_parser = parse_count
+    )]
+    pub bootstrap: usize,
+
+    #[arg(
+        long = "auto",
+        help = "Start with a tenth of the maximum strings and addresses, sampling ten times as many (up to all of them) while the best candidate is ambiguous",
+        conflicts_with = "checkpoint"
+    )]
+    pub auto: bool,
+
+    #[arg(
+        long = "early-stop",
+        help = "Stop correlating once the best candidate is this many times as frequent as the runner-up (e.g. 10)",
+        value_parser = parse_ratio
+    )]
+    pub early_stop: Option<f64>,
+
+    #[arg(
+        long = "approx",
+        help = "Estimate the frequency of each candidate using a fixed amount of memory, counting only the most frequent exactly",
+        conflicts_with = "early_stop"
+    )]
+    pub approx: bool,
+
+    #[arg(
+        long = "checkpoint",
+        help = "Periodically save the progress of the correlation to this file, resuming from it if it already exists",
+        conflicts_with = "approx"
+    )]
+    pub checkpoint: Option<String>,
+
+    #[arg(
+        long = "stream-results",
+        help = "Print the leading candidates as JSON lines while correlating",
+        conflicts_with = "approx"
+    )]
+    pub stream_results: bool,
+
+    #[arg(
+        long = "data-regions",
+        help = "Only search for addresses in regions which look like data, being dense with pointers to a consistent range"
+    )]
+    pub data_regions: bool,
+
+    #[arg(
+        long = "string-regions",
+        help = "Only search for strings in regions which look like string tables, being mostly characters in the charset"
+    )]
+    pub string_regions: bool,
+
+    #[arg(
+        long = "exclude-signatures",
+        help = "Also ignore compressed data and certificates recognised by their signatures"
+    )]
+    pub exclude_signatures: bool,
+
+    #[arg(
+        long = "keep-filesystems",
+        help = "Don't ignore filesystems (SquashFS, JFFS2, UBI or cramfs) found in the image"
+    )]
+    pub keep_filesystems: bool,
+
+    #[arg(
+        long = "yara",
+        help = "Run a file of YARA rules over the image (using the yara command line tool), reporting the regions they match"
+    )]
+    pub yara: Option<String>,
+
+    #[arg(
+        long = "yara-include",
+        help = "Comma separated list of YARA rules, only search the regions they match",
+        value_delimiter = ',',
+        requires = "yara"
+    )]
+    pub yara_include: Vec<String>,
+
+    #[arg(
+        long = "yara-exclude",
+        help = "Comma separated list of YARA rules, ignore the regions they match (e.g. certificates or compressed data)",
+        value_delimiter = ',',
+        requires = "yara"
+    )]
+    pub yara_exclude: Vec<String>,
+
+    #[arg(
+        long = "keep-padding",
+        help = "Don't skip padding (runs of 0x00 or 0xff, as in erased flash)"
+    )]
+    pub keep_padding: bool,
+
+    #[arg(
+        long = "ignore-range",
+        help = "A range of file offsets to ignore (e.g. a certificate store at 0x10000..0x20000), along with the rest of the 4 KiB windows it overlaps (may be repeated)",
+        value_parser = parse_file_range
+    )]
+    pub ignore_ranges: Vec<FileRange>,
+
+    #[arg(
+        long = "coarse",
+        help = "Find the most likely base addresses aligned to 64 KiB first (quicker, but missing any base address not so aligned), then only search the 64 KiB regions they start"
+    )]
+    pub coarse: bool,
+
+    #[arg(
+        long = "use-known-bases",
+        help = "Boost candidates matching the load address of a known SoC, boot loader or kernel"
+    )]
+    pub use_known_bases: bool,
+
+    #[arg(
+        long = "use-device-tree",
+        help = "Only consider base addresses inside the memory described by a device tree found in the image"
+    )]
+    pub use_device_tree: bool,
+
+    #[arg(
+        long = "refine",
+        help = "Re-rank the top candidates by disassembling the image and counting the pointers and absolute jumps which land on its functions (requires the disasm feature)",
+        ignore_case = true
+    )]
+    pub refine: Option<DisasmArch>,
+
+    #[arg(
+        long = "refine-top",
+        help = "Number of candidates to re-rank by disassembly",
+        default_value = "5",
+        value_parser = parse_nonzero
+    )]
+    pub refine_top: usize,
+
+    #[arg(
+        long = "min-landing",
+        help = "Minimum percentage of the addresses landing inside the image at a candidate base address which must land on a string (or other target)",
+        value_parser = parse_percent
+    )]
+    pub min_landing_pct: Option<f64>,
+
+    #[arg(
+        long = "min-score-pct",
+        help = "Also report every other candidate with at least this percentage of the total score (e.g. for images with several segments)",
+        value_parser = parse_percent
+    )]
+    pub min_score_pct: Option<f64>,
+
+    #[arg(
+        long = "slack",
+        help = "How far beyond the end of the image addresses may land, in bytes or as a percentage of the image size (e.g. 0x100000 or 25%)",
+        default_value = "0",
+        value_parser = parse_slack
+    )]
+    pub slack: Slack,
+
+    #[arg(
+        long = "pointer-mask",
+        help = "Mask applied to each address before indexing (e.g. 0x00FFFFFFFFFFFFFF), truncated to the size of an address",
+        value_parser = parse_number
+    )]
+    pub pointer_mask: Option<u64>,
+
+    #[arg(
+        long = "require-prefix",
+        help = "Only consider addresses with this hex prefix (e.g. 0x80) or in this range (e.g. 0x80000000..0xA0000000)",
+        value_parser = parse_address_range
+    )]
+    pub require_prefix: Option<AddressRange>,
+
+    #[arg(
+        long = "addr-max",
+        help = "Ignore addresses above this value (e.g. the top of the device's RAM and flash)",
+        value_parser = parse_number
+    )]
+    pub addr_max: Option<u64>,
+
+    #[arg(
+        long = "pointer-min",
+        help = "Ignore addresses below this value, which are more likely to be lengths, counters or enum values (0 to only ignore zero), including when finding pointers to pointers and data regions",
+        default_value = "0x1000",
+        value_parser = parse_number
+    )]
+    pub pointer_min: u64,
+
+    #[arg(
+        long = "ignore-pointer",
+        help = "A value which isn't a pointer (e.g. a magic number), in addition to the common fill patterns and canaries always ignored (may be repeated)",
+        value_parser = parse_number
+    )]
+    pub ignore_pointers: Vec<u64>,
+
+    #[arg(
+        long = "svd",
+        help = "Ignore pointers to the peripheral registers described by this CMSIS-SVD file"
+    )]
+    pub svd: Option<String>,
+
+    #[arg(
+        long = "symbols",
+        help = "Re-rank the top candidates by how many symbols of a related build (a System.map, nm output or linker map) land on plausible structures in the image"
+    )]
+    pub symbols: Option<String>,
+
+    #[arg(
+        long = "compare",
+        help = "Compare the candidates with the output of another basefind tool (e.g. basefind.py or rbasefind) saved to this file"
+    )]
+    pub compare: Option<String>,
+
+    #[arg(
+        long = "explain",
+        help = "Break down the evidence for each of the top candidates: the targets referenced, the page offsets they were found at and a few examples"
+    )]
+    pub explain: bool,
+
+    #[arg(
+        long = "format",
+        help = "Comma separated list of options for how addresses are written in results and reports (e.g. pad,upper or dec)",
+        value_delimiter = ','
+    )]
+    pub format: Vec<FormatOption>,
+
+    #[arg(
+        long = "bank-size",
+        help = "Size of each bank for bank-switched images, addresses and offsets are taken within the bank (e.g. 0x8000)",
+        value_parser = parse_bank_size
+    )]
+    pub bank_size: Option<u64>,
+
+    #[arg(
+        long = "anchor",
+        help = "A string known to be at an address (e.g. \"Booting Linux\"=0xc0801234), discarding candidates which don't put it there (may be repeated)",
+        value_parser = parse_anchor
+    )]
+    pub anchors: Vec<Anchor>,
+
+    #[arg(
+        long = "anchor-offset",
+        help = "A file offset known to be loaded at an address (e.g. a reset vector found by hand, 0x100=0x08000100), discarding candidates which don't load it there (may be repeated)",
+        value_parser = parse_anchor_offset
+    )]
+    pub anchor_offsets: Vec<AnchorOffset>,
+}
+
+/* A decimal or 0x-prefixed hex number, which may have _ between digits (e.g. 0xffff_0000) */
+pub fn parse_number(s: &str) -> std::result::Result<u64, ParseIntError> {
+    let s = s.replace('_', "");
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+}
+
+impl Args {
+    /* Parse the command line, exiting with an error for combinations of options which are each
+    valid alone but not together */
+    pub fn parse_valid() -> Args {
+        let mut args = Args::parse();
+        if let Some(name) = &args.profile {
+            let argv: Vec<_> = env::args_os().collect();
+            match profile::expand(name, &argv, &mut Args::command()) {
+                Ok(argv) => args = Args::parse_from(argv),
+                Err(msg) => Args::command().error(ErrorKind::InvalidValue, msg).exit(),
+            }
+        }
+        if let Err(msg) = args.validate() {
+            Args::command()
+                .error(ErrorKind::ArgumentConflict, msg)
+                .exit();
+        }
+        args
+    }
+
+    fn validate(&self) -> std::result::Result<(), String> {
+        if self.min_string_length > self.max_string_length {
+            return Err(format!(
+                "--min {} is above --max {}",
+                self.min_string_length, self.max_string_length
+            ));
+        }
+        if self.gpu && !cfg!(feature = "gpu") {
+            return Err("rbase was built without the gpu feature".to_string());
+        }
+        if self.refine.is_some() && !cfg!(feature = "disasm") {
+            return Err("rbase was built without the disasm feature".to_string());
+        }
+        Regex::new(&self.string_regex())
+            .map_err(|e| format!("invalid string regex {}: {e}", self.string_regex()))?;
+        Ok(())
+    }
+
+    /* Bytes in the charset */
+    pub fn charset_bytes(&self) -> [bool; 256] {
+        let re = Regex::new(&format!("^[{}]$", self.charset)).unwrap();
+        std::array::from_fn(|b| re.is_match(&[b as u8]))
+    }
+
+    /* Bytes which may form part of a string, a string can only start after a byte which isn't. The
+    matches of a regex given by the user are kept as they are, since it needn't match the charset. */
+    pub fn string_bytes(&self) -> Option<[bool; 256]> {
+        (!self.allow_mid_string && self.string_regex.is_none()).then(|| self.charset_bytes())
+    }
+
+    /* The strings matched by the default regex, unless the regex doesn't require a NUL */
+    pub fn terminated(&self) -> Option<Terminated> {
+        let charset = self.charset_bytes();
+        (self.string_regex.is_none() && !self.no_null_required && !charset[0]).then_some(
+            Terminated {
+                charset,
+                min: self.min_string_length,
+                max: self.max_string_length,
+                allow_mid_string: self.allow_mid_string,
+            },
+        )
+    }
+
+    pub fn string_regex(&self) -> String {
+        match &self.string_regex {
+            Some(regex) => regex.clone(),
+            None => format!(
+                "([{}]{{{},{}}}){}",
+                self.charset,
+                self.min_string_length,
+                self.max_string_length,
+                if self.no_null_required { "" } else { "\\x00" }
+            ),
+        }
+    }
+
+    /* Apply the detected size and endianness, unless given by the user */
+    pub fn apply_detected(&mut self, detected: &arch::Detected) {
+        self.apply_layout(detected.size, detected.endian);
+    }
+
+    /* Likewise for the size and endianness given by the format of the file (e.g. an ELF core file) */
+    pub fn apply_layout(&mut self, size: Size, endian: Endian) {
+        if !(self.is_24bit || self.is_32bit || self.is_64bit) {
+            match size {
+                Size::Bits24 => self.is_24bit = true,
+                Size::Bits32 => self.is_32bit = true,
+                Size::Bits64 => self.is_64bit = true,
+            }
+        }
+        if !(self.is_little_endian || self.is_big_endian) {
+            match endian {
+                Endian::Little => self.is_little_endian = true,
+                Endian::Big => self.is_big_endian = true,
+            }
+        }
+    }
+
+    /* An option which can't be used when scanning several files at once, since each scan would
+    write to the same file */
+    pub fn batch_conflict(&self) -> Option<&'static str> {
+        [
+            (
+                "--dump-matched-strings",
+                self.dump_matched_strings.is_some(),
+            ),
+            ("--dump-pointers", self.dump_pointers.is_some()),
+            ("--heatmap", self.heatmap.is_some()),
+            ("--plot-data", self.plot_data.is_some()),
+            ("--emit-emu-map", self.emit_emu_map.is_some()),
+            ("--emit-qiling", self.emit_qiling.is_some()),
+            ("--emit-avatar2", self.emit_avatar2.is_some()),
+            ("--checkpoint", self.checkpoint.is_some()),
+            ("--stream-results", self.stream_results),
+        ]
+        .into_iter()
+        .find(|&(_option, given)| given)
+        .map(|(option, _given)| option)
+    }
+
+    pub fn sampling(&self) -> Sampling {
+        Sampling {
+            strategy: self.sample_strategy,
+            seed: self.sample_seed,
+            max_per_page: self.max_per_page,
+            keep_outlier_pages: self.keep_outlier_pages,
+        }
+    }
+
+    /* Number of threads to use, if not the default */
+    pub fn threads(&self) -> Option<usize> {
+        match self.single_thread {
+            true => Some(1),
+            false => self.jobs,
+        }
+    }
+
+    pub fn size(&self) -> Size {
+        if self.is_24bit {
+            Size::Bits24
+        } else if self.is_64bit {
+            Size::Bits64
+        } else {
+            Size::Bits32
+        }
+    }
+
+    pub fn endian(&self) -> Endian {
+        if self.is_big_endian {
+            Endian::Big
+        } else {
+            Endian::Little
+        }
+    }
+}
+
+impl Display for Args {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        writeln!(f, "ARGS")?;
+        writeln!(f, "\tfile: {}", self.filenames.join(", "))?;
+        match &self.profile {
+            Some(name) => writeln!(f, "\tprofile: {}", name)?,
+            None => writeln!(f, "\tprofile: none")?,
+        }
+        writeln!(f, "\tsize: {:}", self.size())?;
+        writeln!(f, "\tendian: {:}", self.endian())?;
+        match self.align {
+            Some(align) => writeln!(f, "\talign: {}", align)?,
+            None => writeln!(f, "\talign: default")?,
+        }
+        writeln!(f, "\tpage size: 0x{:x}", self.page_size)?;
+        writeln!(f, "\tmember: {}", self.member)?;
+        writeln!(f, "\tno mmap: {}", self.no_mmap)?;
+        writeln!(f, "\tkeep headers: {}", self.keep_headers)?;
+        writeln!(f, "\tsections: {}", self.sections)?;
+        match self.nand_layout {
+            Some(layout) => writeln!(f, "\tnand layout: {}", layout)?,
+            None => writeln!(f, "\tnand layout: none")?,
+        }
+        writeln!(f, "\tmax: {}", self.max_string_length)?;
+        writeln!(f, "\tmin: {}", self.min_string_length)?;
+        writeln!(f, "\tstring regex: {}", self.string_regex())?;
+        writeln!(f, "\tallow mid string: {}", self.string_bytes().is_none())?;
+        for (name, regex) in [
+            ("string include", &self.string_include),
+            ("string exclude", &self.string_exclude),
+        ] {
+            match regex {
+                Some(regex) => writeln!(f, "\t{name}: {}", regex)?,
+                None => writeln!(f, "\t{name}: none")?,
+            }
+        }
+        match &self.wordlist {
+            Some(wordlist) => writeln!(f, "\tweight words: {}", wordlist)?,
+            None => writeln!(f, "\tweight words: {}", self.weight_words)?,
+        }
+        writeln!(f, "\tweight format strings: {}", self.weight_format_strings)?;
+        writeln!(f, "\tweight paths: {}", self.weight_paths)?;
+        writeln!(
+            f,
+            "\theuristics: {}",
+            self.heuristics
+                .iter()
+                .map(|kind| kind.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )?;
+        match &self.script {
+            Some(script) => writeln!(f, "\tscript: {}", script)?,
+            None => writeln!(f, "\tscript: none")?,
+        }
+        match &self.enrich {
+            Some(enrich) => writeln!(f, "\tenrich: {}", enrich)?,
+            None => writeln!(f, "\tenrich: none")?,
+        }
+        writeln!(f, "\tmax strings: {}", self.max_strings)?;
+        writeln!(f, "\tmax addresses: {}", self.max_addresses)?;
+        match self.max_per_page {
+            Some(max) => writeln!(f, "\tmax per page: {}", max)?,
+            None => writeln!(f, "\tmax per page: none")?,
+        }
+        writeln!(f, "\tkeep outlier pages: {}", self.keep_outlier_pages)?;
+        match self.sample_strategy {
+            SampleStrategy::Reservoir => writeln!(
+                f,
+                "\tsample strategy: reservoir (seed {})",
+                self.sample_seed
+            )?,
+            strategy => writeln!(f, "\tsample strategy: {}", strategy)?,
+        }
+        writeln!(f, "\tsingle thread: {}", self.single_thread)?;
+        match self.jobs {
+            Some(jobs) => writeln!(f, "\tjobs: {}", jobs)?,
+            None => writeln!(f, "\tjobs: default")?,
+        }
+        writeln!(f, "\tnuma: {}", self.numa)?;
+        match self.timeout {
+            Some(timeout) => writeln!(f, "\ttimeout: {:?}", timeout)?,
+            None => writeln!(f, "\ttimeout: none")?,
+        }
+        match self.max_bytes {
+            Some(max_bytes) => writeln!(f, "\tmax bytes: 0x{:x}", max_bytes)?,
+            None => writeln!(f, "\tmax bytes: none")?,
+        }
+        writeln!(f, "\tmax bytes stride: {}", self.max_bytes_stride)?;
+        writeln!(f, "\tgpu: {}", self.gpu)?;
+        match self.chunk_size {
+            Some(chunk_size) => writeln!(f, "\tchunk size: 0x{:x}", chunk_size)?,
+            None => writeln!(f, "\tchunk size: default")?,
+        }
+        writeln!(f, "\tmin hits: {}", self.min_hits)?;
+        writeln!(f, "\tpermutations: {}", self.permutations)?;
+        writeln!(f, "\tbootstrap: {}", self.bootstrap)?;
+        writeln!(f, "\tauto: {}", self.auto)?;
+        match self.early_stop {
+            Some(ratio) => writeln!(f, "\tearly stop: {}x", ratio)?,
+            None => writeln!(f, "\tearly stop: none")?,
+        }
+        writeln!(f, "\tapprox: {}", self.approx)?;
+        match &self.checkpoint {
+            Some(path) => writeln!(f, "\tcheckpoint: {}", path)?,
+            None => writeln!(f, "\tcheckpoint: none")?,
+        }
+        writeln!(f, "\tstream results: {}", self.stream_results)?;
+        writeln!(f, "\tdata regions: {}", self.data_regions)?;
+        writeln!(f, "\tstring regions: {}", self.string_regions)?;
+        writeln!(f, "\texclude signatures: {}", self.exclude_signatures)?;
+        writeln!(f, "\tkeep filesystems: {}", self.keep_filesystems)?;
+        match &self.yara {
+            Some(yara) => writeln!(f, "\tyara: {}", yara)?,
+            None => writeln!(f, "\tyara: none")?,
+        }
+        for (name, rules) in [
+            ("yara include", &self.yara_include),
+            ("yara exclude", &self.yara_exclude),
+        ] {
+            match rules.is_empty() {
+                true => writeln!(f, "\t{name}: none")?,
+                false => writeln!(f, "\t{name}: {}", rules.join(", "))?,
+            }
+        }
+        writeln!(f, "\tkeep padding: {}", self.keep_padding)?;
+        match self.ignore_ranges.is_empty() {
+            true => writeln!(f, "\tignore ranges: none")?,
+            false => writeln!(
+                f,
+                "\tignore ranges: {}",
+                self.ignore_ranges
+                    .iter()
+                    .map(|range| range.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )?,
+        }
+        writeln!(f, "\tcoarse: {}", self.coarse)?;
+        writeln!(f, "\tuse known bases: {}", self.use_known_bases)?;
+        writeln!(f, "\tuse device tree: {}", self.use_device_tree)?;
+        match self.refine {
+            Some(arch) => writeln!(f, "\trefine: {} (top {})", arch, self.refine_top)?,
+            None => writeln!(f, "\trefine: none")?,
+        }
+        match self.min_landing_pct {
+            Some(pct) => writeln!(f, "\tmin landing: {}%", pct)?,
+            None => writeln!(f, "\tmin landing: none")?,
+        }
+        match self.min_score_pct {
+            Some(pct) => writeln!(f, "\tmin score: {}%", pct)?,
+            None => writeln!(f, "\tmin score: none")?,
+        }
+        writeln!(f, "\tslack: {}", self.slack)?;
+        match self.pointer_mask {
+            Some(mask) => writeln!(f, "\tpointer mask: 0x{:x}", mask)?,
+            None => writeln!(f, "\tpointer mask: none")?,
+        }
+        match self.require_prefix {
+            Some(range) => writeln!(f, "\trequire prefix: {}", range)?,
+            None => writeln!(f, "\trequire prefix: none")?,
+        }
+        match self.addr_max {
+            Some(addr_max) => writeln!(f, "\taddr max: 0x{:x}", addr_max)?,
+            None => writeln!(f, "\taddr max: none")?,
+        }
+        writeln!(f, "\tpointer min: 0x{:x}", self.pointer_min)?;
+        match self.ignore_pointers.is_empty() {
+            true => writeln!(f, "\tignore pointers: none")?,
+            false => writeln!(
+                f,
+                "\tignore pointers: {}",
+                self.ignore_pointers
+                    .iter()
+                    .map(|value| format!("0x{:x}", value))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )?,
+        }
+        match &self.svd {
+            Some(path) => writeln!(f, "\tsvd: {}", path)?,
+            None => writeln!(f, "\tsvd: none")?,
+        }
+        match &self.symbols {
+            Some(path) => writeln!(f, "\tsymbols: {}", path)?,
+            None => writeln!(f, "\tsymbols: none")?,
+        }
+        match &self.compare {
+            Some(path) => writeln!(f, "\tcompare: {}", path)?,
+            None => writeln!(f, "\tcompare: none")?,
+        }
+        writeln!(f, "\texplain: {}", self.explain)?;
+        match self.format.is_empty() {
+            true => writeln!(f, "\tformat: default")?,
+            false => writeln!(
+                f,
+                "\tformat: {}",
+                self.format
+                    .iter()
+                    .map(|option| option.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )?,
+        }
+        match self.bank_size {
+            Some(bank_size) => writeln!(f, "\tbank size: 0x{:x}", bank_size)?,
+            None => writeln!(f, "\tbank size: none")?,
+        }
+        match self.anchors.is_empty() {
+            true => writeln!(f, "\tanchors: none")?,
+            false => writeln!(
+                f,
+                "\tanchors: {}",
+                self.anchors
+                    .iter()
+                    .map(|anchor| anchor.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )?,
+        }
+        match self.anchor_offsets.is_empty() {
+            true => writeln!(f, "\tanchor offsets: none")?,
+            false => writeln!(
+                f,
+                "\tanchor offsets: {}",
+                self.anchor_offsets
+                    .iter()
+                    .map(|anchor| anchor.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+/* An AArch64 Linux kernel Image starts with a header giving the offset of the image from the start
+of a 2 MiB aligned region of RAM at which it must be loaded (text_offset), and the size of the image
+in memory. The kernel's virtual address space follows the same rule, so the base address of the
+image (whether physical or virtual) is a 2 MiB aligned address plus the text offset. */
+
+/* Alignment of the region into which the kernel is loaded */
+const KERNEL_ALIGN: u64 = 0x200000;
+
+/* Candidates consistent with the text offset are ranked as if their score were multiplied by this */
+pub const TEXT_OFFSET_BOOST: usize = 2;
+
+/* Before Linux 3.17, text_offset could be in either endianness, and image_size was zero. These
+kernels always used a text offset of 0x80000. */
+const LEGACY_TEXT_OFFSET: u64 = 0x80000;
+
+pub struct KernelImage {
+    pub text_offset: u64,
+    pub image_size: u64,
+    pub big_endian: bool,
+    pub page_size: Option<u64>,
+}
+
+impl KernelImage {
+    /* Whether the base address is a 2 MiB aligned address plus the text offset */
+    pub fn is_consistent(&self, base: u64) -> bool {
+        base % KERNEL_ALIGN == self.text_offset % KERNEL_ALIGN
+    }
+
+    /* The start of the region of RAM into which the image is loaded at the base address */
+    pub fn ram_base(&self, base: u64) -> u64 {
+        base.wrapping_sub(self.text_offset)
+    }
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    let word = bytes.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(word.try_into().unwrap()))
+}
+
+/* The header at the start of the image, whose fields are little-endian whatever the endianness of
+the kernel */
+pub fn find(bytes: &[u8]) -> Option<KernelImage> {
+    const MAGIC: &[u8] = b"ARM\x64";
+    const MAGIC_OFFSET: usize = 56;
+    if bytes.get(MAGIC_OFFSET..MAGIC_OFFSET + MAGIC.len())? != MAGIC {
+        return None;
+    }
+    let image_size = read_u64(bytes, 16)?;
+    let flags = read_u64(bytes, 24)?;
+    if image_size == 0 {
+        return Some(KernelImage {
+            text_offset: LEGACY_TEXT_OFFSET,
+            image_size,
+            big_endian: false,
+            page_size: None,
+        });
+    }
+    Some(KernelImage {
+        text_offset: read_u64(bytes, 8)?,
+        image_size,
+        big_endian: flags & 1 != 0,
+        page_size: match (flags >> 1) & 3 {
+            1 => Some(0x1000),
+            2 => Some(0x4000),
+            3 => Some(0x10000),
+            _ => None,
+        },
+    })
+}
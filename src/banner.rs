@@ -0,0 +1,65 @@
+/* Version banners, such as the kernel's "Linux version" string or the compiler's identification,
+give immediate context about the firmware being analysed (what it is, how old it is and what it
+was built with), so they are reported alongside the base address. */
+use regex::bytes::Regex;
+
+/* Each kind of banner, with a regex matching it up to the end of its line or string */
+const BANNERS: [(&str, &str); 12] = [
+    ("Linux", r"Linux version \d+\.\d+"),
+    ("U-Boot", r"U-Boot (SPL |TPL )?\d{4}\.\d{2}"),
+    ("GCC", r"GCC: \("),
+    ("Clang", r"clang version \d+\.\d+"),
+    ("BusyBox", r"BusyBox v\d+\.\d+"),
+    ("OpenSSL", r"OpenSSL \d+\.\d+\.\d+"),
+    ("CFE", r"CFE version \d+\.\d+"),
+    ("RedBoot", r"RedBoot\(tm\) "),
+    ("VxWorks", r"VxWorks ?\d+\.\d+"),
+    ("FreeRTOS", r"FreeRTOS V\d+\.\d+"),
+    ("Zephyr", r"Zephyr OS build "),
+    ("Copyright", r"(?i:copyright) (\([cC]\) )?(\d{4}|[A-Z])"),
+];
+
+/* Limits on the length of a banner reported, and the number of each kind */
+const MAX_BANNER_LENGTH: usize = 200;
+const MAX_BANNERS_PER_KIND: usize = 8;
+
+pub struct Banner {
+    pub kind: &'static str,
+    pub offset: usize,
+    pub text: String,
+}
+
+/* The distinct banners in the image, in the order in which they appear */
+pub fn find(bytes: &[u8]) -> Vec<Banner> {
+    let pattern = BANNERS
+        .iter()
+        .enumerate()
+        .map(|(idx, (_kind, regex))| {
+            format!("(?P<b{idx}>{regex}[\\x20-\\x7e]{{0,{MAX_BANNER_LENGTH}}})")
+        })
+        .collect::<Vec<String>>()
+        .join("|");
+    let regex = Regex::new(&format!("(?-u){pattern}")).unwrap();
+    let mut banners: Vec<Banner> = Vec::new();
+    for captures in regex.captures_iter(bytes) {
+        let Some((idx, m)) =
+            (0..BANNERS.len()).find_map(|idx| Some((idx, captures.name(&format!("b{idx}"))?)))
+        else {
+            continue;
+        };
+        let kind = BANNERS[idx].0;
+        let text = String::from_utf8_lossy(m.as_bytes()).trim().to_string();
+        let seen = banners.iter().filter(|banner| banner.kind == kind);
+        if seen.clone().count() == MAX_BANNERS_PER_KIND
+            || seen.clone().any(|banner| banner.text == text)
+        {
+            continue;
+        }
+        banners.push(Banner {
+            kind,
+            offset: m.start(),
+            text,
+        });
+    }
+    banners
+}
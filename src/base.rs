@@ -0,0 +1,1825 @@
+use {
+    crate::{
+        addresses::{get_addresses_by_page_offset, get_pointers, pointer_alignment, sentinels},
+        args::Args,
+        arm64, checkpoint, collect_by_key, compare, dump, emu, explain, fdt, fill, format, heatmap,
+        heuristic::{
+            index_targets, merge, Heuristic, HeuristicKind, Pointers, Strings, TargetIndex,
+        },
+        known,
+        logging::log,
+        pack_buckets,
+        packed::{Pack, PackedVec},
+        progress::{get_progress_bar, ParallelProgressIterator},
+        refine,
+        regions::{self, Regions},
+        script::Script,
+        signature,
+        sketch::CountMinSketch,
+        svd::Svd,
+        symbols::Symbols,
+        timeout, uboot,
+        weight::StringWeigher,
+        yara, PageIndex, RBaseTraits, PAGE_OFFSET_MASK, PAGE_SIZE,
+    },
+    rand::{rngs::StdRng, Rng, SeedableRng},
+    rayon::{
+        iter::{
+            IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator,
+            ParallelIterator,
+        },
+        slice::ParallelSliceMut,
+    },
+    rustc_hash::FxHashMap,
+    std::{
+        cmp::{Ordering, Reverse},
+        collections::BinaryHeap,
+        fmt::{Display, Formatter, Result},
+        fs::remove_file,
+        io::ErrorKind,
+        time::{Duration, Instant},
+    },
+};
+
+/* Granularity and number of the regions searched when using a coarse-to-fine sweep */
+const COARSE_PAGE_OFFSET_MASK: usize = 0xFFFF;
+const COARSE_REGIONS: usize = 3;
+
+/* Number of interleaved batches of page offsets correlated when stopping early, and the minimum
+frequency of the best candidate before doing so */
+const EARLY_STOP_BATCHES: usize = 16;
+const EARLY_STOP_MIN_FREQUENCY: usize = 100;
+
+/* Number of batches of page offsets correlated when checkpointing, and the minimum time between
+saving checkpoints (since the candidates totalled so far can be large) */
+const CHECKPOINT_BATCHES: usize = 64;
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+
+/* When approximating, candidates are only counted exactly if their estimated frequency is at least
+this fraction of the total */
+const APPROX_HEAVY_HITTERS: usize = 0x10000;
+
+/* Number of candidates printed */
+const TOP_CANDIDATES: usize = 10;
+
+/* In auto mode, samples start this many times smaller than the maximum and grow by the same factor
+while the best candidate isn't at least as many times as frequent as the runner-up, or doesn't stay
+on top in most bootstrap samples */
+const AUTO_GROWTH: usize = 10;
+const AUTO_DOMINANCE: f64 = 2.0;
+const AUTO_BOOTSTRAP: usize = 10;
+const AUTO_MIN_STAYS: usize = 9;
+
+/* Number of strings referenced at the chosen base printed with it, and the maximum length of each */
+const EXAMPLE_STRINGS: usize = 5;
+const MAX_EXAMPLE_LENGTH: usize = 60;
+
+/* Subtract the target offsets from the addresses to determine candidate base addresses. Returns
+the frequency of each candidate base address, sorted by base address. */
+pub fn correlate<T: RBaseTraits<T, N>, const N: usize>(
+    msg: &'static str,
+    targets_index: &TargetIndex<T>,
+    addresses_index: &PageIndex<T>,
+) -> Vec<(T, usize)> {
+    correlate_by(
+        msg,
+        targets_index,
+        addresses_index,
+        Some,
+        Correlation::default(),
+    )
+}
+
+/* How the candidates are counted during correlation */
+#[derive(Clone, Copy, Default)]
+enum Counting {
+    #[default]
+    Exact,
+    /* Stop once the best candidate dominates the runner-up by the given ratio */
+    EarlyStop(f64),
+    /* Estimate the frequencies using a sketch, only counting those which may reach the minimum */
+    Approx(usize),
+}
+
+/* How the correlation is carried out. Exact counts can be checkpointed to the given file (and
+resumed from it), and the leading candidates can be streamed as the correlation progresses. */
+#[derive(Clone, Copy, Default)]
+struct Correlation<'a> {
+    counting: Counting,
+    checkpoint: Option<&'a str>,
+    stream_results: bool,
+}
+
+/* As above, but each candidate base address is mapped to the key under which it is counted (or
+discarded if there is none). The key must not change the order of the candidates. */
+fn correlate_by<T: RBaseTraits<T, N>, const N: usize>(
+    msg: &'static str,
+    targets_index: &TargetIndex<T>,
+    addresses_index: &PageIndex<T>,
+    key: impl Fn(T) -> Option<T> + Sync,
+    correlation: Correlation,
+) -> Vec<(T, usize)> {
+    match correlation.counting {
+        Counting::Approx(min_hits) => {
+            correlate_approx(msg, targets_index, addresses_index, key, min_hits)
+        }
+        _ => correlate_batches(msg, targets_index, addresses_index, key, correlation),
+    }
+}
+
+/* When stopping early, checkpointing, streaming results or with a time limit, the page offsets are
+correlated in interleaved batches. After each batch we stop if the best candidate dominates the
+runner-up by the given ratio (or the time limit has expired), save the totals so far to the
+checkpoint (if it is a while since it was last saved) and stream the leading candidates. */
+fn correlate_batches<T: RBaseTraits<T, N>, const N: usize>(
+    msg: &'static str,
+    targets_index: &TargetIndex<T>,
+    addresses_index: &PageIndex<T>,
+    key: impl Fn(T) -> Option<T> + Sync,
+    correlation: Correlation,
+) -> Vec<(T, usize)> {
+    let progress_bar = get_progress_bar(msg, targets_index.len());
+    let early_stop = match correlation.counting {
+        Counting::EarlyStop(ratio) => Some(ratio),
+        _ => None,
+    };
+    let checkpoint = correlation.checkpoint;
+    let batches = match (early_stop, checkpoint, correlation.stream_results) {
+        (_, Some(_), _) => CHECKPOINT_BATCHES,
+        (Some(_), None, _) | (None, None, true) => EARLY_STOP_BATCHES,
+        (None, None, false) if timeout::is_set() => EARLY_STOP_BATCHES,
+        (None, None, false) => 1,
+    };
+    let mut fingerprint =
+        checkpoint.map(|_| checkpoint::fingerprint(targets_index, addresses_index));
+    let resumed = checkpoint
+        .zip(fingerprint)
+        .and_then(|(path, fingerprint)| checkpoint::load(path, msg, fingerprint, batches));
+    let (first, mut totals) = match resumed {
+        Some((batch, totals)) => {
+            log!("Resuming from checkpoint after {batch} of {batches} batches");
+            progress_bar.inc((batch * targets_index.len() / batches) as u64);
+            (batch, totals)
+        }
+        None => (0, Vec::new()),
+    };
+    let mut saved = Instant::now();
+    for batch in first..batches {
+        let candidates: Vec<(T, usize)> = (batch..targets_index.len())
+            .step_by(batches)
+            .collect::<Vec<usize>>()
+            .into_par_iter()
+            .progress_with(progress_bar.clone())
+            .flat_map_iter(|page_offset| {
+                sweep(
+                    &targets_index[page_offset],
+                    &addresses_index[page_offset],
+                    &key,
+                )
+            })
+            .collect();
+        totals = merge_candidates(totals, total_candidates(candidates));
+
+        if let Some((path, hash)) = checkpoint.zip(fingerprint) {
+            if saved.elapsed() >= CHECKPOINT_INTERVAL {
+                /* A checkpoint is only an aid to resuming, so the scan carries on without one */
+                if let Err(e) = checkpoint::save(path, msg, hash, batch + 1, batches, &totals) {
+                    log!("Checkpoint: not saved to {path}: {e}, continuing without one");
+                    fingerprint = None;
+                }
+                saved = Instant::now();
+            }
+        }
+
+        if correlation.stream_results {
+            let done = ((batch + 1) * targets_index.len()).div_ceil(batches);
+            stream_candidates(msg, done, targets_index.len(), &totals);
+        }
+
+        if batch + 1 < batches && timeout::expired() {
+            progress_bar.finish_and_clear();
+            log!(
+                "Timeout: stopped after {} of {} page offsets, the results are partial",
+                (batch + 1) * targets_index.len() / batches,
+                targets_index.len()
+            );
+            break;
+        }
+
+        if let Some(ratio) = early_stop {
+            if batch + 1 < batches && dominates(&totals, ratio) {
+                progress_bar.finish_and_clear();
+                log!(
+                    "Stopped early after {} of {} page offsets",
+                    (batch + 1) * targets_index.len() / batches,
+                    targets_index.len()
+                );
+                break;
+            }
+        }
+    }
+    totals
+}
+
+/* Estimate the frequency of every candidate with a count-min sketch, so the memory used is bounded
+however many distinct candidates there are. Then correlate again, counting exactly only the heavy
+hitters, whose estimate reaches the minimum number of hits (and a fraction of the total). */
+fn correlate_approx<T: RBaseTraits<T, N>, const N: usize>(
+    msg: &'static str,
+    targets_index: &TargetIndex<T>,
+    addresses_index: &PageIndex<T>,
+    key: impl Fn(T) -> Option<T> + Sync,
+    min_hits: usize,
+) -> Vec<(T, usize)> {
+    let sweep_page = |(targets, addresses): (&PackedVec<(T, usize)>, &PackedVec<T>)| {
+        sweep(targets, addresses, &key)
+    };
+
+    let progress_bar = get_progress_bar("Sketching candidate base addresses", targets_index.len());
+    let sketch = targets_index
+        .par_iter()
+        .zip(addresses_index)
+        .progress_with(progress_bar)
+        .map(sweep_page)
+        .fold(CountMinSketch::default, |mut sketch, candidates| {
+            for (base, frequency) in candidates {
+                sketch.add(base.into(), frequency);
+            }
+            sketch
+        })
+        .reduce(CountMinSketch::default, CountMinSketch::merge);
+
+    let total: usize = targets_index
+        .iter()
+        .zip(addresses_index)
+        .map(|(targets, addresses)| targets.len() * addresses.len())
+        .sum();
+    let threshold = min_hits.max(total / APPROX_HEAVY_HITTERS);
+    log!("Counting candidates estimated to occur at least {threshold} times");
+
+    let progress_bar = get_progress_bar(msg, targets_index.len());
+    let candidates = targets_index
+        .par_iter()
+        .zip(addresses_index)
+        .progress_with(progress_bar)
+        .flat_map_iter(sweep_page)
+        .filter(|&(base, _frequency)| sketch.estimate(base.into()) >= threshold);
+    let mut totals: Vec<(T, usize)> = collect_by_key(candidates, |total, frequency| {
+        *total = total.saturating_add(frequency)
+    })
+    .into_iter()
+    .collect();
+    totals.par_sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+    totals
+}
+
+/* The same base address can be a candidate in many buckets, so total them across buckets */
+fn total_candidates<T: Ord + Send>(mut candidates: Vec<(T, usize)>) -> Vec<(T, usize)> {
+    candidates.par_sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+    let mut totals: Vec<(T, usize)> = Vec::new();
+    for (base, frequency) in candidates {
+        match totals.last_mut() {
+            Some((last, total)) if *last == base => *total = total.saturating_add(frequency),
+            _ => totals.push((base, frequency)),
+        }
+    }
+    totals
+}
+
+/* Merge two lists of candidates sorted by base address, totalling the frequency of any in both */
+fn merge_candidates<T: Ord + Copy>(a: Vec<(T, usize)>, b: Vec<(T, usize)>) -> Vec<(T, usize)> {
+    if a.is_empty() {
+        return b;
+    }
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    let mut a = a.into_iter().peekable();
+    let mut b = b.into_iter().peekable();
+    loop {
+        let next = match (a.peek(), b.peek()) {
+            (Some(&(base_a, freq_a)), Some(&(base_b, freq_b))) => match base_a.cmp(&base_b) {
+                Ordering::Less => a.next(),
+                Ordering::Greater => b.next(),
+                Ordering::Equal => {
+                    a.next();
+                    b.next();
+                    Some((base_a, freq_a.saturating_add(freq_b)))
+                }
+            },
+            (Some(_), None) => a.next(),
+            (None, Some(_)) => b.next(),
+            (None, None) => break,
+        };
+        merged.extend(next);
+    }
+    merged
+}
+
+/* Print the leading candidates so far as a JSON line, to stderr since they aren't the result */
+fn stream_candidates<T: RBaseTraits<T, N>, const N: usize>(
+    msg: &str,
+    done: usize,
+    total: usize,
+    totals: &[(T, usize)],
+) {
+    let leaders = top_candidates(totals.to_vec(), TOP_CANDIDATES)
+        .iter()
+        .map(|(base, frequency)| {
+            format!(
+                "{{\"base\":\"{}\",\"score\":{frequency}}}",
+                format::prefixed((*base).into(), N * 2)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+    eprintln!(
+        "{{\"phase\":\"{msg}\",\"page_offsets\":{done},\"total_page_offsets\":{total},\"candidates\":{},\"leaders\":[{leaders}]}}",
+        totals.len()
+    );
+}
+
+/* Whether the best candidate is frequent enough, and at least the given ratio more frequent than
+the runner-up */
+fn dominates<T>(candidates: &[(T, usize)], ratio: f64) -> bool {
+    let (mut best, mut runner_up) = (0, 0);
+    for &(_, frequency) in candidates.iter() {
+        if frequency > best {
+            runner_up = best;
+            best = frequency;
+        } else if frequency > runner_up {
+            runner_up = frequency;
+        }
+    }
+    best >= EARLY_STOP_MIN_FREQUENCY && best as f64 >= ratio * runner_up.max(1) as f64
+}
+
+/* Since the addresses in a bucket are sorted, the candidate base addresses for each target form a
+sorted run. Sweep through the runs in order (using a heap of the next candidate from each run),
+totalling the weight of each candidate as we go. Seeking to the target's offset skips addresses
+below it, but the subtraction is checked anyway so malformed input can never wrap. */
+fn sweep<T: RBaseTraits<T, N>, const N: usize>(
+    targets: &PackedVec<(T, usize)>,
+    addresses: &PackedVec<T>,
+    key: impl Fn(T) -> Option<T>,
+) -> Vec<(T, usize)> {
+    let mut runs = Vec::with_capacity(targets.len());
+    let mut heap: BinaryHeap<Reverse<(T, usize)>> = BinaryHeap::with_capacity(targets.len());
+    for (file_offset, weight) in targets {
+        let mut remaining = addresses.seek(file_offset);
+        if let Some(base) = remaining
+            .next()
+            .and_then(|address| address.checked_sub(file_offset))
+        {
+            heap.push(Reverse((base, runs.len())));
+            runs.push((file_offset, weight, remaining));
+        }
+    }
+
+    let mut candidates: Vec<(T, usize)> = Vec::new();
+    while let Some(Reverse((base, run))) = heap.pop() {
+        let (file_offset, weight, remaining) = &mut runs[run];
+        if let Some(key) = key(base) {
+            match candidates.last_mut() {
+                Some((last, frequency)) if *last == key => {
+                    *frequency = frequency.saturating_add(*weight)
+                }
+                _ => candidates.push((key, *weight)),
+            }
+        }
+        if let Some(base) = remaining
+            .next()
+            .and_then(|address| address.checked_sub(*file_offset))
+        {
+            heap.push(Reverse((base, run)));
+        }
+    }
+    candidates
+}
+
+/* The k most frequent candidates, sorted by frequency and breaking ties by address so the order is
+stable between runs. Only these are sorted, rather than every candidate. */
+fn top_candidates<T: Ord>(mut candidates: Vec<(T, usize)>, k: usize) -> Vec<(T, usize)> {
+    let order = |(a1, v1): &(T, usize), (a2, v2): &(T, usize)| v2.cmp(v1).then(a1.cmp(a2));
+    if k == 0 {
+        return Vec::new();
+    }
+    if candidates.len() > k {
+        candidates.select_nth_unstable_by(k - 1, order);
+        candidates.truncate(k);
+    }
+    candidates.sort_unstable_by(order);
+    candidates
+}
+
+/* Split each bucket of an index by page offset into the buckets of a coarse index, by the offset of
+each value within its 64 KiB region. The values keep their order, so each bucket stays sorted. */
+fn coarsen<V: Pack + Send + Sync>(index: &PageIndex<V>, value: impl Fn(V) -> u64) -> PageIndex<V> {
+    let mut buckets: Vec<Vec<V>> = (0..=COARSE_PAGE_OFFSET_MASK).map(|_| Vec::new()).collect();
+    for bucket in index {
+        for item in bucket {
+            buckets[value(item) as usize & COARSE_PAGE_OFFSET_MASK].push(item);
+        }
+    }
+    buckets
+        .into_par_iter()
+        .map(PackedVec::from_sorted)
+        .collect()
+}
+
+/* Find the most frequent coarse regions of candidate base addresses, then count the candidates
+individually only within those regions. The coarse pass pairs only the targets and addresses at the
+same offset within 64 KiB, so it is far quicker than a full sweep but only finds base addresses
+aligned to 64 KiB. The fine pass then seeks each target's addresses straight to those putting its
+base address inside one of the regions, and counts the candidates aligned to 4 KiB there. */
+fn correlate_coarse_to_fine<T: RBaseTraits<T, N>, const N: usize>(
+    targets_index: &TargetIndex<T>,
+    addresses_index: &PageIndex<T>,
+    correlation: Correlation,
+) -> Vec<(T, usize)> {
+    let coarse = correlate_by(
+        "Collecting coarse candidate regions",
+        &coarsen(targets_index, |(file_offset, _weight)| file_offset.into()),
+        &coarsen(addresses_index, Into::into),
+        Some,
+        correlation,
+    );
+    log!("Found: {:?} coarse candidate regions", coarse.len());
+
+    let regions = top_candidates(coarse, COARSE_REGIONS);
+    for (start, frequency) in regions.iter() {
+        log!(
+            "Coarse region: {}: {frequency}",
+            format::prefixed((*start).into(), N * 2)
+        );
+    }
+
+    let coarse_mask = T::try_from(COARSE_PAGE_OFFSET_MASK).unwrap();
+    let progress_bar = get_progress_bar(
+        "Collecting candidates in coarse regions",
+        targets_index.len(),
+    );
+    let candidates: Vec<(T, usize)> = targets_index
+        .par_iter()
+        .zip(addresses_index)
+        .progress_with(progress_bar)
+        .flat_map_iter(|(targets, addresses)| {
+            let mut candidates = Vec::new();
+            for (file_offset, weight) in targets {
+                for &(region, _frequency) in regions.iter() {
+                    let Some(start) = file_offset.checked_add(region) else {
+                        continue;
+                    };
+                    candidates.extend(
+                        addresses
+                            .seek(start)
+                            .map_while(|address| address.checked_sub(file_offset))
+                            .take_while(|&base| base - region <= coarse_mask)
+                            .map(|base| (base, weight)),
+                    );
+                }
+            }
+            candidates
+        })
+        .collect();
+    total_candidates(candidates)
+}
+
+/* Estimate the distribution of the best score when targets and addresses are unrelated, by
+randomizing the page offset of each target and repeating the correlation */
+fn permutation_test<T: RBaseTraits<T, N>, const N: usize>(
+    targets_index: &TargetIndex<T>,
+    addresses_index: &PageIndex<T>,
+    permutations: usize,
+) -> Vec<usize> {
+    let page_offset_mask = T::try_from(PAGE_OFFSET_MASK).unwrap();
+    (0..permutations)
+        .map(|permutation| {
+            let mut rng = StdRng::seed_from_u64(permutation as u64);
+            let mut permuted: Vec<Vec<(T, usize)>> = vec![Vec::new(); PAGE_SIZE];
+            for (file_offset, weight) in targets_index.iter().flatten() {
+                let page_offset = rng.gen_range(0..=PAGE_OFFSET_MASK);
+                let file_offset = file_offset - (file_offset & page_offset_mask);
+                permuted[page_offset]
+                    .push((file_offset | T::try_from(page_offset).unwrap(), weight));
+            }
+            let permuted = pack_buckets(permuted);
+            correlate("Permutation test", &permuted, addresses_index)
+                .into_iter()
+                .map(|(_base, frequency)| frequency)
+                .max()
+                .unwrap_or(0)
+        })
+        .collect()
+}
+
+/* Summarise how the best score compares with those of the permutation test */
+struct Confidence {
+    best: usize,
+    null_scores: Vec<usize>,
+}
+
+impl Display for Confidence {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let n = self.null_scores.len() as f64;
+        let mean = self.null_scores.iter().sum::<usize>() as f64 / n;
+        let variance = self
+            .null_scores
+            .iter()
+            .map(|&score| (score as f64 - mean).powi(2))
+            .sum::<f64>()
+            / n;
+        let std_dev = variance.sqrt().max(1.0);
+        let z = (self.best as f64 - mean) / std_dev;
+        let exceeded = self
+            .null_scores
+            .iter()
+            .filter(|&&score| score >= self.best)
+            .count();
+        let p = (exceeded + 1) as f64 / (n + 1.0);
+        let label = match z {
+            z if exceeded == 0 && z >= 5.0 => "high",
+            z if exceeded == 0 && z >= 3.0 => "medium",
+            _ => "low",
+        };
+        write!(
+            f,
+            "{label} (z: {z:.1}, p: {p:.3}, null scores: {mean:.1} ± {std_dev:.1} over {} permutations)",
+            self.null_scores.len()
+        )
+    }
+}
+
+/* Fraction of the targets kept in each bootstrap sample */
+const BOOTSTRAP_FRACTION: f64 = 0.5;
+
+/* Repeat the correlation on random subsamples of the targets, returning the best candidate of each
+(if any) */
+fn bootstrap<T: RBaseTraits<T, N>, const N: usize>(
+    targets_index: &TargetIndex<T>,
+    addresses_index: &PageIndex<T>,
+    samples: usize,
+) -> Vec<Option<T>> {
+    (0..samples)
+        .map(|sample| {
+            let mut rng = StdRng::seed_from_u64(sample as u64);
+            let subsample: Vec<Vec<(T, usize)>> = targets_index
+                .iter()
+                .map(|targets| {
+                    targets
+                        .iter()
+                        .filter(|_| rng.gen_bool(BOOTSTRAP_FRACTION))
+                        .collect()
+                })
+                .collect();
+            let subsample = pack_buckets(subsample);
+            correlate("Bootstrap", &subsample, addresses_index)
+                .into_iter()
+                .max_by(|(b1, f1), (b2, f2)| f1.cmp(f2).then(b2.cmp(b1)))
+                .map(|(base, _frequency)| base)
+        })
+        .collect()
+}
+
+/* Summarise how often the best candidate stays on top in the bootstrap samples */
+struct Stability {
+    best: u64,
+    winners: Vec<Option<u64>>,
+    digits: usize,
+}
+
+impl Display for Stability {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let mut others: Vec<(u64, usize)> = Vec::new();
+        for winner in self.winners.iter().flatten() {
+            if *winner == self.best {
+                continue;
+            }
+            match others.iter_mut().find(|(other, _count)| other == winner) {
+                Some((_other, count)) => *count += 1,
+                None => others.push((*winner, 1)),
+            }
+        }
+        others.sort_unstable_by(|(b1, c1), (b2, c2)| c2.cmp(c1).then(b1.cmp(b2)));
+        let stays = self
+            .winners
+            .iter()
+            .filter(|&&winner| winner == Some(self.best))
+            .count();
+        write!(
+            f,
+            "{:.1}% ({} on top in {stays} of {} samples",
+            100.0 * stays as f64 / self.winners.len() as f64,
+            format::prefixed(self.best, self.digits),
+            self.winners.len()
+        )?;
+        if let Some((other, count)) = others.first() {
+            write!(
+                f,
+                ", otherwise most often {} in {count}",
+                format::prefixed(*other, self.digits)
+            )?;
+        }
+        write!(f, ")")
+    }
+}
+
+/* Whether the best candidate of a correlation is ambiguous, being neither dominant nor stable in
+bootstrap samples, and if so why */
+fn ambiguity<T: RBaseTraits<T, N>, const N: usize>(
+    candidates: &[(T, usize)],
+    targets_index: &TargetIndex<T>,
+    addresses_index: &PageIndex<T>,
+) -> Option<String> {
+    let Some(&(best, _frequency)) = candidates
+        .iter()
+        .max_by(|(b1, f1), (b2, f2)| f1.cmp(f2).then(b2.cmp(b1)))
+    else {
+        return Some("no candidates".to_string());
+    };
+    let best_name = format::prefixed(best.into(), N * 2);
+    if !dominates(candidates, AUTO_DOMINANCE) {
+        return Some(format!(
+            "the best candidate {best_name} doesn't dominate the runner-up {AUTO_DOMINANCE} times over"
+        ));
+    }
+    let winners = bootstrap(targets_index, addresses_index, AUTO_BOOTSTRAP);
+    let stays = winners
+        .iter()
+        .filter(|&&winner| winner == Some(best))
+        .count();
+    (stays < AUTO_MIN_STAYS).then(|| {
+        format!(
+            "the best candidate {best_name} stays on top in only {stays} of {AUTO_BOOTSTRAP} samples"
+        )
+    })
+}
+
+/* Discard the candidates at which too few of the addresses landing inside the image land on a target.
+The addresses which do are those which matched the candidate when correlating, so they are counted by
+correlating again (only counting these candidates) with every target given the same weight. */
+fn filter_landing<T: RBaseTraits<T, N>, const N: usize>(
+    candidates: Vec<(T, usize)>,
+    targets_index: &TargetIndex<T>,
+    addresses_index: &PageIndex<T>,
+    extent: T,
+    min_landing_pct: f64,
+) -> Vec<(T, usize)> {
+    let mut bases: Vec<T> = candidates.iter().map(|&(base, _frequency)| base).collect();
+    bases.sort_unstable();
+    let unweighted: TargetIndex<T> = targets_index
+        .par_iter()
+        .map(|targets| {
+            PackedVec::from_sorted(
+                targets
+                    .iter()
+                    .map(|(file_offset, _weight)| (file_offset, 1))
+                    .collect(),
+            )
+        })
+        .collect();
+    let matched = correlate_by(
+        "Counting addresses landing on targets",
+        &unweighted,
+        addresses_index,
+        |base| bases.binary_search(&base).is_ok().then_some(base),
+        Correlation::default(),
+    );
+
+    let mut addresses: Vec<T> = addresses_index.iter().flatten().collect();
+    addresses.sort_unstable();
+    let progress_bar = get_progress_bar("Checking candidates land in image", candidates.len());
+    let landing: Vec<(T, usize)> = candidates
+        .into_par_iter()
+        .progress_with(progress_bar)
+        .filter(|&(base, _frequency)| {
+            let first = addresses.partition_point(|&address| address < base);
+            let last =
+                addresses.partition_point(|&address| address < base || address - base < extent);
+            let on_targets = matched
+                .binary_search_by_key(&base, |&(other, _count)| other)
+                .map_or(0, |idx| matched[idx].1);
+            100.0 * on_targets as f64 / (last - first).max(1) as f64 >= min_landing_pct
+        })
+        .collect();
+    log!(
+        "Found: {:?} candidate base addresses whose addresses land on targets",
+        landing.len()
+    );
+    landing
+}
+
+pub fn get_base_address<T: RBaseTraits<T, N>, const N: usize>(
+    args: &Args,
+    bytes: &[u8],
+    weigher: &StringWeigher,
+    script: Option<&Script>,
+    svd: Option<&Svd>,
+    hints: &[Hint],
+    read_address_bytes: fn([u8; N]) -> T,
+) -> std::result::Result<Option<Base<T>>, String> {
+    /* Optionally only analyse part of a huge image for a quick triage, either its start or evenly
+    spaced slices of it */
+    let len = bytes.len();
+    let (bytes, slices, analysed) = match args.max_bytes.map(|max_bytes| max_bytes as usize) {
+        Some(max_bytes) if max_bytes < len && args.max_bytes_stride => {
+            let slices = regions::slices(len, max_bytes);
+            let (count, windows) = slices.count();
+            log!("Partial: only searching {count} of {windows} windows, in evenly spaced slices");
+            (bytes, Some(slices), Some((max_bytes, len)))
+        }
+        Some(max_bytes) if max_bytes < len => {
+            log!("Partial: only analysing the first 0x{max_bytes:x} of 0x{len:x} bytes");
+            (&bytes[..max_bytes], None, Some((max_bytes, len)))
+        }
+        _ => (bytes, None, None),
+    };
+
+    /* For bank-switched images, both addresses and offsets are only meaningful within a bank */
+    let bank_mask = args
+        .bank_size
+        .map(|bank_size| T::saturating_from(bank_size - 1));
+    let pointer_mask = match (args.pointer_mask.map(T::truncating_from), bank_mask) {
+        (Some(pointer_mask), Some(bank_mask)) => Some(pointer_mask & bank_mask),
+        (pointer_mask, bank_mask) => pointer_mask.or(bank_mask),
+    };
+
+    /* Small values are more likely to be lengths, counters or enum values than pointers */
+    let address_range = match (args.require_prefix, args.addr_max, args.pointer_min) {
+        (None, None, 0) => None,
+        (range, addr_max, pointer_min) => {
+            let (start, end) = range.map_or((0, u64::MAX), |range| range.bounds(T::BITS));
+            Some((
+                start.max(pointer_min),
+                end.min(addr_max.unwrap_or(u64::MAX)),
+            ))
+        }
+    }
+    .map(|(start, end)| (T::saturating_from(start), T::saturating_from(end)));
+
+    /* Words with these values are fill patterns, canaries or otherwise known not to be pointers */
+    let fills = fill::find(bytes, read_address_bytes, pointer_mask);
+    for fill in fills.iter() {
+        log!(
+            "Ignoring: {} words of fill pattern 0x{:x}",
+            fill.words,
+            fill.value
+        );
+    }
+    let ignored_pointers: Vec<T> = sentinels(T::BITS)
+        .chain(args.ignore_pointers.iter().copied())
+        .map(T::saturating_from)
+        .chain(fills.iter().map(|fill| fill.value))
+        .collect();
+
+    /* Look for a device tree describing the memory of the platform */
+    let device_tree = fdt::find(bytes);
+    if let Some(device_tree) = &device_tree {
+        log!("Found: device tree at 0x{:x}", device_tree.offset);
+        for memory in device_tree.memory.iter() {
+            log!(
+                "Device tree memory: {}: 0x{:x}-0x{:x}",
+                memory.name,
+                memory.start,
+                memory.end
+            );
+        }
+    }
+
+    /* Look for an AArch64 kernel Image header, constraining the base address to its text offset */
+    let kernel_image = arm64::find(bytes);
+    if let Some(kernel_image) = &kernel_image {
+        log!(
+            "Found: AArch64 kernel Image, text offset 0x{:x}, image size 0x{:x}, {} endian{}",
+            kernel_image.text_offset,
+            kernel_image.image_size,
+            if kernel_image.big_endian {
+                "big"
+            } else {
+                "little"
+            },
+            match kernel_image.page_size {
+                Some(page_size) => format!(", {} KiB pages", page_size / 1024),
+                None => String::new(),
+            }
+        );
+    }
+
+    /* Look for a U-Boot environment giving the addresses at which images are loaded */
+    let mut hints = hints.to_vec();
+    if let Some(environment) = uboot::find(bytes) {
+        log!("Found: U-Boot environment at 0x{:x}", environment.offset);
+        hints.extend(environment.hints().into_iter().map(|(name, address)| Hint {
+            source: format!("U-Boot environment {name}"),
+            address,
+        }));
+    }
+    for hint in hints.iter() {
+        log!("Hint: {}: 0x{:x}", hint.source, hint.address);
+    }
+
+    /* Look for compressed data, filesystems and certificates. Filesystems are ignored unless they
+    are to be kept, and the others only if asked. */
+    let signatures = signature::scan(bytes);
+    for signature in signatures.iter() {
+        log!(
+            "Found: {} at 0x{:x}-0x{:x}",
+            signature.name,
+            signature.start,
+            signature.end
+        );
+    }
+    let ignored: Vec<(usize, usize)> = signatures
+        .iter()
+        .filter(|signature| match signature.is_filesystem() {
+            true => !args.keep_filesystems,
+            false => args.exclude_signatures,
+        })
+        .map(|signature| (signature.start, signature.end))
+        .collect();
+    let outside_signatures = (!ignored.is_empty()).then(|| {
+        let regions = regions::outside(bytes.len(), ignored.into_iter());
+        let (count, windows) = regions.count();
+        log!(
+            "Ignoring: {} of {windows} windows inside signatures",
+            windows - count
+        );
+        regions
+    });
+
+    /* Run any YARA rules, then only search the regions matched by those to be included (if any) and
+    not by those to be excluded */
+    let yara_matches = match &args.yara {
+        Some(rules) => yara::scan(rules, bytes)?,
+        None => Vec::new(),
+    };
+    for yara_match in yara_matches.iter() {
+        match yara_match.extent {
+            Some((start, end)) => log!("YARA: {} at 0x{:x}-0x{:x}", yara_match.rule, start, end),
+            None => log!("YARA: {}", yara_match.rule),
+        }
+    }
+    let extents = |rules: &[String]| {
+        yara_matches
+            .iter()
+            .filter(|yara_match| rules.contains(&yara_match.rule))
+            .filter_map(|yara_match| yara_match.extent)
+            .collect::<Vec<(usize, usize)>>()
+    };
+    let selected_by_yara =
+        (!args.yara_include.is_empty() || !args.yara_exclude.is_empty()).then(|| {
+            let excluded = regions::outside(bytes.len(), extents(&args.yara_exclude).into_iter());
+            let regions = match args.yara_include.is_empty() {
+                true => excluded,
+                false => regions::inside(bytes.len(), extents(&args.yara_include).into_iter())
+                    .intersect(&excluded),
+            };
+            let (count, windows) = regions.count();
+            log!("Searching: {count} of {windows} windows selected by YARA rules");
+            regions
+        });
+
+    /* Skip padding (as in erased flash) unless it is to be kept, since it can hold neither strings
+    nor pointers */
+    let unpadded = if args.keep_padding {
+        None
+    } else {
+        let regions = regions::unpadded(bytes);
+        let (count, windows) = regions.count();
+        (count != windows).then(|| {
+            log!(
+                "Ignoring: {} of {windows} windows of padding",
+                windows - count
+            );
+            regions
+        })
+    };
+
+    /* Skip any ranges the user knows to be junk */
+    let outside_ignored_ranges = (!args.ignore_ranges.is_empty()).then(|| {
+        let regions = regions::outside(
+            bytes.len(),
+            args.ignore_ranges
+                .iter()
+                .map(|range| (range.start, range.end)),
+        );
+        let (count, windows) = regions.count();
+        log!(
+            "Ignoring: {} of {windows} windows inside the ranges given",
+            windows - count
+        );
+        regions
+    });
+
+    /* Any other regions searched are restricted to those not ignored */
+    let searched = [
+        outside_signatures,
+        selected_by_yara,
+        unpadded,
+        outside_ignored_ranges,
+        slices,
+    ]
+    .into_iter()
+    .flatten()
+    .reduce(|searched, regions| searched.intersect(&regions));
+    let restrict = |regions: Option<Regions>| match (regions, &searched) {
+        (Some(regions), Some(searched)) => Some(regions.intersect(searched)),
+        (regions, searched) => regions.or_else(|| searched.clone()),
+    };
+
+    /* Optionally only search for strings in regions which look like string tables */
+    let string_regions = restrict(if args.string_regions {
+        let regions = regions::string_dense(bytes, args.charset_bytes());
+        let (count, windows) = regions.count();
+        log!("Found: {count} of {windows} windows dense with strings");
+        (count != 0).then_some(regions)
+    } else {
+        None
+    });
+
+    let chunk_size = args.chunk_size.map(|chunk_size| chunk_size as usize);
+    let align = args.align.unwrap_or(pointer_alignment(N));
+    let sampling = args.sampling();
+
+    /* Collect the targets of each heuristic, ignoring any given more than once */
+    let mut kinds = args.heuristics.clone();
+    kinds.sort_unstable();
+    kinds.dedup();
+    let heuristics: Vec<Box<dyn Heuristic<T, N> + '_>> = kinds
+        .iter()
+        .map(|kind| -> Box<dyn Heuristic<T, N>> {
+            match kind {
+                HeuristicKind::Strings => Box::new(Strings {
+                    regex: args.string_regex(),
+                    terminated: args.terminated(),
+                    string_bytes: args.string_bytes(),
+                    weigher,
+                    script,
+                    regions: string_regions.as_ref(),
+                    offset_mask: bank_mask,
+                    chunk_size,
+                }),
+                HeuristicKind::Pointers => Box::new(Pointers {
+                    read_address_bytes,
+                    align,
+                    pointer_mask,
+                    pointer_min: T::saturating_from(args.pointer_min),
+                    address_range,
+                    script,
+                    ignored: &ignored_pointers,
+                    offset_mask: bank_mask,
+                    chunk_size,
+                }),
+            }
+        })
+        .collect();
+    let scanned: Vec<(HeuristicKind, FxHashMap<T, usize>)> = heuristics
+        .iter()
+        .map(|heuristic| (heuristic.kind(), heuristic.targets(bytes)))
+        .collect();
+
+    /* Optionally only search for addresses in regions which look like data */
+    let data_regions = restrict(if args.data_regions {
+        let regions =
+            regions::pointer_dense(bytes, read_address_bytes, pointer_mask, args.pointer_min);
+        let (count, windows) = regions.count();
+        log!("Found: {count} of {windows} windows dense with pointers");
+        (count != 0).then_some(regions)
+    } else {
+        None
+    });
+
+    /* Optionally ignore pointers to peripheral registers, reporting those most often referenced */
+    if let Some(svd) = svd {
+        log!("Found: {} peripheral address blocks", svd.len());
+        let pointers = get_pointers(
+            bytes,
+            read_address_bytes,
+            align,
+            pointer_mask,
+            address_range,
+            script,
+            None,
+            chunk_size,
+        )
+        .filter_map(|(_file_offset, pointer)| svd.lookup(pointer.into()))
+        .map(|peripheral| (peripheral.name.as_str(), 1));
+        let peripherals = collect_by_key(pointers, |count, other| *count += other);
+        let mut peripherals: Vec<(&str, usize)> = peripherals.into_iter().collect();
+        peripherals.sort_unstable_by(|(a1, v1), (a2, v2)| v2.cmp(v1).then(a1.cmp(a2)));
+        for (name, count) in peripherals.iter().take(TOP_CANDIDATES) {
+            log!("Ignoring: {count} pointers to {name} registers");
+        }
+    }
+
+    let trailing = match bytes.len().checked_sub(N) {
+        Some(len) => len % align,
+        None => bytes.len(),
+    };
+    if trailing != 0 {
+        log!("Ignoring: {trailing} trailing bytes which don't form a whole address");
+    }
+    let pointers = || {
+        get_pointers(
+            bytes,
+            read_address_bytes,
+            align,
+            pointer_mask,
+            address_range,
+            script,
+            data_regions.as_ref(),
+            chunk_size,
+        )
+        .filter(|(_file_offset, pointer)| !ignored_pointers.contains(pointer))
+        .filter(|&(_file_offset, pointer)| {
+            svd.is_none_or(|svd| svd.lookup(pointer.into()).is_none())
+        })
+    };
+
+    let correlation = Correlation {
+        counting: match (args.approx, args.early_stop) {
+            (true, _) => Counting::Approx(args.min_hits),
+            (false, Some(ratio)) => Counting::EarlyStop(ratio),
+            (false, None) => Counting::Exact,
+        },
+        checkpoint: args.checkpoint.as_deref(),
+        stream_results: args.stream_results,
+    };
+
+    /* Index the targets and addresses, and correlate them. In auto mode, start with small samples of
+    each and only sample more (eventually all of them) while the result is ambiguous. */
+    let mut caps = match args.auto {
+        true => (
+            (args.max_strings / AUTO_GROWTH).max(1),
+            (args.max_addresses / AUTO_GROWTH).max(1),
+        ),
+        false => (args.max_strings, args.max_addresses),
+    };
+    let mut dump_pointers = args.dump_pointers.as_deref();
+    let (targets, targets_index, addresses_index, base_addresses) = loop {
+        let (max_strings, max_addresses) = caps;
+        let targets: Vec<(HeuristicKind, TargetIndex<T>)> = scanned
+            .iter()
+            .map(|(kind, targets)| {
+                (
+                    *kind,
+                    index_targets(*kind, targets.clone(), max_strings, sampling),
+                )
+            })
+            .collect();
+        let targets_index = merge(&targets);
+        let (addresses_index, num_addresses) = get_addresses_by_page_offset(
+            pointers(),
+            dump_pointers.take(),
+            max_addresses,
+            sampling,
+        )?;
+
+        let base_addresses = if args.coarse {
+            correlate_coarse_to_fine(&targets_index, &addresses_index, correlation)
+        } else {
+            correlate_by(
+                "Collecting candidate base addresses",
+                &targets_index,
+                &addresses_index,
+                Some,
+                correlation,
+            )
+        };
+        if !args.auto {
+            break (targets, targets_index, addresses_index, base_addresses);
+        }
+
+        let sampled = num_addresses > max_addresses
+            || scanned
+                .iter()
+                .any(|(_kind, targets)| targets.len() > max_strings);
+        match ambiguity(&base_addresses, &targets_index, &addresses_index) {
+            None => {
+                log!("Auto: the best candidate is unambiguous with up to {max_strings} strings and {max_addresses} addresses");
+            }
+            Some(reason) if !sampled => log!("Auto: {reason}, but nothing more to sample"),
+            Some(reason) if timeout::expired() => {
+                log!("Auto: {reason}, but out of time to sample more")
+            }
+            Some(reason) => {
+                caps = (
+                    max_strings.saturating_mul(AUTO_GROWTH),
+                    max_addresses.saturating_mul(AUTO_GROWTH),
+                );
+                log!(
+                    "Auto: {reason}, sampling up to {} strings and {} addresses",
+                    caps.0,
+                    caps.1
+                );
+                continue;
+            }
+        }
+        break (targets, targets_index, addresses_index, base_addresses);
+    };
+
+    if let Some(path) = &args.heatmap {
+        let strings = targets
+            .iter()
+            .filter(|(kind, _index)| *kind == HeuristicKind::Strings)
+            .flat_map(|(_kind, index)| index.iter().flatten())
+            .filter_map(|(file_offset, _weight)| file_offset.try_into().ok());
+        let pointers = get_pointers(
+            bytes,
+            read_address_bytes,
+            align,
+            pointer_mask,
+            address_range,
+            script,
+            None,
+            chunk_size,
+        )
+        .map(|(file_offset, _pointer)| file_offset);
+        heatmap::write(path, bytes.len(), strings, pointers).map_err(|e| format!("{path}: {e}"))?;
+        log!(
+            "Wrote: heatmap of {} byte regions to {}",
+            heatmap::region_size(bytes.len()),
+            path
+        );
+    }
+
+    /* The correlation is complete, so there is nothing left to resume */
+    if let Some(path) = &args.checkpoint {
+        match remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                log!("Checkpoint: not removed from {path}: {e}")
+            }
+            _ => {}
+        }
+    }
+
+    let num_candidates = base_addresses.len();
+    log!("Found: {:?} candidate base addresses", num_candidates);
+
+    /* Filter out any candidates which don't appear at least the minimum number of times */
+    let recurring: Vec<(T, usize)> = base_addresses
+        .into_par_iter()
+        .filter(|&(_k, v)| v >= args.min_hits)
+        .collect();
+    log!(
+        "Found: {:?} recurring candidate base addresses",
+        recurring.len()
+    );
+
+    /* Filter out any candidates which aren't a multiple of the page size, if larger than the pages
+    the addresses are indexed by */
+    let recurring = match args.page_size as usize == PAGE_SIZE {
+        true => recurring,
+        false => {
+            let aligned: Vec<(T, usize)> = recurring
+                .into_iter()
+                .filter(|&(base, _frequency)| {
+                    Into::<u64>::into(base).is_multiple_of(args.page_size)
+                })
+                .collect();
+            log!(
+                "Found: {:?} candidate base addresses aligned to page size 0x{:x}",
+                aligned.len(),
+                args.page_size
+            );
+            aligned
+        }
+    };
+
+    /* Filter out any candidates where too few of the addresses landing inside the image land on
+    strings (or other targets) */
+    let recurring = match args.min_landing_pct {
+        Some(min_landing_pct) => {
+            let slack = args.slack.bytes(bytes.len());
+            let extent = (bytes.len() as u64).saturating_add(slack);
+            filter_landing(
+                recurring,
+                &targets_index,
+                &addresses_index,
+                T::saturating_from(extent),
+                min_landing_pct,
+            )
+        }
+        None => recurring,
+    };
+
+    /* Optionally filter out any candidates outside the memory described by the device tree */
+    let recurring = match &device_tree {
+        Some(device_tree) if args.use_device_tree && !device_tree.memory.is_empty() => {
+            let inside: Vec<(T, usize)> = recurring
+                .into_iter()
+                .filter(|&(base, _frequency)| device_tree.lookup(base.into()).is_some())
+                .collect();
+            log!(
+                "Found: {:?} candidate base addresses inside device tree memory",
+                inside.len()
+            );
+            inside
+        }
+        _ => recurring,
+    };
+
+    /* Discard any candidates which don't put each anchor's string at its address, or load each
+    anchor offset at its address */
+    let recurring = match args.anchors.is_empty() && args.anchor_offsets.is_empty() {
+        true => recurring,
+        false => {
+            let mut anchored: Vec<Vec<u64>> = Vec::new();
+            for anchor in args.anchors.iter() {
+                let bases = anchor.bases(bytes);
+                log!("Anchor: {} occurs {} times", anchor, bases.len());
+                anchored.push(bases);
+            }
+            for anchor in args.anchor_offsets.iter() {
+                let base = anchor.base().unwrap();
+                if !base.is_multiple_of(args.page_size) {
+                    log!("Anchor: {anchor} gives base 0x{base:x}, which isn't page aligned");
+                }
+                anchored.push(vec![base]);
+            }
+            let consistent: Vec<(T, usize)> = recurring
+                .into_iter()
+                .filter(|&(base, _frequency)| {
+                    anchored.iter().all(|bases| bases.contains(&base.into()))
+                })
+                .collect();
+            log!(
+                "Found: {:?} candidate base addresses consistent with the anchors",
+                consistent.len()
+            );
+            consistent
+        }
+    };
+
+    /* A kernel Image is loaded at a 2 MiB aligned address plus its text offset, so candidates which
+    are get ranked higher (rather than the others being discarded, in case the header is wrong) */
+    let consistent = |base: T| {
+        kernel_image
+            .as_ref()
+            .is_some_and(|kernel_image| kernel_image.is_consistent(base.into()))
+    };
+    if kernel_image.is_some() {
+        log!(
+            "Found: {} candidate base addresses consistent with the text offset",
+            recurring
+                .iter()
+                .filter(|&&(base, _)| consistent(base))
+                .count()
+        );
+    }
+
+    /* Each candidate is scored by its frequency, unless a script scores it otherwise */
+    let mut candidates: Vec<(T, usize, usize)> = recurring
+        .into_iter()
+        .map(|(base, frequency)| (base, frequency, frequency))
+        .collect();
+    if let Some(script) = script {
+        candidates = candidates
+            .into_iter()
+            .filter_map(|(base, frequency, _score)| {
+                let known = known::lookup(base.into()).is_some();
+                match script.score_candidate(base.into(), frequency, known) {
+                    Some(0) => None,
+                    Some(score) => Some((base, frequency, score)),
+                    None => Some((base, frequency, frequency)),
+                }
+            })
+            .collect();
+    }
+
+    if let Some(path) = &args.plot_data {
+        let scores: Vec<(T, usize)> = candidates
+            .iter()
+            .map(|&(base, _frequency, score)| (base, score))
+            .collect();
+        dump::plot_data(path, &scores).map_err(|e| format!("{path}: {e}"))?;
+        log!("Wrote: {} candidate scores to {}", candidates.len(), path);
+    }
+
+    /* Rank the candidates, ready to be re-ranked. Known base addresses and those consistent with
+    a kernel's text offset are boosted in the ranking alone, so their scores are still comparable
+    with the others. */
+    let total_score = candidates
+        .iter()
+        .map(|&(_base, _frequency, score)| score)
+        .fold(0, usize::saturating_add);
+    let boost = |base: T| {
+        let known = match args.use_known_bases && known::lookup(base.into()).is_some() {
+            true => known::KNOWN_BASE_BOOST,
+            false => 1,
+        };
+        let text_offset = match consistent(base) {
+            true => arm64::TEXT_OFFSET_BOOST,
+            false => 1,
+        };
+        known * text_offset
+    };
+    let mut sorted: Vec<Candidate<T>> = candidates
+        .into_iter()
+        .map(|(base, frequency, score)| Candidate {
+            base,
+            frequency,
+            score,
+            coverage: score as f64 / total_score.max(1) as f64,
+            rank: score.saturating_mul(boost(base)),
+        })
+        .collect();
+    sorted.par_sort_unstable_by(|a, b| b.rank.cmp(&a.rank).then(a.base.cmp(&b.base)));
+    let Some(mut base) = Base::new(sorted) else {
+        return Ok(None);
+    };
+    base.analysed = analysed;
+    base.min_coverage = args.min_score_pct.map(|pct| pct / 100.0);
+
+    /* Print the top candidates, and any others with at least the minimum coverage */
+    let shown = match base.min_coverage {
+        Some(min_coverage) => base
+            .iter()
+            .rposition(|candidate| candidate.coverage >= min_coverage)
+            .map_or(0, |idx| idx + 1)
+            .max(TOP_CANDIDATES),
+        None => TOP_CANDIDATES,
+    };
+    for (idx, candidate) in base.top(shown).iter().enumerate() {
+        let known = match known::lookup(candidate.base.into()) {
+            Some(name) => format!(" [{name}]"),
+            None => String::new(),
+        };
+        let memory = match device_tree
+            .as_ref()
+            .and_then(|device_tree| device_tree.lookup(candidate.base.into()))
+        {
+            Some(memory) => format!(" [{}]", memory.name),
+            None => String::new(),
+        };
+        log!(
+            "{:2}: {}: {} ({:.2}%){known}{memory}",
+            idx + 1,
+            format::prefixed(candidate.base.into(), N * 2),
+            candidate.score,
+            100.0 * candidate.coverage
+        );
+    }
+
+    if args.permutations != 0 {
+        let null_scores = permutation_test(&targets_index, &addresses_index, args.permutations);
+        let confidence = Confidence {
+            best: base.best().frequency,
+            null_scores,
+        };
+        log!("Confidence: {}", confidence);
+    }
+
+    if args.bootstrap != 0 {
+        let stability = Stability {
+            best: base.best().base.into(),
+            winners: bootstrap(&targets_index, &addresses_index, args.bootstrap)
+                .into_iter()
+                .map(|winner| winner.map(Into::into))
+                .collect(),
+            digits: N * 2,
+        };
+        log!("Stability: {}", stability);
+    }
+
+    /* Optionally re-rank the top candidates by disassembly */
+    if let Some(arch) = args.refine {
+        let top: Vec<(T, usize)> = base
+            .iter()
+            .take(args.refine_top)
+            .map(|candidate| (candidate.base, candidate.score))
+            .collect();
+        let refined = refine::refine(bytes, &top, arch, args.endian());
+        for (idx, candidate) in refined.iter().enumerate() {
+            log!(
+                "{:2}: {}: {} references to functions, frequency {}",
+                idx + 1,
+                format::prefixed(candidate.base.into(), N * 2),
+                candidate.references,
+                candidate.frequency
+            );
+        }
+        base.rerank(
+            &refined
+                .iter()
+                .map(|candidate| candidate.base)
+                .collect::<Vec<T>>(),
+        );
+    }
+
+    /* Optionally re-rank the top candidates by how many symbols of a related build they put on
+    plausible structures in the image */
+    if let Some(path) = &args.symbols {
+        let symbols = Symbols::load(path)?;
+        log!("Found: {} symbols in {}", symbols.len(), path);
+        let mut scored: Vec<(T, usize)> = base
+            .iter()
+            .take(TOP_CANDIDATES)
+            .map(|candidate| {
+                (
+                    candidate.base,
+                    symbols.landing(bytes, candidate.base.into()),
+                )
+            })
+            .collect();
+        scored.sort_by_key(|&(_base, landing)| Reverse(landing));
+        for (idx, (candidate, landing)) in scored.iter().enumerate() {
+            log!(
+                "{:2}: {}: {} of {} symbols land on plausible structures",
+                idx + 1,
+                format::prefixed((*candidate).into(), N * 2),
+                landing,
+                symbols.len()
+            );
+        }
+        base.rerank(
+            &scored
+                .iter()
+                .map(|&(base, _landing)| base)
+                .collect::<Vec<T>>(),
+        );
+    }
+
+    /* Optionally compare the candidates with those found by another tool */
+    if let Some(path) = &args.compare {
+        let theirs = compare::load(path)?;
+        log!("Found: {} candidates in {}", theirs.len(), path);
+        let ours: Vec<(u64, usize)> = base
+            .iter()
+            .map(|candidate| (candidate.base.into(), candidate.score))
+            .collect();
+        compare::report(&ours, &theirs, TOP_CANDIDATES, N * 2);
+    }
+
+    /* Optionally break down the evidence for each of the top candidates */
+    if args.explain {
+        for (idx, candidate) in base.top(TOP_CANDIDATES).iter().enumerate() {
+            let evidence = explain::explain(candidate.base, &targets, &addresses_index);
+            log!(
+                "Explain: {:2}: {}",
+                idx + 1,
+                format::prefixed(candidate.base.into(), N * 2)
+            );
+            for line in explain::describe(&evidence, bytes) {
+                log!("Explain: {:2}: {line}", idx + 1);
+            }
+        }
+    }
+
+    /* Cross-check the best candidate against the load addresses given by headers and the like */
+    if !hints.is_empty() {
+        let best: u64 = base.best().base.into();
+        match hints.iter().find(|hint| hint.address == best) {
+            Some(hint) => log!("Base matches hint: {}", hint.source),
+            None => log!("Base matches no hint"),
+        }
+    }
+    for anchor in args.anchors.iter() {
+        let best: u64 = base.best().base.into();
+        match anchor
+            .offsets(bytes)
+            .into_iter()
+            .find(|&offset| best.checked_add(offset as u64) == Some(anchor.address))
+        {
+            Some(offset) => log!("Base agrees with anchor {anchor}: string at 0x{offset:x}"),
+            None => log!("Base disagrees with anchor {anchor}"),
+        }
+    }
+    for anchor in args.anchor_offsets.iter() {
+        let best: u64 = base.best().base.into();
+        match anchor.base() == Some(best) {
+            true => log!("Base agrees with anchor {anchor}"),
+            false => log!("Base disagrees with anchor {anchor}"),
+        }
+    }
+    if let Some(kernel_image) = &kernel_image {
+        let best: u64 = base.best().base.into();
+        match kernel_image.is_consistent(best) {
+            true => log!(
+                "Base is RAM base 0x{:x} + text offset 0x{:x}",
+                kernel_image.ram_base(best),
+                kernel_image.text_offset
+            ),
+            false => log!(
+                "Base isn't a 2 MiB aligned RAM base + text offset 0x{:x}",
+                kernel_image.text_offset
+            ),
+        }
+    }
+
+    /* Keep a few of the strings referenced at the chosen base, spread through the image, to print
+    with it */
+    if let Some((_kind, strings_index)) = targets
+        .iter()
+        .find(|(kind, _index)| *kind == HeuristicKind::Strings)
+    {
+        let matched = dump::matched(base.best().base, strings_index, &addresses_index);
+        let step = matched.len().div_ceil(EXAMPLE_STRINGS).max(1);
+        base.strings = matched
+            .into_iter()
+            .step_by(step)
+            .filter_map(|(file_offset, address)| {
+                let offset = file_offset.try_into().ok()?;
+                let text = dump::string_at(bytes, offset, MAX_EXAMPLE_LENGTH);
+                Some((address, text.escape_ascii().to_string()))
+            })
+            .collect();
+    }
+
+    if let Some(path) = &args.emit_emu_map {
+        let map = emu::map(
+            base.best().base.into(),
+            len,
+            bytes.len(),
+            &regions::pointer_dense(bytes, read_address_bytes, pointer_mask, args.pointer_min),
+            &regions::string_dense(bytes, args.charset_bytes()),
+            &regions::unpadded(bytes),
+        );
+        emu::write(path, &map, N * 2).map_err(|e| format!("{path}: {e}"))?;
+        log!(
+            "Wrote: emulator memory map of {} regions to {}",
+            map.regions.len(),
+            path
+        );
+    }
+
+    if let Some(path) = &args.dump_matched_strings {
+        match targets
+            .iter()
+            .find(|(kind, _index)| *kind == HeuristicKind::Strings)
+        {
+            Some((_kind, strings_index)) => {
+                let matched = dump::matched_strings(
+                    path,
+                    bytes,
+                    base.best().base,
+                    strings_index,
+                    &addresses_index,
+                    args.max_string_length,
+                )
+                .map_err(|e| format!("{path}: {e}"))?;
+                log!("Wrote: {} matched strings to {}", matched, path);
+            }
+            None => log!("Not writing matched strings, the strings heuristic is disabled"),
+        }
+    }
+
+    Ok(Some(base))
+}
+
+/* A load address found in the image (e.g. in a vendor header), and where it came from */
+#[derive(Clone, Debug)]
+pub struct Hint {
+    pub source: String,
+    pub address: u64,
+}
+
+/* A candidate base address, with its frequency (the number of targets it correlates), its score
+(its frequency, unless adjusted by a script), the fraction of the total score of all candidates which
+it accounts for, and the key by which it is ranked (its score, boosted if it is a known base address
+and those are used, or is consistent with a kernel's text offset) */
+#[derive(Clone, Copy, Debug)]
+pub struct Candidate<T> {
+    pub base: T,
+    pub frequency: usize,
+    pub score: usize,
+    pub coverage: f64,
+    pub rank: usize,
+}
+
+/* Every candidate base address which survived filtering, best first, and the address and text of
+a few of the strings referenced at the best */
+pub struct Base<T> {
+    candidates: Vec<Candidate<T>>,
+    strings: Vec<(T, String)>,
+    /* The number of bytes analysed and the length of the image, if only part of it was */
+    analysed: Option<(usize, usize)>,
+    /* The minimum coverage of other candidates reported with the best (e.g. for the other segments
+    of an image), if any */
+    min_coverage: Option<f64>,
+}
+
+impl<T: PartialEq> Base<T> {
+    /* The candidates must already be sorted, and there is no base if there are none */
+    pub fn new(candidates: Vec<Candidate<T>>) -> Option<Base<T>> {
+        (!candidates.is_empty()).then_some(Base {
+            candidates,
+            strings: Vec::new(),
+            analysed: None,
+            min_coverage: None,
+        })
+    }
+
+    pub fn best(&self) -> &Candidate<T> {
+        &self.candidates[0]
+    }
+
+    pub fn top(&self, n: usize) -> &[Candidate<T>] {
+        &self.candidates[..n.min(self.candidates.len())]
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Candidate<T>> {
+        self.candidates.iter()
+    }
+
+    /* The best candidate, as the result of the search, for an image of the given length with
+    addresses of the given number of bits */
+    pub fn found(&self, len: usize, bits: u32) -> Found
+    where
+        T: Copy + Into<u64>,
+    {
+        let base: u64 = self.best().base.into();
+        let end = base as u128 + len as u128;
+        Found {
+            base,
+            size: len as u64,
+            end: end.min(u64::MAX as u128) as u64,
+            bits,
+            analysed: self.analysed,
+            /* Images loaded at a known base are evidently not over peripherals */
+            mmio: known::lookup(base)
+                .is_none()
+                .then(|| known::mmio(base, end.min(u64::MAX as u128) as u64))
+                .flatten(),
+            strings: self
+                .strings
+                .iter()
+                .map(|(address, text)| ((*address).into(), text.clone()))
+                .collect(),
+            others: match self.min_coverage {
+                Some(min_coverage) => self
+                    .candidates
+                    .iter()
+                    .skip(1)
+                    .filter(|candidate| candidate.coverage >= min_coverage)
+                    .map(|candidate| (candidate.base.into(), candidate.score, candidate.coverage))
+                    .collect(),
+                None => Vec::new(),
+            },
+        }
+    }
+
+    /* Move the given candidates to the front, in the given order */
+    fn rerank(&mut self, order: &[T]) {
+        let n = order.len().min(self.candidates.len());
+        self.candidates[..n].sort_by_key(|candidate| {
+            order
+                .iter()
+                .position(|base| *base == candidate.base)
+                .unwrap_or(n)
+        });
+    }
+}
+
+/* The base address found, with a few of the strings it references so the result can be trusted at a
+glance. The span of addresses the image occupies is also given (with its end exclusive, and limited
+to the largest address), along with whether it runs past the top of the address space or over the
+registers of common peripherals, either of which suggests the base is wrong. Other candidates with
+enough of the evidence are given too, since an image may hold several segments with distinct bases. */
+pub struct Found {
+    pub base: u64,
+    pub end: u64,
+    pub size: u64,
+    /* Number of bits in an address */
+    pub bits: u32,
+    /* The number of bytes analysed and the length of the image, if only part of it was */
+    pub analysed: Option<(usize, usize)>,
+    pub mmio: Option<(u64, u64, &'static str)>,
+    pub strings: Vec<(u64, String)>,
+    /* Other candidates with enough of the evidence to be reported, as base, score and coverage */
+    pub others: Vec<(u64, usize, f64)>,
+}
+
+impl Found {
+    /* The base address alone, as printed to stdout */
+    pub fn address(&self) -> String {
+        format::bare(self.base, (self.bits / 4) as usize)
+    }
+}
+
+impl Display for Found {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let digits = (self.bits / 4) as usize;
+        write!(f, "Found base: {}", self.address())?;
+        write!(
+            f,
+            "\n\tspan: {}-{} (0x{:x} bytes)",
+            format::prefixed(self.base, digits),
+            format::prefixed(self.end, digits),
+            self.size
+        )?;
+        if self.base as u128 + self.size as u128 > 1 << self.bits {
+            write!(
+                f,
+                "\n\twarning: span exceeds the {}-bit address space",
+                self.bits
+            )?;
+        }
+        if let Some((analysed, len)) = self.analysed {
+            write!(
+                f,
+                "\n\twarning: partial result, only 0x{:x} of 0x{:x} bytes were analysed",
+                analysed, len
+            )?;
+        }
+        if let Some((start, end, name)) = self.mmio {
+            write!(
+                f,
+                "\n\twarning: span overlaps {} at 0x{:x}-0x{:x}",
+                name, start, end
+            )?;
+        }
+        for (address, text) in self.strings.iter() {
+            write!(
+                f,
+                "\n\t{}: \"{}\"",
+                format::prefixed(*address, digits),
+                text
+            )?;
+        }
+        for (base, score, coverage) in self.others.iter() {
+            write!(
+                f,
+                "\n\talso: {}: {} ({:.2}%)",
+                format::prefixed(*base, digits),
+                score,
+                100.0 * coverage
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* An index holding the given values at the given page offsets */
+    fn index<V: Pack + Send + Sync>(values: &[(usize, V)]) -> PageIndex<V> {
+        let mut buckets = vec![Vec::new(); PAGE_SIZE];
+        for &(page_offset, value) in values {
+            buckets[page_offset].push(value);
+        }
+        pack_buckets(buckets)
+    }
+
+    #[test]
+    fn sweep_skips_addresses_below_a_target_near_the_top() {
+        let targets = PackedVec::from_sorted(vec![(0xFFFF_F010u32, 1)]);
+        let addresses = PackedVec::from_sorted(vec![0x10u32, 0x1010, 0xFFFF_F010]);
+        assert_eq!(sweep::<u32, 4>(&targets, &addresses, Some), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn sweep_counts_a_pointer_of_zero() {
+        let targets = PackedVec::from_sorted(vec![(0u32, 1), (0x1000, 2)]);
+        let addresses = PackedVec::from_sorted(vec![0u32, 0x1000]);
+        assert_eq!(
+            sweep::<u32, 4>(&targets, &addresses, Some),
+            vec![(0, 3), (0x1000, 1)]
+        );
+    }
+
+    #[test]
+    fn sweep_saturates_the_frequency() {
+        let targets = PackedVec::from_sorted(vec![(0u64, usize::MAX), (0x1000, usize::MAX)]);
+        let addresses = PackedVec::from_sorted(vec![0x1000u64, 0x2000]);
+        assert_eq!(
+            sweep::<u64, 8>(&targets, &addresses, Some),
+            vec![(0, usize::MAX), (0x1000, usize::MAX), (0x2000, usize::MAX)]
+        );
+    }
+
+    #[test]
+    fn totals_saturate() {
+        assert_eq!(
+            total_candidates(vec![(1u32, usize::MAX), (0, 1), (1, 1)]),
+            vec![(0, 1), (1, usize::MAX)]
+        );
+        assert_eq!(
+            merge_candidates(
+                vec![(0u32, 1), (1, usize::MAX)],
+                vec![(1, usize::MAX), (2, 1)]
+            ),
+            vec![(0, 1), (1, usize::MAX), (2, 1)]
+        );
+    }
+
+    #[test]
+    fn correlate_never_wraps_at_the_edges_of_the_address_space() {
+        let targets_index = index(&[(0xFF0, (0xFFFF_FFF0u32, 1)), (0, (0, 1))]);
+        let addresses_index = index(&[(0xFF0, 0xFFFF_FFF0u32), (0xFF0, 0xFF0), (0, 0)]);
+        assert_eq!(
+            correlate::<u32, 4>("test", &targets_index, &addresses_index),
+            vec![(0, 2)]
+        );
+    }
+
+    #[test]
+    fn correlate_keeps_24_bit_bases_in_range() {
+        let targets_index = index(&[(0xFF0, (0xFF_FFF0u32, 1))]);
+        let addresses_index = index(&[(0xFF0, 0xFF_FFF0u32), (0xFF0, 0x0FF0)]);
+        assert_eq!(
+            correlate::<u32, 3>("test", &targets_index, &addresses_index),
+            vec![(0, 1)]
+        );
+    }
+}
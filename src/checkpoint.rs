@@ -0,0 +1,97 @@
+use {
+    crate::{PageIndex, RBaseTraits},
+    rustc_hash::FxHasher,
+    std::{
+        fs::{rename, File},
+        hash::{Hash, Hasher},
+        io::{BufRead, BufReader, BufWriter, Result, Write},
+    },
+};
+
+/* A checkpoint records the candidates totalled so far by a phase of the correlation, and how many of
+its batches of page offsets have been correlated:
+
+    rbase checkpoint
+    phase: Collecting candidate base addresses
+    fingerprint: 0123456789abcdef
+    batches: 12/64
+    0x80010000	3000
+    ...
+
+The fingerprint identifies the targets and addresses being correlated, so a checkpoint is only
+resumed by a scan of the same image with the same options. */
+const HEADER: &str = "rbase checkpoint";
+
+pub fn fingerprint<T: RBaseTraits<T, N>, const N: usize>(
+    targets_index: &PageIndex<(T, usize)>,
+    addresses_index: &PageIndex<T>,
+) -> u64 {
+    let mut hasher = FxHasher::default();
+    N.hash(&mut hasher);
+    for (targets, addresses) in targets_index.iter().zip(addresses_index) {
+        targets.len().hash(&mut hasher);
+        targets.iter().for_each(|target| target.hash(&mut hasher));
+        addresses.len().hash(&mut hasher);
+        addresses
+            .iter()
+            .for_each(|address| address.hash(&mut hasher));
+    }
+    hasher.finish()
+}
+
+/* Write the checkpoint to a temporary file first, so an interruption while writing never leaves a
+truncated checkpoint behind */
+pub fn save<T: RBaseTraits<T, N>, const N: usize>(
+    path: &str,
+    phase: &str,
+    fingerprint: u64,
+    batch: usize,
+    batches: usize,
+    totals: &[(T, usize)],
+) -> Result<()> {
+    let temp = format!("{path}.tmp");
+    let mut writer = BufWriter::new(File::create(&temp)?);
+    writeln!(writer, "{HEADER}")?;
+    writeln!(writer, "phase: {phase}")?;
+    writeln!(writer, "fingerprint: {fingerprint:016x}")?;
+    writeln!(writer, "batches: {batch}/{batches}")?;
+    for (base, frequency) in totals.iter() {
+        writeln!(writer, "0x{base:0width$x}\t{frequency}", width = N * 2)?;
+    }
+    writer.flush()?;
+    drop(writer);
+    rename(temp, path)
+}
+
+/* The number of batches already correlated and the candidates totalled by them, if there is a
+checkpoint for this phase of the same scan */
+pub fn load<T: RBaseTraits<T, N>, const N: usize>(
+    path: &str,
+    phase: &str,
+    fingerprint: u64,
+    batches: usize,
+) -> Option<(usize, Vec<(T, usize)>)> {
+    let file = File::open(path).ok()?;
+    let mut lines = BufReader::new(file).lines().map_while(|line| line.ok());
+    if lines.next()? != HEADER
+        || lines.next()? != format!("phase: {phase}")
+        || lines.next()? != format!("fingerprint: {fingerprint:016x}")
+    {
+        return None;
+    }
+    let line = lines.next()?;
+    let (batch, total) = line.strip_prefix("batches: ")?.split_once('/')?;
+    if total.parse::<usize>().ok()? != batches {
+        return None;
+    }
+    let batch = batch.parse::<usize>().ok()?;
+
+    let totals = lines
+        .map(|line| {
+            let (base, frequency) = line.split_once('\t')?;
+            let base = usize::from_str_radix(base.strip_prefix("0x")?, 16).ok()?;
+            Some((T::try_from(base).ok()?, frequency.parse().ok()?))
+        })
+        .collect::<Option<Vec<(T, usize)>>>()?;
+    Some((batch, totals))
+}
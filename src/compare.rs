@@ -0,0 +1,103 @@
+/* Compares the candidates with those found by another basefind tool (e.g. basefind.py or rbasefind),
+so users can see where the tools agree and investigate where they don't. Their results are read from
+their output, taking each line starting with an address followed by a score (optionally preceded by
+a rank), such as "0x80010000: 3000" or " 1: 0x80010000 3000". */
+use {
+    crate::{format, logging::log},
+    regex::Regex,
+    std::fs::read_to_string,
+};
+
+const RESULT: &str = r"^\s*(?:\d+[:.]\s+)?0x([0-9a-fA-F]+)[:,]?\s+(\d+)";
+
+pub fn load(path: &str) -> Result<Vec<(u64, usize)>, String> {
+    let source = read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let regex = Regex::new(RESULT).unwrap();
+    let mut results: Vec<(u64, usize)> = source
+        .lines()
+        .filter_map(|line| {
+            let captures = regex.captures(line)?;
+            Some((
+                u64::from_str_radix(&captures[1], 16).ok()?,
+                captures[2].parse().ok()?,
+            ))
+        })
+        .collect();
+    results.sort_by_key(|&(_base, score)| std::cmp::Reverse(score));
+    Ok(results)
+}
+
+/* The fraction of the best score by which it beats the second */
+fn margin(results: &[(u64, usize)]) -> String {
+    match results {
+        [(_, best), (_, second), ..] if *best != 0 => {
+            format!("{:.2}%", 100.0 * (best - second) as f64 / *best as f64)
+        }
+        [_] => "100.00%".to_string(),
+        _ => "none".to_string(),
+    }
+}
+
+fn rank(results: &[(u64, usize)], base: u64) -> String {
+    match results.iter().position(|&(other, _score)| other == base) {
+        Some(idx) => format!("{:>4} {:>10}", idx + 1, results[idx].1),
+        None => format!("{:>4} {:>10}", "-", "-"),
+    }
+}
+
+/* Log a table of the leading candidates of both, and a summary of their agreement */
+pub fn report(ours: &[(u64, usize)], theirs: &[(u64, usize)], top: usize, width: usize) {
+    let mut bases: Vec<u64> = Vec::new();
+    for &(base, _score) in ours.iter().take(top).chain(theirs.iter().take(top)) {
+        if !bases.contains(&base) {
+            bases.push(base);
+        }
+    }
+    log!(
+        "Compare: {:<w$}  {:>4} {:>10}  {:>4} {:>10}",
+        "base",
+        "ours",
+        "score",
+        "them",
+        "score",
+        w = width + 2
+    );
+    for base in bases.iter() {
+        log!(
+            "Compare: {}  {}  {}",
+            format::prefixed(*base, width),
+            rank(ours, *base),
+            rank(theirs, *base)
+        );
+    }
+    match (ours.first(), theirs.first()) {
+        (Some(ours), Some(theirs)) if ours.0 == theirs.0 => {
+            log!(
+                "Compare: best candidates agree: {}",
+                format::prefixed(ours.0, width)
+            )
+        }
+        (Some(ours), Some(theirs)) => log!(
+            "Compare: best candidates differ: ours {}, theirs {}",
+            format::prefixed(ours.0, width),
+            format::prefixed(theirs.0, width)
+        ),
+        _ => log!("Compare: no candidates to compare"),
+    }
+    log!(
+        "Compare: margin of the best over the second: ours {}, theirs {}",
+        margin(ours),
+        margin(theirs)
+    );
+    let only = |a: &[(u64, usize)], b: &[(u64, usize)]| {
+        a.iter()
+            .take(top)
+            .filter(|&&(base, _score)| !b.iter().any(|&(other, _score)| other == base))
+            .count()
+    };
+    log!(
+        "Compare: {} of our top {top} candidates are unique to us, {} of theirs to them",
+        only(ours, theirs),
+        only(theirs, ours)
+    );
+}
@@ -0,0 +1,56 @@
+/* Maps version banners to CPE identifiers, so the components of the firmware can be looked up in
+vulnerability databases. The database is a local file, one entry per line, of a regex matched
+against the text of each banner and a CPE template separated by a tab, e.g.
+
+    # Comments and blank lines are ignored
+    ^Linux version (\d+\.\d+\.\d+)	cpe:2.3:o:linux:linux_kernel:$1:*:*:*:*:*:*:*
+    ^U-Boot (\d{4}\.\d{2})	cpe:2.3:a:denx:u-boot:$1:*:*:*:*:*:*:*
+
+where the template may refer to the regex's capture groups as $1 or ${name}. */
+use {crate::banner::Banner, regex::Regex, std::fs::read_to_string};
+
+pub struct Database {
+    entries: Vec<(Regex, String)>,
+}
+
+pub struct Cpe<'a> {
+    pub id: String,
+    pub banner: &'a Banner,
+}
+
+impl Database {
+    pub fn load(path: &str) -> Result<Database, String> {
+        let source = read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        let mut entries = Vec::new();
+        for (idx, line) in source.lines().enumerate() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let (regex, template) = line.split_once('\t').ok_or(format!(
+                "{path}:{}: expected a regex and CPE separated by a tab",
+                idx + 1
+            ))?;
+            let regex = Regex::new(regex).map_err(|e| format!("{path}:{}: {e}", idx + 1))?;
+            entries.push((regex, template.trim().to_string()));
+        }
+        Ok(Database { entries })
+    }
+
+    /* The distinct CPEs of the banners, in the order of the banners they were first found in */
+    pub fn enrich<'a>(&self, banners: &'a [Banner]) -> Vec<Cpe<'a>> {
+        let mut cpes: Vec<Cpe> = Vec::new();
+        for banner in banners {
+            for (regex, template) in &self.entries {
+                let Some(captures) = regex.captures(&banner.text) else {
+                    continue;
+                };
+                let mut id = String::new();
+                captures.expand(template, &mut id);
+                if cpes.iter().all(|cpe| cpe.id != id) {
+                    cpes.push(Cpe { id, banner });
+                }
+            }
+        }
+        cpes
+    }
+}
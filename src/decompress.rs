@@ -0,0 +1,61 @@
+/* Kernels are often compressed (e.g. the Image.gz or Image.lz4 of an Android boot image), hiding
+their strings and pointers, so they are decompressed before being analysed */
+use {
+    flate2::read::MultiGzDecoder,
+    lz4_flex::{block, frame::FrameDecoder},
+    std::io::Read,
+};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b, 0x08];
+const LZ4_FRAME_MAGIC: &[u8] = &[0x04, 0x22, 0x4d, 0x18];
+/* The legacy LZ4 format used by the Linux kernel, being blocks each preceded by their compressed
+size, which decompress to at most 8 MiB */
+const LZ4_LEGACY_MAGIC: &[u8] = &[0x02, 0x21, 0x4c, 0x18];
+const LZ4_LEGACY_BLOCK_SIZE: usize = 0x800000;
+
+/* Limit on the size of the decompressed data, in case it is corrupt */
+const MAX_DECOMPRESSED: u64 = 1 << 32;
+
+fn read_all(reader: impl Read) -> Option<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    reader
+        .take(MAX_DECOMPRESSED)
+        .read_to_end(&mut decompressed)
+        .ok()?;
+    Some(decompressed)
+}
+
+fn lz4_legacy(mut bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    let mut block = vec![0; LZ4_LEGACY_BLOCK_SIZE];
+    /* The kernel's build appends the uncompressed size, so stop at anything which isn't a block */
+    while let Some(size) = bytes.get(..4) {
+        let size = u32::from_le_bytes(size.try_into().unwrap()) as usize;
+        let Some(compressed) = bytes.get(4..4 + size) else {
+            break;
+        };
+        let Ok(len) = block::decompress_into(compressed, &mut block) else {
+            break;
+        };
+        decompressed.extend_from_slice(&block[..len]);
+        bytes = &bytes[4 + size..];
+        if bytes.starts_with(LZ4_LEGACY_MAGIC) {
+            bytes = &bytes[LZ4_LEGACY_MAGIC.len()..];
+        }
+    }
+    (!decompressed.is_empty()).then_some(decompressed)
+}
+
+/* The name of the compression and the decompressed data, if the bytes are compressed in a
+recognised format */
+pub fn decompress(bytes: &[u8]) -> Option<(&'static str, Vec<u8>)> {
+    if bytes.starts_with(GZIP_MAGIC) {
+        Some(("gzip", read_all(MultiGzDecoder::new(bytes))?))
+    } else if bytes.starts_with(LZ4_FRAME_MAGIC) {
+        Some(("LZ4", read_all(FrameDecoder::new(bytes))?))
+    } else if bytes.starts_with(LZ4_LEGACY_MAGIC) {
+        Some(("LZ4", lz4_legacy(&bytes[LZ4_LEGACY_MAGIC.len()..])?))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,162 @@
+use {
+    crate::{format, PageIndex, RBaseTraits},
+    flate2::{write::GzEncoder, Compression},
+    std::{
+        fs::File,
+        io::{BufWriter, Result, Write},
+    },
+};
+
+/* A dump file, which is gzip compressed if its name ends in .gz since the dumps for large images can
+run into gigabytes */
+pub enum Output {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl Output {
+    pub fn create(path: &str) -> Result<Output> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(match path.ends_with(".gz") {
+            true => Output::Gzip(GzEncoder::new(file, Compression::default())),
+            false => Output::Plain(file),
+        })
+    }
+
+    /* Flush the file, first writing the gzip trailer if it is compressed */
+    pub fn finish(self) -> Result<()> {
+        match self {
+            Output::Plain(mut file) => file.flush(),
+            Output::Gzip(encoder) => encoder.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            Output::Plain(file) => file.write(buf),
+            Output::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            Output::Plain(file) => file.flush(),
+            Output::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/* Text of the string at the given offset, up to the first non-printable character */
+pub fn string_at(bytes: &[u8], offset: usize, max_string_length: usize) -> &[u8] {
+    let tail = &bytes[offset.min(bytes.len())..];
+    let length = tail
+        .iter()
+        .take(max_string_length)
+        .take_while(|b| b.is_ascii_graphic() || b.is_ascii_whitespace())
+        .count();
+    &tail[..length]
+}
+
+/* The file offset and address of each string referenced by at least one address at the given base
+address, in order of file offset */
+pub fn matched<T: RBaseTraits<T, N>, const N: usize>(
+    base: T,
+    strings_index: &PageIndex<(T, usize)>,
+    addresses_index: &PageIndex<T>,
+) -> Vec<(T, T)> {
+    let mut matched: Vec<(T, T)> = strings_index
+        .iter()
+        .zip(addresses_index)
+        .flat_map(|(strings, addresses)| {
+            strings.iter().filter_map(|(file_offset, _weight)| {
+                let address = base.checked_add(file_offset)?;
+                addresses
+                    .contains(address)
+                    .then_some((file_offset, address))
+            })
+        })
+        .collect();
+    matched.sort_unstable();
+    matched
+}
+
+/* Write each string referenced by at least one address at the given base address */
+pub fn matched_strings<T: RBaseTraits<T, N>, const N: usize>(
+    path: &str,
+    bytes: &[u8],
+    base: T,
+    strings_index: &PageIndex<(T, usize)>,
+    addresses_index: &PageIndex<T>,
+    max_string_length: usize,
+) -> Result<usize> {
+    let matched = matched(base, strings_index, addresses_index);
+    let mut writer = Output::create(path)?;
+    writeln!(writer, "offset\taddress\tstring")?;
+    for (file_offset, address) in matched.iter() {
+        let text = match (*file_offset).try_into() {
+            Ok(offset) => string_at(bytes, offset, max_string_length),
+            Err(_) => &[],
+        };
+        writeln!(
+            writer,
+            "0x{file_offset:0width$x}\t{}\t{}",
+            format::prefixed((*address).into(), N * 2),
+            text.escape_ascii(),
+            width = N * 2
+        )?;
+    }
+    writer.finish()?;
+    Ok(matched.len())
+}
+
+/* Percentiles of the candidate scores summarised in the plot data */
+const PERCENTILES: [f64; 5] = [50.0, 90.0, 99.0, 99.9, 100.0];
+
+/* Write the score of each candidate base address (in decimal, so it can be plotted directly) as tab
+separated values, preceded by a comment summarising the distribution of the scores */
+pub fn plot_data<T: RBaseTraits<T, N>, const N: usize>(
+    path: &str,
+    candidates: &[(T, usize)],
+) -> Result<()> {
+    let mut scores: Vec<usize> = candidates.iter().map(|&(_base, score)| score).collect();
+    scores.sort_unstable();
+    let percentiles: Vec<String> = PERCENTILES
+        .iter()
+        .map(|&percentile| {
+            let idx = (percentile / 100.0 * scores.len() as f64).ceil() as usize;
+            let score = scores.get(idx.max(1) - 1).copied().unwrap_or(0);
+            format!("p{percentile}: {score}")
+        })
+        .collect();
+
+    let mut sorted: Vec<(T, usize)> = candidates.to_vec();
+    sorted.sort_unstable();
+
+    let mut writer = Output::create(path)?;
+    writeln!(writer, "# candidates: {}", candidates.len())?;
+    writeln!(writer, "# score percentiles: {}", percentiles.join(", "))?;
+    writeln!(writer, "base\tscore")?;
+    for (base, score) in sorted {
+        writeln!(writer, "{}\t{score}", base.into())?;
+    }
+    writer.finish()
+}
+
+/* Write each decoded pointer and the file offset at which it was found */
+pub fn pointers<T: RBaseTraits<T, N>, const N: usize>(
+    path: &str,
+    pointers: &[(usize, T)],
+) -> Result<()> {
+    let mut writer = Output::create(path)?;
+    writeln!(writer, "offset\tpointer")?;
+    for (file_offset, pointer) in pointers.iter() {
+        writeln!(
+            writer,
+            "0x{file_offset:08x}\t{}",
+            format::prefixed((*pointer).into(), N * 2)
+        )?;
+    }
+    writer.finish()
+}
@@ -0,0 +1,85 @@
+/* The program headers of an ELF file, giving the address at which each segment is loaded */
+use crate::{
+    args::{Endian, Size},
+    memdump::Region,
+};
+
+/* Types of ELF file */
+pub const ET_EXEC: u64 = 2;
+pub const ET_DYN: u64 = 3;
+pub const ET_CORE: u64 = 4;
+
+pub struct Elf {
+    pub kind: u64,
+    pub size: Size,
+    pub endian: Endian,
+    /* The PT_LOAD segments, ignoring any without data in the file */
+    pub segments: Vec<Region>,
+}
+
+fn read(bytes: &[u8], offset: usize, len: usize, endian: Endian) -> Option<u64> {
+    let field = bytes.get(offset..offset.checked_add(len)?)?;
+    let mut word = [0u8; 8];
+    match endian {
+        Endian::Little => {
+            word[..len].copy_from_slice(field);
+            Some(u64::from_le_bytes(word))
+        }
+        Endian::Big => {
+            word[8 - len..].copy_from_slice(field);
+            Some(u64::from_be_bytes(word))
+        }
+    }
+}
+
+pub fn parse(bytes: &[u8]) -> Option<Elf> {
+    const MAGIC: &[u8] = b"\x7fELF";
+    const PT_LOAD: u64 = 1;
+    if !bytes.starts_with(MAGIC) {
+        return None;
+    }
+    let size = match bytes.get(4)? {
+        1 => Size::Bits32,
+        2 => Size::Bits64,
+        _ => return None,
+    };
+    let endian = match bytes.get(5)? {
+        1 => Endian::Little,
+        2 => Endian::Big,
+        _ => return None,
+    };
+    let read = |offset, len| read(bytes, offset, len, endian);
+    let kind = read(16, 2)?;
+    /* Offsets of e_phoff, e_phentsize and e_phnum, then of p_offset, p_vaddr and p_filesz, and the
+    size of an address or offset */
+    let (header, entry, word) = match size {
+        Size::Bits64 => ((32, 54, 56), (8, 16, 32), 8),
+        _ => ((28, 42, 44), (4, 8, 16), 4),
+    };
+    let phoff = read(header.0, word)? as usize;
+    let phentsize = read(header.1, 2)? as usize;
+    let phnum = read(header.2, 2)? as usize;
+    let mut segments = Vec::new();
+    for idx in 0..phnum {
+        let phdr = phoff.checked_add(idx.checked_mul(phentsize)?)?;
+        if read(phdr, 4)? != PT_LOAD {
+            continue;
+        }
+        let offset = read(phdr + entry.0, word)? as usize;
+        let address = read(phdr + entry.1, word)?;
+        let len = read(phdr + entry.2, word)? as usize;
+        if len != 0 && offset.checked_add(len)? <= bytes.len() {
+            segments.push(Region {
+                offset,
+                len,
+                address,
+            });
+        }
+    }
+    Some(Elf {
+        kind,
+        size,
+        endian,
+        segments,
+    })
+}
@@ -0,0 +1,137 @@
+/* A memory map of the image loaded at the base address found, for generating emulation and fuzzing
+harnesses (e.g. with Unicorn or QEMU). The permissions of each page are guessed from its contents:
+pages dense with pointers are data, pages dense with strings are read-only data, padding is left
+writable (being .bss or free memory as often as not) and anything else is taken to be code. Pages
+which weren't analysed are given every permission. Consecutive pages with the same guess form a
+region. The map is written as YAML if the file name ends in .yaml or .yml, and as JSON otherwise. */
+use {
+    crate::{dump::Output, format, regions::Regions, PAGE_SIZE},
+    std::io::{Result, Write},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Code,
+    ReadOnlyData,
+    Data,
+    Padding,
+    Unknown,
+}
+
+impl Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Code => "code",
+            Kind::ReadOnlyData => "rodata",
+            Kind::Data => "data",
+            Kind::Padding => "padding",
+            Kind::Unknown => "unknown",
+        }
+    }
+
+    fn permissions(&self) -> &'static str {
+        match self {
+            Kind::Code => "r-x",
+            Kind::ReadOnlyData => "r--",
+            Kind::Data | Kind::Padding => "rw-",
+            Kind::Unknown => "rwx",
+        }
+    }
+}
+
+pub struct Region {
+    pub kind: Kind,
+    pub file_offset: usize,
+    pub address: u64,
+    pub size: u64,
+}
+
+pub struct MemoryMap {
+    pub base: u64,
+    /* Size of the image, rounded up to a whole number of pages */
+    pub size: u64,
+    pub regions: Vec<Region>,
+}
+
+/* The regions of an image of the given length at the base, of which the given number of bytes were
+analysed for the regions dense with pointers and strings, and those which aren't padding */
+pub fn map(
+    base: u64,
+    len: usize,
+    analysed: usize,
+    data: &Regions,
+    strings: &Regions,
+    unpadded: &Regions,
+) -> MemoryMap {
+    let kind = |file_offset: usize| match file_offset {
+        _ if file_offset >= analysed => Kind::Unknown,
+        _ if !unpadded.contains(file_offset) => Kind::Padding,
+        _ if data.contains(file_offset) => Kind::Data,
+        _ if strings.contains(file_offset) => Kind::ReadOnlyData,
+        _ => Kind::Code,
+    };
+    let mut regions: Vec<Region> = Vec::new();
+    for file_offset in (0..len).step_by(PAGE_SIZE) {
+        let kind = kind(file_offset);
+        match regions.last_mut() {
+            Some(region) if region.kind == kind => region.size += PAGE_SIZE as u64,
+            _ => regions.push(Region {
+                kind,
+                file_offset,
+                address: base.saturating_add(file_offset as u64),
+                size: PAGE_SIZE as u64,
+            }),
+        }
+    }
+    MemoryMap {
+        base,
+        size: len.next_multiple_of(PAGE_SIZE) as u64,
+        regions,
+    }
+}
+
+/* Write the map, with addresses of the given number of digits */
+pub fn write(path: &str, map: &MemoryMap, digits: usize) -> Result<()> {
+    let name = path.strip_suffix(".gz").unwrap_or(path);
+    let mut writer = Output::create(path)?;
+    if name.ends_with(".yaml") || name.ends_with(".yml") {
+        writeln!(writer, "base: {}", format::prefixed(map.base, digits))?;
+        writeln!(writer, "size: 0x{:x}", map.size)?;
+        writeln!(writer, "regions:")?;
+        for region in map.regions.iter() {
+            writeln!(writer, "  - name: {}", region.kind.name())?;
+            writeln!(
+                writer,
+                "    address: {}",
+                format::prefixed(region.address, digits)
+            )?;
+            writeln!(writer, "    size: 0x{:x}", region.size)?;
+            writeln!(writer, "    file_offset: 0x{:x}", region.file_offset)?;
+            writeln!(writer, "    permissions: {}", region.kind.permissions())?;
+        }
+    } else {
+        /* JSON has no hexadecimal numbers, so addresses are written in decimal */
+        let regions: Vec<String> = map
+            .regions
+            .iter()
+            .map(|region| {
+                format!(
+                    "    {{\"name\": \"{}\", \"address\": {}, \"size\": {}, \"file_offset\": {}, \"permissions\": \"{}\"}}",
+                    region.kind.name(),
+                    region.address,
+                    region.size,
+                    region.file_offset,
+                    region.kind.permissions()
+                )
+            })
+            .collect();
+        writeln!(writer, "{{")?;
+        writeln!(writer, "  \"base\": {},", map.base)?;
+        writeln!(writer, "  \"size\": {},", map.size)?;
+        writeln!(writer, "  \"regions\": [")?;
+        writeln!(writer, "{}", regions.join(",\n"))?;
+        writeln!(writer, "  ]")?;
+        writeln!(writer, "}}")?;
+    }
+    writer.finish()
+}
@@ -0,0 +1,239 @@
+/* Espressif (ESP32 and ESP8266) application and boot loader images are a header followed by a list of
+segments, each declaring the address at which it is loaded (into IRAM, DRAM or the flash cache). The
+segments aren't contiguous in memory, so no single base address applies to the image, but each can
+be analysed on its own and the address found checked against the one declared. A dump of the whole
+flash has a partition table, giving the offsets of the application images. */
+
+/* Offsets of the partition table and boot loader in the flash (the boot loader is at the start of
+the flash for the newer chips, and after the first 4 KiB for the ESP32 and ESP32-S2) */
+const PARTITION_TABLE_OFFSET: usize = 0x8000;
+const BOOTLOADER_OFFSETS: [usize; 2] = [0x0, 0x1000];
+
+const IMAGE_MAGIC: u8 = 0xe9;
+const MAX_SEGMENTS: usize = 16;
+
+/* Chips of the ESP32 family, by the id in their extended header */
+const CHIPS: [(u16, &str); 10] = [
+    (0, "ESP32"),
+    (2, "ESP32-S2"),
+    (5, "ESP32-C3"),
+    (9, "ESP32-S3"),
+    (12, "ESP32-C2"),
+    (13, "ESP32-C6"),
+    (16, "ESP32-H2"),
+    (18, "ESP32-P4"),
+    (20, "ESP32-C61"),
+    (23, "ESP32-C5"),
+];
+
+/* Ranges of the addresses at which segments can be loaded, rejecting headers which merely start
+with the magic byte (e.g. an x86 jump) */
+const ESP32_ADDRESSES: (u32, u32) = (0x3c00_0000, 0x6000_0000);
+const ESP8266_ADDRESSES: (u32, u32) = (0x3ff0_0000, 0x4030_0000);
+
+pub struct Partition {
+    pub label: String,
+    pub app: bool,
+    pub offset: usize,
+    pub size: usize,
+}
+
+pub struct Segment {
+    /* File offset of its data */
+    pub offset: usize,
+    pub len: usize,
+    pub load_address: u32,
+}
+
+pub struct Image {
+    pub offset: usize,
+    pub chip: &'static str,
+    pub entry: u32,
+    pub segments: Vec<Segment>,
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let word = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(word.try_into().unwrap()))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    let half = bytes.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes(half.try_into().unwrap()))
+}
+
+/* The segments following a header of the given size, if they all fit in the image and are loaded
+within the range of addresses */
+fn segments(
+    bytes: &[u8],
+    offset: usize,
+    header_size: usize,
+    (low, high): (u32, u32),
+) -> Option<Vec<Segment>> {
+    let count = *bytes.get(offset + 1)? as usize;
+    if !(1..=MAX_SEGMENTS).contains(&count) {
+        return None;
+    }
+    let mut segments = Vec::new();
+    let mut next = offset + header_size;
+    for _ in 0..count {
+        let load_address = read_u32(bytes, next)?;
+        let len = read_u32(bytes, next + 4)? as usize;
+        let start = next + 8;
+        let end = start.checked_add(len)?;
+        if !(low..high).contains(&load_address) || !len.is_multiple_of(4) || end > bytes.len() {
+            return None;
+        }
+        segments.push(Segment {
+            offset: start,
+            len,
+            load_address,
+        });
+        next = end;
+    }
+    Some(segments)
+}
+
+/* The image at the offset, trying the ESP32 header (with its extended header) before the shorter
+one of the ESP8266 */
+fn parse(bytes: &[u8], offset: usize) -> Option<Image> {
+    const ESP8266_HEADER_SIZE: usize = 8;
+    const ESP32_HEADER_SIZE: usize = 24;
+    if *bytes.get(offset)? != IMAGE_MAGIC {
+        return None;
+    }
+    let entry = read_u32(bytes, offset + 4)?;
+    let chip = CHIPS
+        .iter()
+        .find(|&&(id, _chip)| Some(id) == read_u16(bytes, offset + 12))
+        .map(|&(_id, chip)| chip);
+    if let Some(chip) = chip {
+        if let Some(segments) = segments(bytes, offset, ESP32_HEADER_SIZE, ESP32_ADDRESSES) {
+            return Some(Image {
+                offset,
+                chip,
+                entry,
+                segments,
+            });
+        }
+    }
+    Some(Image {
+        offset,
+        chip: "ESP8266",
+        entry,
+        segments: segments(bytes, offset, ESP8266_HEADER_SIZE, ESP8266_ADDRESSES)?,
+    })
+}
+
+/* The entries of the partition table of a flash dump, which end at the first without the magic
+(either the MD5 of the table, or erased flash) */
+pub fn partitions(bytes: &[u8]) -> Vec<Partition> {
+    const MAGIC: [u8; 2] = [0xaa, 0x50];
+    const ENTRY_SIZE: usize = 32;
+    const APP: u8 = 0x00;
+    const MAX_PARTITIONS: usize = 95;
+    let Some(table) = bytes.get(PARTITION_TABLE_OFFSET..) else {
+        return Vec::new();
+    };
+    table
+        .chunks_exact(ENTRY_SIZE)
+        .take(MAX_PARTITIONS)
+        .take_while(|entry| entry.starts_with(&MAGIC))
+        .filter_map(|entry| {
+            let label = &entry[12..28];
+            let len = label.iter().position(|&b| b == 0).unwrap_or(label.len());
+            Some(Partition {
+                label: String::from_utf8_lossy(&label[..len]).into_owned(),
+                app: entry[2] == APP,
+                offset: read_u32(entry, 4)? as usize,
+                size: read_u32(entry, 8)? as usize,
+            })
+        })
+        .collect()
+}
+
+/* The images in the file, either a single image or those of a flash dump (the boot loader and those
+in app partitions) */
+pub fn find(bytes: &[u8], partitions: &[Partition]) -> Vec<Image> {
+    let bootloader = BOOTLOADER_OFFSETS
+        .into_iter()
+        .find_map(|offset| parse(bytes, offset));
+    let apps = partitions
+        .iter()
+        .filter(|partition| partition.app)
+        .filter_map(|partition| parse(bytes, partition.offset));
+    bootloader.into_iter().chain(apps).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* An ESP32-S3 image of the given segments, each a load address and its length */
+    fn image(entry: u32, segments: &[(u32, usize)]) -> Vec<u8> {
+        let mut image = vec![0; 24];
+        image[0] = IMAGE_MAGIC;
+        image[1] = segments.len() as u8;
+        image[4..8].copy_from_slice(&entry.to_le_bytes());
+        image[12..14].copy_from_slice(&9u16.to_le_bytes());
+        for &(load_address, len) in segments {
+            image.extend(load_address.to_le_bytes());
+            image.extend((len as u32).to_le_bytes());
+            image.extend(vec![0xa5; len]);
+        }
+        image
+    }
+
+    fn partition(label: &str, kind: u8, offset: u32, size: u32) -> Vec<u8> {
+        let mut entry = vec![0xaa, 0x50, kind, 0];
+        entry.extend(offset.to_le_bytes());
+        entry.extend(size.to_le_bytes());
+        entry.extend(label.bytes().chain([0; 16]).take(16));
+        entry.extend([0; 4]);
+        entry
+    }
+
+    #[test]
+    fn finds_the_images_of_a_flash_dump() {
+        let mut flash = vec![0xff; 0x20000];
+        let bootloader = image(0x403c_9000, &[(0x3fce_3800, 0x100), (0x403c_9000, 0x200)]);
+        flash[0x0..bootloader.len()].copy_from_slice(&bootloader);
+        let table = [
+            partition("nvs", 0x01, 0x9000, 0x6000),
+            partition("factory", 0x00, 0x10000, 0x10000),
+        ]
+        .concat();
+        flash[PARTITION_TABLE_OFFSET..PARTITION_TABLE_OFFSET + table.len()].copy_from_slice(&table);
+        let app = image(0x4200_0010, &[(0x3c02_0020, 0x400)]);
+        flash[0x10000..0x10000 + app.len()].copy_from_slice(&app);
+
+        let partitions = partitions(&flash);
+        let labels: Vec<(&str, bool)> = partitions
+            .iter()
+            .map(|partition| (partition.label.as_str(), partition.app))
+            .collect();
+        assert_eq!(labels, vec![("nvs", false), ("factory", true)]);
+
+        let images = find(&flash, &partitions);
+        assert_eq!(images.len(), 2);
+        assert_eq!((images[0].offset, images[0].chip), (0, "ESP32-S3"));
+        let segments: Vec<(usize, usize, u32)> = images[0]
+            .segments
+            .iter()
+            .map(|segment| (segment.offset, segment.len, segment.load_address))
+            .collect();
+        assert_eq!(
+            segments,
+            vec![(32, 0x100, 0x3fce_3800), (0x128, 0x200, 0x403c_9000)]
+        );
+        assert_eq!((images[1].offset, images[1].entry), (0x10000, 0x4200_0010));
+    }
+
+    #[test]
+    fn ignores_a_magic_byte_without_plausible_segments() {
+        let mut bytes = image(0x4000_0000, &[(0x1000, 0x10)]);
+        assert!(find(&bytes, &[]).is_empty());
+        bytes[1] = 0;
+        assert!(find(&bytes, &[]).is_empty());
+    }
+}
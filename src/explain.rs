@@ -0,0 +1,125 @@
+/* Breaks down the evidence for a candidate base address: the targets referenced by addresses at that
+base, the page offsets at which they were found and a few examples. Genuine evidence is spread over
+many page offsets, whereas a repetitive table (e.g. of fixed-stride records) concentrates it on a
+few, which is easy to spot here but not from the score alone. */
+use crate::{dump::string_at, format, heuristic::HeuristicKind, PageIndex, RBaseTraits};
+
+/* Number of page offsets which contributed most, and example pairs, given for each candidate */
+const TOP_PAGE_OFFSETS: usize = 5;
+const EXAMPLES: usize = 3;
+
+/* Maximum length of the text of an example string */
+const MAX_EXAMPLE_LENGTH: usize = 40;
+
+pub struct Evidence<T> {
+    /* Number of targets of each kind referenced at the base */
+    pub targets: Vec<(HeuristicKind, usize)>,
+    /* Number of targets referenced at each page offset, most first */
+    pub page_offsets: Vec<(usize, usize)>,
+    /* Targets (as kind, file offset and address) spread through the image */
+    pub examples: Vec<(HeuristicKind, T, T)>,
+}
+
+impl<T> Evidence<T> {
+    pub fn total(&self) -> usize {
+        self.targets.iter().map(|&(_kind, count)| count).sum()
+    }
+}
+
+/* Find each target of each heuristic referenced by an address at the base */
+pub fn explain<T: RBaseTraits<T, N>, const N: usize>(
+    base: T,
+    targets: &[(HeuristicKind, PageIndex<(T, usize)>)],
+    addresses_index: &PageIndex<T>,
+) -> Evidence<T> {
+    let mut referenced: Vec<(HeuristicKind, T, T)> = Vec::new();
+    let mut page_offsets = vec![0; addresses_index.len()];
+    for (kind, index) in targets.iter() {
+        for (page_offset, (targets, addresses)) in index.iter().zip(addresses_index).enumerate() {
+            for (file_offset, _weight) in targets.iter() {
+                let Some(address) = base.checked_add(file_offset) else {
+                    continue;
+                };
+                if addresses.contains(address) {
+                    referenced.push((*kind, file_offset, address));
+                    page_offsets[page_offset] += 1;
+                }
+            }
+        }
+    }
+    let targets = targets
+        .iter()
+        .map(|(kind, _index)| {
+            let count = referenced
+                .iter()
+                .filter(|(other, _, _)| other == kind)
+                .count();
+            (*kind, count)
+        })
+        .collect();
+    let mut page_offsets: Vec<(usize, usize)> = page_offsets
+        .into_iter()
+        .enumerate()
+        .filter(|&(_page_offset, count)| count != 0)
+        .collect();
+    page_offsets.sort_unstable_by(|(p1, c1), (p2, c2)| c2.cmp(c1).then(p1.cmp(p2)));
+    referenced.sort_unstable_by_key(|&(_kind, file_offset, _address)| file_offset);
+    let step = referenced.len().div_ceil(EXAMPLES).max(1);
+    Evidence {
+        targets,
+        page_offsets,
+        examples: referenced.into_iter().step_by(step).collect(),
+    }
+}
+
+/* Describe the evidence in a few lines */
+pub fn describe<T: RBaseTraits<T, N>, const N: usize>(
+    evidence: &Evidence<T>,
+    bytes: &[u8],
+) -> Vec<String> {
+    let total = evidence.total();
+    let targets = evidence
+        .targets
+        .iter()
+        .map(|(kind, count)| format!("{count} {kind}"))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let mut lines = vec![format!(
+        "{total} targets referenced ({targets}) at {} page offsets",
+        evidence.page_offsets.len()
+    )];
+    if !evidence.page_offsets.is_empty() {
+        let top: usize = evidence
+            .page_offsets
+            .iter()
+            .take(TOP_PAGE_OFFSETS)
+            .map(|&(_page_offset, count)| count)
+            .sum();
+        lines.push(format!(
+            "top page offsets: {} ({:.2}% of targets)",
+            evidence
+                .page_offsets
+                .iter()
+                .take(TOP_PAGE_OFFSETS)
+                .map(|(page_offset, count)| format!("0x{page_offset:03x} x{count}"))
+                .collect::<Vec<String>>()
+                .join(", "),
+            100.0 * top as f64 / total as f64
+        ));
+    }
+    for &(kind, file_offset, address) in evidence.examples.iter() {
+        let target = match (kind, file_offset.try_into()) {
+            (HeuristicKind::Strings, Ok(offset)) => format!(
+                "string \"{}\"",
+                string_at(bytes, offset, MAX_EXAMPLE_LENGTH).escape_ascii()
+            ),
+            (HeuristicKind::Strings, Err(_)) => "string".to_string(),
+            (HeuristicKind::Pointers, _) => "pointer".to_string(),
+        };
+        lines.push(format!(
+            "{} -> {target} at 0x{file_offset:x}",
+            format::prefixed(address.into(), N * 2)
+        ));
+    }
+    lines
+}
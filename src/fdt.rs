@@ -0,0 +1,237 @@
+/* Flattened device trees (as embedded in many kernel and boot loader images) describe the memory of
+the platform, which bounds where the image can be loaded. Everything in a device tree is big-endian. */
+
+const FDT_MAGIC: [u8; 4] = [0xd0, 0x0d, 0xfe, 0xed];
+const FDT_HEADER_SIZE: usize = 40;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/* Defaults for the number of cells in an address or size, if the root node doesn't give them */
+const DEFAULT_ADDRESS_CELLS: usize = 2;
+const DEFAULT_SIZE_CELLS: usize = 1;
+
+/* A range of memory described by the device tree */
+pub struct Memory {
+    pub name: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+pub struct DeviceTree {
+    pub offset: usize,
+    pub memory: Vec<Memory>,
+}
+
+impl DeviceTree {
+    /* The memory containing the address, if any */
+    pub fn lookup(&self, address: u64) -> Option<&Memory> {
+        self.memory
+            .iter()
+            .find(|memory| memory.start <= address && address <= memory.end)
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let word = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_be_bytes(word.try_into().unwrap()))
+}
+
+/* A NUL-terminated string at the given offset */
+fn read_str(bytes: &[u8], offset: usize) -> Option<&str> {
+    let tail = bytes.get(offset..)?;
+    let len = tail.iter().position(|&b| b == 0)?;
+    std::str::from_utf8(&tail[..len]).ok()
+}
+
+/* A value made up of the given number of cells */
+fn read_cells(value: &[u8], cells: usize) -> Option<u64> {
+    if cells == 0 || cells > 2 || value.len() < cells * 4 {
+        return None;
+    }
+    Some(value[..cells * 4].chunks_exact(4).fold(0, |acc, cell| {
+        (acc << 32) | u32::from_be_bytes(cell.try_into().unwrap()) as u64
+    }))
+}
+
+/* Find the first valid device tree in the image */
+pub fn find(bytes: &[u8]) -> Option<DeviceTree> {
+    (0..bytes.len().saturating_sub(FDT_HEADER_SIZE))
+        .step_by(4)
+        .filter(|&offset| bytes[offset..offset + 4] == FDT_MAGIC)
+        .find_map(|offset| parse(bytes, offset))
+}
+
+fn parse(bytes: &[u8], offset: usize) -> Option<DeviceTree> {
+    let total_size = read_u32(bytes, offset + 4)? as usize;
+    let dt = bytes.get(offset..offset.checked_add(total_size)?)?;
+    let off_struct = read_u32(dt, 8)? as usize;
+    let off_strings = read_u32(dt, 12)? as usize;
+    let version = read_u32(dt, 20)?;
+    if version < 16 || off_struct >= dt.len() || off_strings >= dt.len() {
+        return None;
+    }
+
+    let mut address_cells = DEFAULT_ADDRESS_CELLS;
+    let mut size_cells = DEFAULT_SIZE_CELLS;
+    let mut path: Vec<&str> = Vec::new();
+    let mut is_memory: Vec<bool> = Vec::new();
+    let mut regs: Vec<(String, &[u8])> = Vec::new();
+    let mut pos = off_struct;
+    loop {
+        let token = read_u32(dt, pos)?;
+        pos += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = read_str(dt, pos)?;
+                pos += (name.len() + 4) & !3;
+                /* Memory nodes are children of the root, named memory or memory@<address> */
+                is_memory.push(path.len() == 1 && name.split('@').next() == Some("memory"));
+                path.push(name);
+            }
+            FDT_END_NODE => {
+                path.pop()?;
+                is_memory.pop()?;
+            }
+            FDT_PROP => {
+                let len = read_u32(dt, pos)? as usize;
+                let name = read_str(dt, off_strings + read_u32(dt, pos + 4)? as usize)?;
+                let value = dt.get(pos + 8..pos + 8 + len)?;
+                pos += 8 + ((len + 3) & !3);
+                match (path.len(), name) {
+                    (1, "#address-cells") => address_cells = read_cells(value, 1)? as usize,
+                    (1, "#size-cells") => size_cells = read_cells(value, 1)? as usize,
+                    (_, "reg") if is_memory.last() == Some(&true) => {
+                        regs.push((path.last()?.to_string(), value))
+                    }
+                    _ => {}
+                }
+            }
+            FDT_NOP => {}
+            FDT_END => break,
+            _ => return None,
+        }
+    }
+
+    /* Each reg property is a list of (address, size) pairs */
+    let stride = (address_cells + size_cells) * 4;
+    if stride == 0 {
+        return None;
+    }
+    let memory = regs
+        .iter()
+        .flat_map(|(name, value)| {
+            value.chunks_exact(stride).filter_map(|pair| {
+                let start = read_cells(pair, address_cells)?;
+                let size = read_cells(&pair[address_cells * 4..], size_cells)?;
+                Some(Memory {
+                    name: name.clone(),
+                    start,
+                    end: start.checked_add(size.checked_sub(1)?)?,
+                })
+            })
+        })
+        .collect();
+    Some(DeviceTree { offset, memory })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* The structure block of a device tree, and its strings block */
+    #[derive(Default)]
+    struct Builder {
+        structure: Vec<u8>,
+        strings: Vec<u8>,
+    }
+
+    impl Builder {
+        fn token(&mut self, token: u32) {
+            self.structure.extend(token.to_be_bytes());
+        }
+
+        fn begin(&mut self, name: &str) {
+            self.token(FDT_BEGIN_NODE);
+            self.structure.extend(name.bytes().chain([0]));
+            self.structure
+                .resize(self.structure.len().next_multiple_of(4), 0);
+        }
+
+        fn prop(&mut self, name: &str, cells: &[u32]) {
+            self.token(FDT_PROP);
+            self.token(cells.len() as u32 * 4);
+            self.token(self.strings.len() as u32);
+            self.strings.extend(name.bytes().chain([0]));
+            cells.iter().for_each(|&cell| self.token(cell));
+        }
+
+        fn finish(mut self) -> Vec<u8> {
+            self.token(FDT_END);
+            let off_struct = FDT_HEADER_SIZE;
+            let off_strings = off_struct + self.structure.len();
+            let total_size = off_strings + self.strings.len();
+            let header = [
+                u32::from_be_bytes(FDT_MAGIC),
+                total_size as u32,
+                off_struct as u32,
+                off_strings as u32,
+                0,
+                17,
+                16,
+                0,
+                self.strings.len() as u32,
+                self.structure.len() as u32,
+            ];
+            let mut dt: Vec<u8> = header.iter().flat_map(|word| word.to_be_bytes()).collect();
+            dt.extend(self.structure);
+            dt.extend(self.strings);
+            dt
+        }
+    }
+
+    #[test]
+    fn finds_the_memory_of_an_embedded_device_tree() {
+        let mut builder = Builder::default();
+        builder.begin("");
+        builder.prop("#address-cells", &[1]);
+        builder.prop("#size-cells", &[1]);
+        builder.begin("cpus");
+        builder.prop("reg", &[0, 1]);
+        builder.token(FDT_END_NODE);
+        builder.begin("memory@80000000");
+        builder.prop("reg", &[0x8000_0000, 0x1000_0000, 0xa000_0000, 0x100_0000]);
+        builder.token(FDT_END_NODE);
+        builder.token(FDT_END_NODE);
+        let image = [vec![0; 0x100], builder.finish(), vec![0; 0x100]].concat();
+
+        let device_tree = find(&image).unwrap();
+        assert_eq!(device_tree.offset, 0x100);
+        let memory: Vec<(&str, u64, u64)> = device_tree
+            .memory
+            .iter()
+            .map(|memory| (memory.name.as_str(), memory.start, memory.end))
+            .collect();
+        assert_eq!(
+            memory,
+            vec![
+                ("memory@80000000", 0x8000_0000, 0x8fff_ffff),
+                ("memory@80000000", 0xa000_0000, 0xa0ff_ffff)
+            ]
+        );
+        assert!(device_tree.lookup(0x8001_0000).is_some());
+        assert!(device_tree.lookup(0x9000_0000).is_none());
+    }
+
+    #[test]
+    fn ignores_a_truncated_device_tree() {
+        let mut builder = Builder::default();
+        builder.begin("");
+        builder.token(FDT_END_NODE);
+        let dt = builder.finish();
+        assert!(find(&dt[..dt.len() - 4]).is_none());
+    }
+}
@@ -0,0 +1,65 @@
+/* Erased flash and memory filled by the linker or a boot loader are long runs of the same word, which
+are never pointers however plausible the value looks. The values which fill such runs are found so
+that words with them can be excluded from the pointers. */
+use {
+    crate::{collect_by_key, RBaseTraits},
+    rayon::{iter::ParallelIterator, slice::ParallelSlice},
+};
+
+/* Minimum number of consecutive words with the same value to be a run of fill */
+const MIN_RUN_LENGTH: usize = 16;
+
+/* Minimum number of words in runs of a value for it to be a fill pattern */
+const MIN_FILL_WORDS: usize = 256;
+
+/* Size of the chunks searched for runs in parallel, runs spanning two are counted as two */
+const CHUNK_SIZE: usize = 0x10000;
+
+pub struct Fill<T> {
+    pub value: T,
+    /* Number of words in the image with the value */
+    pub words: usize,
+}
+
+/* Find the (masked) values which fill long runs of words, most common first. Zero isn't a pointer
+anyway, so is never reported. */
+pub fn find<T: RBaseTraits<T, N>, const N: usize>(
+    bytes: &[u8],
+    read_address_bytes: fn([u8; N]) -> T,
+    pointer_mask: Option<T>,
+) -> Vec<Fill<T>> {
+    let read = move |word: &[u8]| {
+        let value = read_address_bytes(word.try_into().unwrap());
+        match pointer_mask {
+            Some(mask) => value & mask,
+            None => value,
+        }
+    };
+    let runs = bytes.par_chunks(CHUNK_SIZE).flat_map_iter(|chunk| {
+        let words: Vec<T> = chunk.chunks_exact(N).map(read).collect();
+        words
+            .chunk_by(|a, b| a == b)
+            .filter(|run| run.len() >= MIN_RUN_LENGTH && run[0] != T::default())
+            .map(|run| (run[0], run.len()))
+            .collect::<Vec<(T, usize)>>()
+    });
+    let values: Vec<T> = collect_by_key(runs, |words, other| *words += other)
+        .into_iter()
+        .filter(|&(_value, words)| words >= MIN_FILL_WORDS)
+        .map(|(value, _words)| value)
+        .collect();
+    if values.is_empty() {
+        return Vec::new();
+    }
+    let words = bytes
+        .par_chunks_exact(N)
+        .map(read)
+        .filter(|value| values.contains(value))
+        .map(|value| (value, 1));
+    let mut fills: Vec<Fill<T>> = collect_by_key(words, |words, other| *words += other)
+        .into_iter()
+        .map(|(value, words)| Fill { value, words })
+        .collect();
+    fills.sort_unstable_by(|a, b| b.words.cmp(&a.words).then(a.value.cmp(&b.value)));
+    fills
+}
@@ -0,0 +1,83 @@
+/* How addresses are written in the results and reports. By default each is written as it always has
+been (candidates prefixed and padded to the size of a pointer, the base found bare), but they can be
+padded (or not) throughout, written in uppercase or written in decimal. */
+use {
+    clap::{builder::PossibleValue, ValueEnum},
+    std::{
+        fmt::{Display, Formatter, Result},
+        sync::OnceLock,
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FormatOption {
+    /* Pad every address to the size of a pointer */
+    Pad,
+    /* Never pad addresses */
+    NoPad,
+    Upper,
+    Lower,
+    Hex,
+    Dec,
+}
+
+impl Display for FormatOption {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let value = self
+            .to_possible_value()
+            .unwrap_or(PossibleValue::new("unknown"));
+        write!(f, "{}", value.get_name())
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct AddressFormat {
+    /* Whether to pad addresses, if not as each is by default */
+    pad: Option<bool>,
+    upper: bool,
+    decimal: bool,
+}
+
+static FORMAT: OnceLock<AddressFormat> = OnceLock::new();
+
+/* Apply the options, later ones overriding earlier ones */
+pub fn init(options: &[FormatOption]) {
+    let mut format = AddressFormat::default();
+    for option in options {
+        match option {
+            FormatOption::Pad => format.pad = Some(true),
+            FormatOption::NoPad => format.pad = Some(false),
+            FormatOption::Upper => format.upper = true,
+            FormatOption::Lower => format.upper = false,
+            FormatOption::Hex => format.decimal = false,
+            FormatOption::Dec => format.decimal = true,
+        }
+    }
+    FORMAT.set(format).ok().expect("format already initialized");
+}
+
+fn format(value: u64, digits: usize, prefix: &str, pad: bool) -> String {
+    let format = FORMAT.get().copied().unwrap_or_default();
+    if format.decimal {
+        return value.to_string();
+    }
+    let width = match format.pad.unwrap_or(pad) {
+        true => digits,
+        false => 0,
+    };
+    match format.upper {
+        true => format!("{prefix}{value:0width$X}"),
+        false => format!("{prefix}{value:0width$x}"),
+    }
+}
+
+/* An address prefixed by 0x and (by default) padded to the given number of digits, as in the lists
+of candidates */
+pub fn prefixed(value: u64, digits: usize) -> String {
+    format(value, digits, "0x", true)
+}
+
+/* An address without a prefix or (by default) padding, as in the base found */
+pub fn bare(value: u64, digits: usize) -> String {
+    format(value, digits, "", false)
+}
@@ -0,0 +1,395 @@
+/* Finding strings and pointers means examining every byte of the image, which for multi-gigabyte
+dumps is the bulk of the work. Both are embarrassingly parallel, so with the gpu feature they can be
+offloaded to a GPU (or any other wgpu adapter). The image is processed in batches, and each batch
+yields the (unordered) positions of the strings or pointers found in it, which are sorted so the
+results don't depend on the order in which the GPU found them. Everything else (weighting strings,
+filtering by region or script, etc.) is still done on the CPU. Without an adapter, the CPU is used
+for everything. */
+
+use {crate::strings::Terminated, std::sync::OnceLock};
+#[cfg(feature = "gpu")]
+use {
+    std::sync::mpsc::channel,
+    wgpu::{util::DeviceExt, BufferUsages, ComputePipeline, Device, Queue},
+};
+
+/* Number of words (or bytes, when finding strings) in each batch, within the limits of the
+storage buffers and of the number of workgroups dispatched */
+pub const BATCH_WORDS: usize = 1 << 22;
+pub const BATCH_BYTES: usize = 1 << 23;
+
+#[cfg(feature = "gpu")]
+const WORKGROUP_SIZE: usize = 256;
+
+/* Decode each word, given the shift of each of its bytes, as a 64-bit value split into two 32-bit
+halves. Those which are non-zero after masking and within the range are appended to the output. */
+#[cfg(feature = "gpu")]
+const POINTERS_SHADER: &str = r"
+struct Params {
+    words: u32,
+    size: u32,
+    mask_lo: u32,
+    mask_hi: u32,
+    start_lo: u32,
+    start_hi: u32,
+    end_lo: u32,
+    end_hi: u32,
+    shifts: array<vec4<u32>, 2>,
+}
+
+struct Found {
+    count: atomic<u32>,
+    items: array<u32>,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> data: array<u32>;
+@group(0) @binding(2) var<storage, read_write> found: Found;
+
+fn byte_at(i: u32) -> u32 {
+    return (data[i >> 2u] >> ((i & 3u) * 8u)) & 0xffu;
+}
+
+fn at_most(a_lo: u32, a_hi: u32, b_lo: u32, b_hi: u32) -> bool {
+    return a_hi < b_hi || (a_hi == b_hi && a_lo <= b_lo);
+}
+
+@compute @workgroup_size(256)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let word = id.x;
+    if word >= params.words {
+        return;
+    }
+    var lo = 0u;
+    var hi = 0u;
+    for (var j = 0u; j < params.size; j++) {
+        let b = byte_at(word * params.size + j);
+        let shift = params.shifts[j >> 2u][j & 3u];
+        if shift < 32u {
+            lo |= b << shift;
+        } else {
+            hi |= b << (shift - 32u);
+        }
+    }
+    lo &= params.mask_lo;
+    hi &= params.mask_hi;
+    if (lo == 0u && hi == 0u)
+        || !at_most(params.start_lo, params.start_hi, lo, hi)
+        || !at_most(lo, hi, params.end_lo, params.end_hi) {
+        return;
+    }
+    let idx = atomicAdd(&found.count, 1u);
+    found.items[idx] = word;
+}
+";
+
+/* Find each NUL terminating a run of at least the minimum number of characters in the charset,
+walking back at most one more than the maximum to find where the run starts. The batch is preceded
+by enough of the image to do so. Longer runs are only strings if they may start mid-string, in which
+case they start the maximum length before the NUL, as the leftmost match of the string regex would.
+The start and end of each is appended to the output. */
+#[cfg(feature = "gpu")]
+const STRINGS_SHADER: &str = r"
+struct Params {
+    len: u32,
+    first: u32,
+    min: u32,
+    max: u32,
+    allow_mid_string: u32,
+    charset: array<vec4<u32>, 2>,
+}
+
+struct Found {
+    count: atomic<u32>,
+    items: array<u32>,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> data: array<u32>;
+@group(0) @binding(2) var<storage, read_write> found: Found;
+
+fn byte_at(i: u32) -> u32 {
+    return (data[i >> 2u] >> ((i & 3u) * 8u)) & 0xffu;
+}
+
+fn in_charset(b: u32) -> bool {
+    let bits = params.charset[b >> 7u][(b >> 5u) & 3u];
+    return ((bits >> (b & 31u)) & 1u) == 1u;
+}
+
+@compute @workgroup_size(256)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let end = params.first + id.x;
+    if end >= params.len || byte_at(end) != 0u {
+        return;
+    }
+    var start = end;
+    while start > 0u && end - start <= params.max && in_charset(byte_at(start - 1u)) {
+        start -= 1u;
+    }
+    let run = end - start;
+    if run < params.min {
+        return;
+    }
+    if run > params.max {
+        if params.allow_mid_string == 0u {
+            return;
+        }
+        start = end - params.max;
+    }
+    let idx = atomicAdd(&found.count, 1u);
+    found.items[idx * 2u] = start;
+    found.items[idx * 2u + 1u] = end;
+}
+";
+
+#[cfg(feature = "gpu")]
+pub struct Gpu {
+    pub name: String,
+    device: Device,
+    queue: Queue,
+    pointers: ComputePipeline,
+    strings: ComputePipeline,
+}
+
+#[cfg(feature = "gpu")]
+impl Gpu {
+    fn new() -> Option<Gpu> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                required_features: wgpu::Features::empty(),
+                required_limits: wgpu::Limits::downlevel_defaults(),
+                memory_hints: wgpu::MemoryHints::Performance,
+            },
+            None,
+        ))
+        .ok()?;
+        let pipeline = |source: &str| {
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: None,
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: None,
+                layout: None,
+                module: &module,
+                entry_point: Some("main"),
+                compilation_options: Default::default(),
+                cache: None,
+            })
+        };
+        Some(Gpu {
+            name: adapter.get_info().name,
+            pointers: pipeline(POINTERS_SHADER),
+            strings: pipeline(STRINGS_SHADER),
+            device,
+            queue,
+        })
+    }
+
+    /* Run the pipeline over the data with the given parameters and number of invocations, returning
+    the records (each of the given number of words) it appends to an output with room for the given
+    number */
+    fn run(
+        &self,
+        pipeline: &ComputePipeline,
+        params: &[u32],
+        data: &[u8],
+        invocations: usize,
+        (stride, capacity): (usize, usize),
+    ) -> Vec<u32> {
+        let params: Vec<u8> = params
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect();
+        let params = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: &params,
+                usage: BufferUsages::UNIFORM,
+            });
+        let data = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: data,
+                usage: BufferUsages::STORAGE,
+            });
+        let size = (4 * (1 + stride * capacity)) as u64;
+        let found = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: data.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: found.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(invocations.div_ceil(WORKGROUP_SIZE) as u32, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&found, 0, &staging, 0, size);
+        self.queue.submit([encoder.finish()]);
+
+        let slice = staging.slice(..);
+        let (sender, receiver) = channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).unwrap();
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv().unwrap().unwrap();
+        let view = slice.get_mapped_range();
+        let words: Vec<u32> = view
+            .chunks_exact(4)
+            .map(|word| u32::from_ne_bytes(word.try_into().unwrap()))
+            .collect();
+        let count = (words[0] as usize).min(capacity);
+        words[1..=stride * count].to_vec()
+    }
+
+    /* The indexes of the words of the given size in the batch which are pointers, given the shift
+    of each byte within a word, a mask and an inclusive range */
+    pub fn pointers(
+        &self,
+        words: &[u8],
+        size: usize,
+        shifts: &[u32],
+        mask: u64,
+        (start, end): (u64, u64),
+    ) -> Vec<usize> {
+        let count = words.len() / size;
+        let mut params = vec![
+            count as u32,
+            size as u32,
+            mask as u32,
+            (mask >> 32) as u32,
+            start as u32,
+            (start >> 32) as u32,
+            end as u32,
+            (end >> 32) as u32,
+        ];
+        params.extend((0..8).map(|j| shifts.get(j).copied().unwrap_or(0)));
+        let found = self.run(&self.pointers, &params, words, count, (1, count));
+        let mut indexes: Vec<usize> = found.into_iter().map(|word| word as usize).collect();
+        indexes.sort_unstable();
+        indexes
+    }
+
+    /* The start and end (the offset of the NUL) of each string ending in the batch of bytes from the
+    given offset, given at least the maximum length of the bytes preceding it */
+    pub fn strings(
+        &self,
+        bytes: &[u8],
+        from: usize,
+        terminated: &Terminated,
+    ) -> Vec<(usize, usize)> {
+        let mut params = vec![
+            bytes.len() as u32,
+            from as u32,
+            terminated.min as u32,
+            terminated.max as u32,
+            terminated.allow_mid_string as u32,
+            0,
+            0,
+            0,
+        ];
+        params.extend((0..8).map(|word| {
+            (0..32)
+                .filter(|bit| terminated.charset[word * 32 + bit])
+                .fold(0, |bits, bit| bits | (1 << bit))
+        }));
+        let capacity = (bytes.len() - from) / (terminated.min + 1) + 1;
+        let found = self.run(
+            &self.strings,
+            &params,
+            bytes,
+            bytes.len() - from,
+            (2, capacity),
+        );
+        let mut strings: Vec<(usize, usize)> = found
+            .chunks_exact(2)
+            .map(|pair| (pair[0] as usize, pair[1] as usize))
+            .collect();
+        strings.sort_unstable();
+        strings
+    }
+}
+
+#[cfg(not(feature = "gpu"))]
+pub struct Gpu {
+    pub name: String,
+}
+
+#[cfg(not(feature = "gpu"))]
+impl Gpu {
+    fn new() -> Option<Gpu> {
+        None
+    }
+
+    pub fn pointers(
+        &self,
+        _bytes: &[u8],
+        _size: usize,
+        _shifts: &[u32],
+        _mask: u64,
+        _range: (u64, u64),
+    ) -> Vec<usize> {
+        unreachable!("rbase was built without the gpu feature")
+    }
+
+    pub fn strings(
+        &self,
+        _bytes: &[u8],
+        _from: usize,
+        _terminated: &Terminated,
+    ) -> Vec<(usize, usize)> {
+        unreachable!("rbase was built without the gpu feature")
+    }
+}
+
+static GPU: OnceLock<Option<Gpu>> = OnceLock::new();
+
+/* Look for an adapter to offload the search for strings and pointers to */
+pub fn init() -> Option<&'static Gpu> {
+    GPU.get_or_init(Gpu::new).as_ref()
+}
+
+/* The adapter in use, if any */
+pub fn get() -> Option<&'static Gpu> {
+    GPU.get().and_then(|gpu| gpu.as_ref())
+}
@@ -0,0 +1,177 @@
+/* Ready-to-edit configurations for dynamic analysis of the image at the base address found: a Qiling
+profile for its blob loader, or an avatar2 script. The architecture is the one detected or given to
+refine the result, if any, and anything which couldn't be determined is left for the user to fill
+in. Entry points are rarely at the base, but it is as good a guess as any. */
+use {
+    crate::{refine::DisasmArch, Endian, PAGE_SIZE},
+    std::{
+        fs::File,
+        io::{BufWriter, Result, Write},
+    },
+};
+
+/* Heap given to the image in the Qiling profile */
+const QILING_HEAP_SIZE: u64 = 0x100000;
+
+pub struct Target<'a> {
+    /* Path of the file analysed */
+    pub path: &'a str,
+    /* Where the image starts in the file, or none if the file had to be transformed (e.g.
+    decompressed) to analyse it */
+    pub file_offset: Option<usize>,
+    pub arch: Option<DisasmArch>,
+    pub endian: Endian,
+    pub base: u64,
+    pub size: u64,
+}
+
+impl Target<'_> {
+    /* Size of the memory to map for the image, in whole pages */
+    fn mapped_size(&self) -> u64 {
+        self.size.next_multiple_of(PAGE_SIZE as u64)
+    }
+
+    /* A comment noting what must be done before loading the file, if it isn't the image as is */
+    fn caveat(&self) -> Option<String> {
+        match self.file_offset {
+            Some(0) => None,
+            Some(file_offset) => Some(format!(
+                "# NOTE: the image starts at offset 0x{file_offset:x} of the file, so extract it first"
+            )),
+            None => Some(
+                "# NOTE: the image was decoded from the file (e.g. decompressed), so load the decoded image instead"
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+/* The architecture of an ISA detected in the image, if it can be disassembled */
+pub fn detected_arch(name: &str) -> Option<DisasmArch> {
+    [
+        ("AArch64", DisasmArch::Arm64),
+        ("ARM", DisasmArch::Arm),
+        ("Thumb", DisasmArch::Thumb),
+        ("MIPS64", DisasmArch::Mips64),
+        ("MIPS", DisasmArch::Mips),
+        ("PowerPC64", DisasmArch::Ppc64),
+        ("PowerPC", DisasmArch::Ppc),
+        ("x86-64", DisasmArch::X86_64),
+        ("x86", DisasmArch::X86),
+    ]
+    .into_iter()
+    .find(|(prefix, _arch)| name.starts_with(prefix))
+    .map(|(_prefix, arch)| arch)
+}
+
+/* Write a Qiling profile, with an example of how to load the image with it */
+pub fn qiling(path: &str, target: &Target) -> Result<()> {
+    let archtype = match target.arch {
+        Some(DisasmArch::Arm | DisasmArch::Thumb) => Some("ARM"),
+        Some(DisasmArch::Arm64) => Some("ARM64"),
+        Some(DisasmArch::Mips) => Some("MIPS"),
+        Some(DisasmArch::Ppc) => Some("PPC"),
+        Some(DisasmArch::X86) => Some("X86"),
+        Some(DisasmArch::X86_64) => Some("X8664"),
+        Some(DisasmArch::Mips64 | DisasmArch::Ppc64) | None => None,
+    };
+    let endian = match target.endian {
+        Endian::Little => "EL",
+        Endian::Big => "EB",
+    };
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "# Qiling profile for {}, generated by rbase",
+        target.path
+    )?;
+    if let Some(caveat) = target.caveat() {
+        writeln!(writer, "{caveat}")?;
+    }
+    if archtype.is_none() {
+        writeln!(
+            writer,
+            "# TODO: the architecture is unknown or unsupported by Qiling, set archtype below"
+        )?;
+    }
+    writeln!(writer, "#")?;
+    writeln!(writer, "# Load the image with it like so:")?;
+    writeln!(writer, "#   from qiling import Qiling")?;
+    writeln!(
+        writer,
+        "#   from qiling.const import QL_ARCH, QL_ENDIAN, QL_OS"
+    )?;
+    writeln!(writer, "#   with open({:?}, \"rb\") as f:", target.path)?;
+    writeln!(writer, "#       code = f.read()")?;
+    writeln!(
+        writer,
+        "#   ql = Qiling(code=code, archtype=QL_ARCH.{}, endian=QL_ENDIAN.{endian}, ostype=QL_OS.BLOB, profile={:?})",
+        archtype.unwrap_or("TODO"),
+        path
+    )?;
+    if target.arch == Some(DisasmArch::Thumb) {
+        writeln!(
+            writer,
+            "#   (the code is Thumb, so start at the entry point with its lowest bit set)"
+        )?;
+    }
+    writeln!(writer, "#   ql.run()")?;
+    writeln!(writer)?;
+    writeln!(writer, "[CODE]")?;
+    writeln!(writer, "load_address = 0x{:x}", target.base)?;
+    writeln!(writer, "entry_point = 0x{:x}", target.base)?;
+    writeln!(writer, "ram_size = 0x{:x}", target.mapped_size())?;
+    writeln!(writer, "heap_size = 0x{QILING_HEAP_SIZE:x}")?;
+    writer.flush()
+}
+
+/* Write an avatar2 script which maps the image at the base and runs it under QEMU */
+pub fn avatar2(path: &str, target: &Target) -> Result<()> {
+    let arch = match (target.arch, target.endian) {
+        (Some(DisasmArch::Arm), Endian::Little) => Some("ARM"),
+        (Some(DisasmArch::Arm), Endian::Big) => Some("ARMBE"),
+        (Some(DisasmArch::Thumb), Endian::Little) => Some("ARM_CORTEX_M3"),
+        (Some(DisasmArch::Mips), Endian::Little) => Some("MIPS_24KF"),
+        (Some(DisasmArch::Mips), Endian::Big) => Some("MIPS_BE"),
+        (Some(DisasmArch::Ppc), Endian::Big) => Some("PPC32"),
+        (Some(DisasmArch::X86), _) => Some("X86"),
+        (Some(DisasmArch::X86_64), _) => Some("X86_64"),
+        _ => None,
+    };
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(
+        writer,
+        "# avatar2 target for {}, generated by rbase",
+        target.path
+    )?;
+    if let Some(caveat) = target.caveat() {
+        writeln!(writer, "{caveat}")?;
+    }
+    writeln!(writer, "from avatar2 import *")?;
+    writeln!(writer)?;
+    if arch.is_none() {
+        writeln!(
+            writer,
+            "# TODO: the architecture is unknown or unsupported by avatar2, set arch below"
+        )?;
+    }
+    writeln!(
+        writer,
+        "avatar = Avatar(arch={}, output_directory=\"/tmp/avatar\")",
+        arch.unwrap_or("None")
+    )?;
+    writeln!(
+        writer,
+        "avatar.add_memory_range(0x{:x}, 0x{:x}, name=\"image\", file={:?}, permissions=\"rwx\")",
+        target.base,
+        target.mapped_size(),
+        target.path
+    )?;
+    writeln!(
+        writer,
+        "qemu = avatar.add_target(QemuTarget, entry_address=0x{:x})",
+        target.base
+    )?;
+    writeln!(writer, "avatar.init_targets()")?;
+    writer.flush()
+}
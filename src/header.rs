@@ -0,0 +1,231 @@
+/* Many firmware images are wrapped in a vendor header which describes the payload, and sometimes the
+address at which it is loaded. The header shifts every file offset in the payload, so it is skipped
+before the payload is analysed, and any load address is a useful hint as to the base address. */
+use crc32fast::hash;
+
+/* Wrappers may themselves be wrapped (e.g. a signed header around a firmware header), so headers
+are skipped until none is recognised, up to this many */
+const MAX_HEADERS: usize = 8;
+
+pub struct Header {
+    pub name: &'static str,
+    /* Offset of the payload from the start of the header */
+    pub payload: usize,
+    pub load_address: Option<u64>,
+}
+
+pub trait HeaderParser: Sync {
+    /* The header at the start of the bytes, if this parser recognises it */
+    fn parse(&self, bytes: &[u8]) -> Option<Header>;
+}
+
+fn read_le(bytes: &[u8], offset: usize) -> Option<u32> {
+    let word = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(word.try_into().unwrap()))
+}
+
+fn read_be(bytes: &[u8], offset: usize) -> Option<u32> {
+    let word = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_be_bytes(word.try_into().unwrap()))
+}
+
+/* Legacy U-Boot image (mkimage), big-endian with a CRC of the header */
+struct UImage;
+
+impl HeaderParser for UImage {
+    fn parse(&self, bytes: &[u8]) -> Option<Header> {
+        const MAGIC: u32 = 0x27051956;
+        const HEADER_SIZE: usize = 64;
+        if read_be(bytes, 0)? != MAGIC {
+            return None;
+        }
+        let mut header = bytes.get(..HEADER_SIZE)?.to_vec();
+        let crc = read_be(&header, 4)?;
+        header[4..8].fill(0);
+        if hash(&header) != crc {
+            return None;
+        }
+        Some(Header {
+            name: "U-Boot legacy image",
+            payload: HEADER_SIZE,
+            load_address: Some(read_be(bytes, 16)? as u64),
+        })
+    }
+}
+
+/* Broadcom TRX, whose header gives the offsets of up to three (or four) partitions, the first of
+which is the loader or kernel */
+struct Trx;
+
+impl HeaderParser for Trx {
+    fn parse(&self, bytes: &[u8]) -> Option<Header> {
+        const MAGIC: &[u8] = b"HDR0";
+        const HEADER_SIZE: usize = 28;
+        if !bytes.starts_with(MAGIC) {
+            return None;
+        }
+        let len = read_le(bytes, 4)? as usize;
+        let version = read_le(bytes, 12)? >> 16;
+        let first = read_le(bytes, 16)? as usize;
+        if !(1..=2).contains(&version) || len > bytes.len() || len < HEADER_SIZE {
+            return None;
+        }
+        Some(Header {
+            name: "TRX",
+            payload: if (HEADER_SIZE..len).contains(&first) {
+                first
+            } else {
+                HEADER_SIZE
+            },
+            load_address: None,
+        })
+    }
+}
+
+/* Ambarella firmware section, whose header gives the address at which the section is loaded */
+struct Ambarella;
+
+impl HeaderParser for Ambarella {
+    fn parse(&self, bytes: &[u8]) -> Option<Header> {
+        const MAGIC: u32 = 0xa324eb90;
+        const HEADER_SIZE: usize = 0x100;
+        if read_le(bytes, 24)? != MAGIC || bytes.len() < HEADER_SIZE {
+            return None;
+        }
+        Some(Header {
+            name: "Ambarella section",
+            payload: HEADER_SIZE,
+            load_address: Some(read_le(bytes, 16)? as u64),
+        })
+    }
+}
+
+/* Mediatek image header (mkimage for Mediatek preloaders and kernels). An extended header gives its
+own size, and an address of 0xffffffff means the payload isn't loaded at a fixed address. */
+struct Mediatek;
+
+impl HeaderParser for Mediatek {
+    fn parse(&self, bytes: &[u8]) -> Option<Header> {
+        const MAGIC: u32 = 0x58881688;
+        const EXT_MAGIC: u32 = 0x58891689;
+        const HEADER_SIZE: usize = 0x200;
+        const NO_ADDRESS: u32 = 0xffffffff;
+        if read_le(bytes, 0)? != MAGIC {
+            return None;
+        }
+        let payload = match read_le(bytes, 0x30)? {
+            EXT_MAGIC => read_le(bytes, 0x34)? as usize,
+            _ => HEADER_SIZE,
+        };
+        if payload > bytes.len() {
+            return None;
+        }
+        Some(Header {
+            name: "Mediatek image",
+            payload,
+            load_address: Some(read_le(bytes, 0x28)?)
+                .filter(|&address| address != NO_ADDRESS)
+                .map(|address| address as u64),
+        })
+    }
+}
+
+/* D-Link DLOB, big-endian, whose header is followed by a metadata string and then the data. The data
+is often another DLOB. */
+struct Dlob;
+
+impl HeaderParser for Dlob {
+    fn parse(&self, bytes: &[u8]) -> Option<Header> {
+        const MAGIC: u32 = 0x5ea3a417;
+        const HEADER_SIZE: usize = 0x1c;
+        if read_be(bytes, 0)? != MAGIC {
+            return None;
+        }
+        let metadata_len = read_be(bytes, 4)? as usize;
+        let data_len = read_be(bytes, 8)? as usize;
+        let payload = HEADER_SIZE.checked_add(metadata_len)?;
+        if payload.checked_add(data_len)? > bytes.len() {
+            return None;
+        }
+        Some(Header {
+            name: "DLOB",
+            payload,
+            load_address: None,
+        })
+    }
+}
+
+const PARSERS: &[&dyn HeaderParser] = &[&UImage, &Trx, &Ambarella, &Mediatek, &Dlob];
+
+/* The headers at the start of the image, with their offsets, and the offset of the innermost
+payload */
+pub fn skip(bytes: &[u8]) -> (Vec<(usize, Header)>, usize) {
+    let mut headers = Vec::new();
+    let mut offset = 0;
+    while headers.len() < MAX_HEADERS {
+        let Some(header) = PARSERS
+            .iter()
+            .find_map(|parser| parser.parse(&bytes[offset..]))
+        else {
+            break;
+        };
+        if header.payload == 0 {
+            break;
+        }
+        let payload = offset + header.payload;
+        headers.push((offset, header));
+        offset = payload;
+    }
+    (headers, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* A legacy U-Boot image header loading the payload at the given address */
+    fn uimage(load_address: u32) -> Vec<u8> {
+        let mut header = vec![0; 64];
+        header[0..4].copy_from_slice(&0x27051956u32.to_be_bytes());
+        header[16..20].copy_from_slice(&load_address.to_be_bytes());
+        header[32..40].copy_from_slice(b"kernel\0\0");
+        let crc = hash(&header);
+        header[4..8].copy_from_slice(&crc.to_be_bytes());
+        header
+    }
+
+    #[test]
+    fn skips_nested_headers() {
+        let payload = b"payload bytes";
+        let inner = [uimage(0x80010000), payload.to_vec()].concat();
+        let mut trx = vec![0; 28];
+        trx[0..4].copy_from_slice(b"HDR0");
+        trx[4..8].copy_from_slice(&((28 + inner.len()) as u32).to_le_bytes());
+        trx[12..16].copy_from_slice(&(1u32 << 16).to_le_bytes());
+        trx[16..20].copy_from_slice(&28u32.to_le_bytes());
+        let image = [trx, inner].concat();
+
+        let (headers, offset) = skip(&image);
+        let found: Vec<(usize, &str, Option<u64>)> = headers
+            .iter()
+            .map(|(offset, header)| (*offset, header.name, header.load_address))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (0, "TRX", None),
+                (28, "U-Boot legacy image", Some(0x80010000))
+            ]
+        );
+        assert_eq!(&image[offset..], payload);
+    }
+
+    #[test]
+    fn ignores_a_uimage_with_a_bad_crc() {
+        let mut image = [uimage(0x80010000), vec![0; 16]].concat();
+        image[20] ^= 1;
+        let (headers, offset) = skip(&image);
+        assert!(headers.is_empty());
+        assert_eq!(offset, 0);
+    }
+}
@@ -0,0 +1,73 @@
+use {
+    png::{BitDepth, ColorType, Encoder, EncodingError},
+    rayon::iter::ParallelIterator,
+    std::{fs::File, io::BufWriter},
+};
+
+/* Width of the heatmap in pixels, and the smallest region of the file represented by each pixel.
+Regions are a power of two in size, chosen so the heatmap is no taller than it is wide. */
+const WIDTH: usize = 256;
+const MIN_REGION_SIZE: usize = 64;
+
+/* Size of the region of the file represented by each pixel */
+pub fn region_size(len: usize) -> usize {
+    len.div_ceil(WIDTH * WIDTH)
+        .next_power_of_two()
+        .max(MIN_REGION_SIZE)
+}
+
+/* Write a heatmap of the file, with a pixel for each region laid out left to right, then top to
+bottom. The red channel shows the density of strings (by the number starting in each region) and
+the green channel shows the density of pointers, each relative to the densest region. */
+pub fn write(
+    path: &str,
+    len: usize,
+    strings: impl Iterator<Item = usize>,
+    pointers: impl ParallelIterator<Item = usize>,
+) -> Result<(), EncodingError> {
+    let region_size = region_size(len);
+    let regions = len.div_ceil(region_size).max(1);
+
+    let mut string_counts = vec![0usize; regions];
+    for file_offset in strings.filter(|&file_offset| file_offset < len) {
+        string_counts[file_offset / region_size] += 1;
+    }
+    let empty = || vec![0usize; regions];
+    let pointer_counts = pointers
+        .fold(empty, |mut counts, file_offset| {
+            counts[file_offset / region_size] += 1;
+            counts
+        })
+        .reduce(empty, |mut a, b| {
+            a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+            a
+        });
+
+    let scale = |counts: &[usize]| -> Vec<u8> {
+        let max = counts.iter().copied().max().unwrap_or(0).max(1);
+        counts
+            .iter()
+            .map(|&count| (count * 255 / max) as u8)
+            .collect()
+    };
+    let red = scale(&string_counts);
+    let green = scale(&pointer_counts);
+
+    let height = regions.div_ceil(WIDTH);
+    let mut pixels = vec![0u8; WIDTH * height * 3];
+    for (region, pixel) in pixels.chunks_exact_mut(3).take(regions).enumerate() {
+        pixel[0] = red[region];
+        pixel[1] = green[region];
+    }
+
+    let mut encoder = Encoder::new(
+        BufWriter::new(File::create(path)?),
+        WIDTH as u32,
+        height as u32,
+    );
+    encoder.set_color(ColorType::Rgb);
+    encoder.set_depth(BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()
+}
@@ -0,0 +1,205 @@
+use {
+    crate::{
+        addresses::get_pointers,
+        collect_by_key, gpu, index_by_page_offset,
+        logging::log,
+        pack_buckets, page_offset,
+        progress::{get_progress_bar, ParallelProgressIterator},
+        regions::Regions,
+        sample::Sampling,
+        script::Script,
+        strings::{chunk_overlap, get_chunks, get_strings, get_terminated_strings, Terminated},
+        weight::StringWeigher,
+        PageIndex, RBaseTraits, PAGE_SIZE,
+    },
+    clap::{builder::PossibleValue, ValueEnum},
+    rayon::iter::{IntoParallelIterator, ParallelIterator},
+    rustc_hash::FxHashMap,
+    std::fmt::{Display, Formatter, Result},
+};
+
+/* Target file offsets and their weights, indexed by page offset */
+pub type TargetIndex<T> = PageIndex<(T, usize)>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum HeuristicKind {
+    Strings,
+    Pointers,
+}
+
+impl Display for HeuristicKind {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let value = self
+            .to_possible_value()
+            .unwrap_or(PossibleValue::new("unknown"));
+        write!(f, "{}", value.get_name())
+    }
+}
+
+/* A source of evidence for the base address. Each heuristic finds the locations in the image which
+are likely to be referenced by pointers, weighted by how likely they are to be referenced. Each
+address which could reference one of these targets is then a weighted vote for the base address at
+which it would do so. */
+pub trait Heuristic<T: RBaseTraits<T, N>, const N: usize>: Sync {
+    fn kind(&self) -> HeuristicKind;
+
+    /* Weight of each target, keyed by its file offset */
+    fn targets(&self, bytes: &[u8]) -> FxHashMap<T, usize>;
+}
+
+/* Strings are typically only ever referenced by a pointer to their first character */
+pub struct Strings<'a, T> {
+    pub regex: String,
+    pub terminated: Option<Terminated>,
+    pub string_bytes: Option<[bool; 256]>,
+    pub weigher: &'a StringWeigher,
+    pub script: Option<&'a Script>,
+    pub regions: Option<&'a Regions>,
+    pub offset_mask: Option<T>,
+    pub chunk_size: Option<usize>,
+}
+
+impl<T: RBaseTraits<T, N>, const N: usize> Heuristic<T, N> for Strings<'_, T> {
+    fn kind(&self) -> HeuristicKind {
+        HeuristicKind::Strings
+    }
+
+    fn targets(&self, bytes: &[u8]) -> FxHashMap<T, usize> {
+        if let (Some(gpu), Some(terminated)) = (gpu::get(), &self.terminated) {
+            return get_terminated_strings(
+                gpu,
+                bytes,
+                terminated,
+                self.weigher,
+                self.script,
+                self.regions,
+                self.offset_mask,
+            );
+        }
+        get_strings(
+            get_chunks(bytes, self.chunk_size, chunk_overlap(&self.regex)),
+            &self.regex,
+            self.string_bytes,
+            self.weigher,
+            self.script,
+            self.regions,
+            self.offset_mask,
+        )
+    }
+}
+
+/* Pointers are themselves referenced by other pointers, e.g. in linked structures or tables of
+tables */
+pub struct Pointers<'a, T, const N: usize> {
+    pub read_address_bytes: fn([u8; N]) -> T,
+    /* The alignment of the pointers in bytes */
+    pub align: usize,
+    pub pointer_mask: Option<T>,
+    /* Pointers below the minimum are more likely to be small integers (counts, flags, etc.) */
+    pub pointer_min: T,
+    pub address_range: Option<(T, T)>,
+    pub script: Option<&'a Script>,
+    pub ignored: &'a [T],
+    pub offset_mask: Option<T>,
+    pub chunk_size: Option<usize>,
+}
+
+impl<T: RBaseTraits<T, N>, const N: usize> Heuristic<T, N> for Pointers<'_, T, N> {
+    fn kind(&self) -> HeuristicKind {
+        HeuristicKind::Pointers
+    }
+
+    fn targets(&self, bytes: &[u8]) -> FxHashMap<T, usize> {
+        let pointers = get_pointers(
+            bytes,
+            self.read_address_bytes,
+            self.align,
+            self.pointer_mask,
+            self.address_range,
+            self.script,
+            None,
+            self.chunk_size,
+        )
+        .filter(|&(_file_offset, pointer)| pointer >= self.pointer_min)
+        .filter(|(_file_offset, pointer)| !self.ignored.contains(pointer))
+        .filter_map(|(file_offset, _pointer)| {
+            let file_offset = T::checked_from(file_offset)?;
+            match self.offset_mask {
+                Some(mask) => Some((file_offset & mask, 1)),
+                None => Some((file_offset, 1)),
+            }
+        });
+        let offsets = collect_by_key(pointers, |_weight, _other| {});
+        log!("Found: {:?} pointers", offsets.len());
+        offsets
+    }
+}
+
+/* Index each target by its page offset */
+pub fn index_targets<T: RBaseTraits<T, N>, const N: usize>(
+    kind: HeuristicKind,
+    targets: FxHashMap<T, usize>,
+    max_targets: usize,
+    sampling: Sampling,
+) -> TargetIndex<T> {
+    let (msg, name) = match kind {
+        HeuristicKind::Strings => ("Indexing strings", "strings"),
+        HeuristicKind::Pointers => ("Indexing pointers", "pointers"),
+    };
+    if targets.len() > max_targets {
+        log!(
+            "Sampling: {} of {} {} ({})",
+            max_targets,
+            targets.len(),
+            name,
+            sampling.strategy
+        );
+    }
+    let targets = sampling.sample(targets.into_par_iter().collect(), max_targets);
+    let progress_bar = get_progress_bar(msg, targets.len());
+    index_by_page_offset(
+        targets.into_par_iter().progress_with(progress_bar),
+        |&(file_offset, _weight)| page_offset(file_offset),
+        sampling,
+        name,
+    )
+}
+
+/* Combine the targets of each heuristic, so their votes are counted together */
+pub fn merge<T: RBaseTraits<T, N>, const N: usize>(
+    indexes: &[(HeuristicKind, TargetIndex<T>)],
+) -> TargetIndex<T> {
+    let mut merged: Vec<Vec<(T, usize)>> = vec![Vec::new(); PAGE_SIZE];
+    for (_kind, index) in indexes.iter() {
+        for (merged, targets) in merged.iter_mut().zip(index) {
+            merged.extend(targets);
+        }
+    }
+    pack_buckets(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::u24_from_le_bytes};
+
+    #[test]
+    fn pointers_beyond_the_reach_of_an_address_are_skipped() {
+        let mut bytes = vec![0; 0x100_0010];
+        bytes[0x10..0x13].copy_from_slice(&[0x56, 0x34, 0x12]);
+        bytes[0x100_0000..0x100_0003].copy_from_slice(&[0x56, 0x34, 0x12]);
+        let pointers: Pointers<u32, 3> = Pointers {
+            read_address_bytes: u24_from_le_bytes,
+            align: 1,
+            pointer_mask: None,
+            pointer_min: 0x10000,
+            address_range: None,
+            script: None,
+            ignored: &[],
+            offset_mask: None,
+            chunk_size: None,
+        };
+        let offsets = pointers.targets(&bytes);
+        assert!(offsets.contains_key(&0x10));
+        assert!(offsets.keys().all(|&offset| offset <= 0xFF_FFFF));
+    }
+}
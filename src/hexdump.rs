@@ -0,0 +1,186 @@
+/* Memory dumped over a UART or JTAG session is often captured as text, e.g. by xxd, hexdump -C or a
+boot loader's memory display command. Each line gives an address followed by the bytes at it (in
+order, however they are grouped), and sometimes their characters. The bytes are placed by their
+address, so any gaps between lines are filled with zeros, and lines of hexdump -C which are just a *
+repeat the previous line up to the next address. */
+
+/* Only text is parsed, so the start of the file is checked for NUL bytes */
+const TEXT_CHECK_SIZE: usize = 0x1000;
+
+/* Limit on the span of addresses dumped, so a stray address can't exhaust memory */
+const MAX_SIZE: u64 = 1 << 30;
+
+pub struct Hexdump {
+    pub address: u64,
+    pub bytes: Vec<u8>,
+}
+
+enum Line {
+    Data(u64, Vec<u8>),
+    Repeat,
+}
+
+fn parse_hex(token: &str) -> Option<Vec<u8>> {
+    if token.is_empty() || !token.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..token.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(token.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+/* The address and bytes of a line, taking hex tokens until the first which isn't (e.g. the
+characters column). The characters column may itself look like hex, so the bytes are later
+truncated to the distance to the next address. */
+fn parse_line(line: &str) -> Option<Line> {
+    let line = line.trim();
+    if line == "*" {
+        return Some(Line::Repeat);
+    }
+    let mut tokens = line.split_whitespace();
+    let address = tokens.next()?;
+    let address = address.strip_suffix(':').unwrap_or(address);
+    let address = address
+        .strip_prefix("0x")
+        .or_else(|| address.strip_prefix("0X"))
+        .unwrap_or(address);
+    if address.is_empty() || address.len() > 16 {
+        return None;
+    }
+    let address = u64::from_str_radix(address, 16).ok()?;
+    let mut bytes = Vec::new();
+    for token in tokens {
+        match parse_hex(token).filter(|group| group.len() <= 8) {
+            Some(group) => bytes.extend(group),
+            None => break,
+        }
+    }
+    Some(Line::Data(address, bytes))
+}
+
+/* The bytes dumped, if the file is a hexdump */
+pub fn parse(bytes: &[u8]) -> Option<Hexdump> {
+    if bytes.is_empty() || bytes[..bytes.len().min(TEXT_CHECK_SIZE)].contains(&0) {
+        return None;
+    }
+    let text = std::str::from_utf8(bytes).ok()?;
+
+    /* Lines which aren't part of the dump (e.g. the command which produced it) are ignored */
+    let total = text.lines().filter(|line| !line.trim().is_empty()).count();
+    let lines: Vec<Line> = text.lines().filter_map(parse_line).collect();
+    let dumped = lines
+        .iter()
+        .filter(|line| matches!(line, Line::Data(_address, bytes) if !bytes.is_empty()))
+        .count();
+    if dumped * 2 <= total {
+        return None;
+    }
+    let addresses: Vec<u64> = lines
+        .iter()
+        .filter_map(|line| match line {
+            Line::Data(address, _bytes) => Some(*address),
+            Line::Repeat => None,
+        })
+        .collect();
+    let start = *addresses.iter().min()?;
+
+    /* The usual number of bytes per line, used for the last one */
+    let mut strides: Vec<u64> = addresses
+        .windows(2)
+        .filter_map(|pair| pair[1].checked_sub(pair[0]))
+        .filter(|&stride| stride != 0)
+        .collect();
+    strides.sort_unstable();
+    let stride = strides.get(strides.len() / 2).copied();
+
+    let mut dump: Vec<u8> = Vec::new();
+    let mut previous: &[u8] = &[];
+    let mut repeat = false;
+    for (idx, line) in lines.iter().enumerate() {
+        let Line::Data(address, data) = line else {
+            repeat = true;
+            continue;
+        };
+        let offset = address - start;
+        if offset >= MAX_SIZE {
+            return None;
+        }
+        let offset = offset as usize;
+        if repeat && !previous.is_empty() {
+            while dump.len() < offset {
+                dump.extend_from_slice(previous);
+            }
+        }
+        repeat = false;
+
+        let next = lines[idx + 1..].iter().find_map(|line| match line {
+            Line::Data(address, _bytes) => Some(*address),
+            Line::Repeat => None,
+        });
+        let len = match (next, stride) {
+            (Some(next), _) => next.saturating_sub(*address),
+            (None, Some(stride)) => stride,
+            (None, None) => data.len() as u64,
+        };
+        let data = &data[..data.len().min(len as usize)];
+
+        /* Later lines overwrite any earlier ones at the same address, and gaps are filled */
+        let end = offset + data.len();
+        if dump.len() < end {
+            dump.resize(end, 0);
+        }
+        dump[offset..end].copy_from_slice(data);
+        previous = data;
+    }
+    if dump.is_empty() {
+        return None;
+    }
+    Some(Hexdump {
+        address: start,
+        bytes: dump,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hexdump_c_with_repeated_lines() {
+        let text = "\
+80010000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 00 00 00  |Hello, world!...|
+80010010  ff ff ff ff ff ff ff ff  ff ff ff ff ff ff ff ff  |................|
+*
+80010040  de ad be ef                                       |....|
+80010044
+";
+        let hexdump = parse(text.as_bytes()).unwrap();
+        assert_eq!(hexdump.address, 0x80010000);
+        let mut expected = b"Hello, world!\0\0\0".to_vec();
+        expected.extend([0xff; 0x30]);
+        expected.extend([0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(hexdump.bytes, expected);
+    }
+
+    #[test]
+    fn parses_xxd_filling_gaps_with_zeros() {
+        let text = "\
+00001000: 4865 6c6c 6f00 0102 0304 0506 0708 090a  Hello...........
+00001020: aabb ccdd                                ....
+";
+        let hexdump = parse(text.as_bytes()).unwrap();
+        assert_eq!(hexdump.address, 0x1000);
+        let mut expected = b"Hello\0".to_vec();
+        expected.extend(1..=10);
+        expected.extend([0; 0x10]);
+        expected.extend([0xaa, 0xbb, 0xcc, 0xdd]);
+        assert_eq!(hexdump.bytes, expected);
+    }
+
+    #[test]
+    fn ignores_text_which_isnt_a_dump() {
+        assert!(parse(b"Linux version 5.10.0\nsome other text\n").is_none());
+        assert!(parse(b"\x7fELF\0\0\0").is_none());
+    }
+}
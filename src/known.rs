@@ -0,0 +1,61 @@
+/* Load addresses commonly used by various SoC families, boot loaders and kernels */
+const KNOWN_BASES: &[(u64, &str)] = &[
+    (0x00000000, "Cortex-M flash / vector table"),
+    (0x00010000, "ARM Linux user-space executable"),
+    (0x00100000, "x86 Linux kernel (physical)"),
+    (0x00400000, "MIPS/x86 user-space executable"),
+    (0x08000000, "STM32 flash"),
+    (0x10000000, "RP2040 XIP flash"),
+    (0x1FFF0000, "STM32 system memory"),
+    (0x20000000, "Cortex-M SRAM"),
+    (0x30008000, "Samsung S3C Linux kernel"),
+    (0x3F400000, "ESP32 flash-mapped data"),
+    (0x40080000, "ESP32 IRAM"),
+    (0x400D0000, "ESP32 flash-mapped code"),
+    (0x40100000, "ESP8266 IRAM"),
+    (0x40200000, "ESP8266 flash-mapped code"),
+    (0x80000000, "MIPS KSEG0"),
+    (0x80001000, "Broadcom BCM47xx Linux kernel"),
+    (0x80002000, "Lantiq Linux kernel"),
+    (0x80008000, "ARM Linux kernel (physical)"),
+    (0x80010000, "Broadcom BCM63xx Linux kernel"),
+    (0x80060000, "Atheros AR71xx Linux kernel"),
+    (0x87800000, "i.MX6 U-Boot"),
+    (0x9FC00000, "MIPS KSEG0 reset vector"),
+    (0xBFC00000, "MIPS KSEG1 reset vector"),
+    (0xC0008000, "ARM Linux kernel"),
+    (0xC1000000, "x86 Linux kernel"),
+    (0xFFFF0000, "ARM high vectors"),
+    (0xFFFFFF8008080000, "AArch64 Linux kernel (4.x)"),
+    (0xFFFF000008080000, "AArch64 Linux kernel (4.16+)"),
+    (0xFFFFFFC000080000, "AArch64 Linux kernel (3.x)"),
+    (0xFFFFFFFF81000000, "x86-64 Linux kernel"),
+];
+
+/* Candidates matching a known base address are ranked as if their score were multiplied by this when
+using known bases */
+pub const KNOWN_BASE_BOOST: usize = 2;
+
+pub fn lookup(base: u64) -> Option<&'static str> {
+    KNOWN_BASES
+        .iter()
+        .find(|&&(known, _)| known == base)
+        .map(|&(_, name)| name)
+}
+
+/* Address ranges commonly used for memory-mapped peripheral registers, which an image is unlikely to
+be loaded over */
+const MMIO_RANGES: &[(u64, u64, &str)] = &[
+    (0x40000000, 0x60000000, "Cortex-M peripherals"),
+    (0xB0000000, 0xB0100000, "Ralink/MediaTek SoC registers"),
+    (0xB8000000, 0xB8200000, "Atheros SoC registers"),
+    (0xE0000000, 0xE0100000, "Cortex-M private peripheral bus"),
+];
+
+/* The first MMIO range overlapping the given range of addresses (end exclusive) */
+pub fn mmio(start: u64, end: u64) -> Option<(u64, u64, &'static str)> {
+    MMIO_RANGES
+        .iter()
+        .find(|&&(mmio_start, mmio_end, _)| start < mmio_end && mmio_start < end)
+        .copied()
+}
@@ -0,0 +1,299 @@
+/* The search for the base address of a firmware image, which the rbase binary drives. Other tools
+can call base::get_base_address for every ranked candidate, and set a progress::ProgressSink to
+follow each stage of the search. */
+use {
+    packed::{Pack, PackedVec},
+    rayon::iter::{IntoParallelIterator, ParallelIterator},
+    rustc_hash::FxHashMap,
+    sample::Sampling,
+    std::{
+        collections::hash_map::Entry,
+        fmt::LowerHex,
+        hash::Hash,
+        mem::size_of,
+        num::TryFromIntError,
+        ops::{BitAnd, BitOr, Sub},
+    },
+};
+
+pub use args::{Endian, Size};
+
+pub mod addresses;
+pub mod anchor;
+pub mod android;
+pub mod arch;
+pub mod archive;
+pub mod args;
+pub mod arm64;
+pub mod banner;
+pub mod base;
+pub mod checkpoint;
+pub mod compare;
+pub mod cpe;
+pub mod decompress;
+pub mod dump;
+pub mod elf;
+pub mod emu;
+pub mod esp;
+pub mod explain;
+pub mod fdt;
+pub mod fill;
+pub mod format;
+pub mod gpu;
+pub mod harness;
+pub mod header;
+pub mod heatmap;
+pub mod heuristic;
+pub mod hexdump;
+pub mod known;
+pub mod logging;
+pub mod mapping;
+pub mod memdump;
+pub mod nand;
+pub mod numa;
+pub mod packed;
+pub mod profile;
+pub mod progress;
+pub mod refine;
+pub mod regions;
+pub mod remote;
+pub mod sample;
+pub mod script;
+pub mod sections;
+pub mod signature;
+pub mod sketch;
+pub mod strings;
+pub mod svd;
+pub mod symbols;
+pub mod timeout;
+pub mod uboot;
+pub mod weight;
+pub mod yara;
+
+pub const PAGE_OFFSET_MASK: usize = 0xFFF;
+pub const PAGE_SIZE: usize = PAGE_OFFSET_MASK + 1;
+
+/* Values indexed by their page offset, with a sorted bucket for each possible page offset */
+pub type PageIndex<V> = Vec<PackedVec<V>>;
+
+pub fn page_offset<T: Into<u64>>(value: T) -> usize {
+    value.into() as usize & PAGE_OFFSET_MASK
+}
+
+/* Collect the values into buckets by page offset, each of which is sorted (and sampled, if there are
+more than the maximum per page). Each thread fills its own buckets, which are merged at the end, so
+the hot loop needs neither hashing nor locking. */
+pub fn index_by_page_offset<V: Pack + Ord + Send + Sync>(
+    values: impl ParallelIterator<Item = V>,
+    page_offset: impl Fn(&V) -> usize + Sync + Send,
+    sampling: Sampling,
+    name: &str,
+) -> PageIndex<V> {
+    let empty = || (0..PAGE_SIZE).map(|_| Vec::new()).collect::<Vec<Vec<V>>>();
+    let buckets = values
+        .fold(empty, |mut index, value| {
+            index[page_offset(&value)].push(value);
+            index
+        })
+        .reduce(empty, |mut a, b| {
+            for (a, mut b) in a.iter_mut().zip(b) {
+                a.append(&mut b);
+            }
+            a
+        });
+    pack_buckets(sampling.cap_per_page(buckets, name))
+}
+
+/* Collect the values into a hashtable, combining those with the same key. Each thread accumulates
+into its own hashtable and these are merged at the end, avoiding contention on a shared one. */
+pub fn collect_by_key<K: Eq + Hash + Send, V: Send>(
+    values: impl ParallelIterator<Item = (K, V)>,
+    combine: impl Fn(&mut V, V) + Sync + Send,
+) -> FxHashMap<K, V> {
+    let insert = |map: &mut FxHashMap<K, V>, key: K, value: V| match map.entry(key) {
+        Entry::Occupied(mut entry) => combine(entry.get_mut(), value),
+        Entry::Vacant(entry) => {
+            entry.insert(value);
+        }
+    };
+    values
+        .fold(FxHashMap::default, |mut map, (key, value)| {
+            insert(&mut map, key, value);
+            map
+        })
+        .reduce(FxHashMap::default, |mut a, mut b| {
+            if a.len() < b.len() {
+                std::mem::swap(&mut a, &mut b);
+            }
+            a.reserve(b.len());
+            for (key, value) in b {
+                insert(&mut a, key, value);
+            }
+            a
+        })
+}
+
+/* Sort each bucket and pack it, since the index of a large image can hold tens of millions of
+values */
+pub fn pack_buckets<V: Pack + Send + Sync>(buckets: Vec<Vec<V>>) -> PageIndex<V> {
+    buckets
+        .into_par_iter()
+        .map(|mut bucket| {
+            bucket.sort_unstable();
+            PackedVec::from_sorted(bucket)
+        })
+        .collect()
+}
+
+pub trait RBaseTraits<T, const N: usize>:
+    Copy
+    + Send
+    + Sync
+    + Default
+    + PartialEq
+    + Eq
+    + Hash
+    + BitAnd<Output = T>
+    + BitOr<Output = T>
+    + Sub<Output = T>
+    + PartialOrd
+    + Ord
+    + LowerHex
+    + TryFrom<usize, Error = TryFromIntError>
+    + TryInto<usize>
+    + Into<u64>
+    + Pack
+{
+    const BITS: u32;
+
+    /* Convert a value given on the command line, saturating if it doesn't fit */
+    fn saturating_from(value: u64) -> T;
+
+    /* Likewise, but keeping only the bits which fit (e.g. of a mask) */
+    fn truncating_from(value: u64) -> T;
+
+    /* Convert a file offset, unless it is too large for an address */
+    fn checked_from(value: usize) -> Option<T>;
+
+    fn checked_add(self, rhs: T) -> Option<T>;
+    fn checked_sub(self, rhs: T) -> Option<T>;
+}
+
+impl RBaseTraits<u32, 3> for u32 {
+    const BITS: u32 = 24;
+
+    fn saturating_from(value: u64) -> u32 {
+        u32::try_from(value).unwrap_or(u32::MAX).min(0xFFFFFF)
+    }
+
+    fn truncating_from(value: u64) -> u32 {
+        value as u32 & 0xFFFFFF
+    }
+
+    fn checked_from(value: usize) -> Option<u32> {
+        u32::try_from(value).ok().filter(|&value| value <= 0xFFFFFF)
+    }
+
+    fn checked_add(self, rhs: u32) -> Option<u32> {
+        u32::checked_add(self, rhs).filter(|&sum| sum <= 0xFFFFFF)
+    }
+
+    fn checked_sub(self, rhs: u32) -> Option<u32> {
+        u32::checked_sub(self, rhs)
+    }
+}
+
+impl RBaseTraits<u32, { size_of::<u32>() }> for u32 {
+    const BITS: u32 = u32::BITS;
+
+    fn saturating_from(value: u64) -> u32 {
+        u32::try_from(value).unwrap_or(u32::MAX)
+    }
+
+    fn truncating_from(value: u64) -> u32 {
+        value as u32
+    }
+
+    fn checked_from(value: usize) -> Option<u32> {
+        u32::try_from(value).ok()
+    }
+
+    fn checked_add(self, rhs: u32) -> Option<u32> {
+        u32::checked_add(self, rhs)
+    }
+
+    fn checked_sub(self, rhs: u32) -> Option<u32> {
+        u32::checked_sub(self, rhs)
+    }
+}
+
+impl RBaseTraits<u64, { size_of::<u64>() }> for u64 {
+    const BITS: u32 = u64::BITS;
+
+    fn saturating_from(value: u64) -> u64 {
+        value
+    }
+
+    fn truncating_from(value: u64) -> u64 {
+        value
+    }
+
+    fn checked_from(value: usize) -> Option<u64> {
+        u64::try_from(value).ok()
+    }
+
+    fn checked_add(self, rhs: u64) -> Option<u64> {
+        u64::checked_add(self, rhs)
+    }
+
+    fn checked_sub(self, rhs: u64) -> Option<u64> {
+        u64::checked_sub(self, rhs)
+    }
+}
+
+pub fn u24_from_le_bytes(bytes: [u8; 3]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0])
+}
+
+pub fn u24_from_be_bytes(bytes: [u8; 3]) -> u32 {
+    u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_of_24_bits_never_exceed_them() {
+        assert_eq!(
+            <u32 as RBaseTraits<u32, 3>>::checked_add(0xFF_FFF0, 0xF),
+            Some(0xFF_FFFF)
+        );
+        assert_eq!(
+            <u32 as RBaseTraits<u32, 3>>::checked_add(0xFF_FFF0, 0x10),
+            None
+        );
+        assert_eq!(<u32 as RBaseTraits<u32, 3>>::checked_sub(0x10, 0x11), None);
+        assert_eq!(
+            <u32 as RBaseTraits<u32, 3>>::checked_from(0xFF_FFFF),
+            Some(0xFF_FFFF)
+        );
+        assert_eq!(<u32 as RBaseTraits<u32, 3>>::checked_from(0x100_0000), None);
+        assert_eq!(
+            <u32 as RBaseTraits<u32, 3>>::saturating_from(0x1_0000_0000),
+            0xFF_FFFF
+        );
+    }
+
+    #[test]
+    fn addresses_of_32_and_64_bits_never_wrap() {
+        assert_eq!(<u32 as RBaseTraits<u32, 4>>::checked_add(u32::MAX, 1), None);
+        assert_eq!(<u32 as RBaseTraits<u32, 4>>::checked_sub(0, 1), None);
+        assert_eq!(
+            <u32 as RBaseTraits<u32, 4>>::checked_from(0x1_0000_0000),
+            None
+        );
+        assert_eq!(<u64 as RBaseTraits<u64, 8>>::checked_add(u64::MAX, 1), None);
+        assert_eq!(<u64 as RBaseTraits<u64, 8>>::checked_sub(0, 1), None);
+    }
+}
@@ -0,0 +1,48 @@
+use std::{
+    fmt::Arguments,
+    fs::File,
+    io::{LineWriter, Result, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+};
+
+/* Diagnostic output is printed to stderr (leaving stdout for the results, so they can be captured by
+scripts), unless a log file is given, in which case it is written there instead so the terminal only
+shows the progress and the result */
+static LOG_FILE: OnceLock<Mutex<LineWriter<File>>> = OnceLock::new();
+
+/* Diagnostic output is discarded when it would be interleaved from several scans at once */
+static DISCARD: AtomicBool = AtomicBool::new(false);
+
+pub fn init(path: &str) -> Result<()> {
+    let file = LineWriter::new(File::create(path)?);
+    LOG_FILE
+        .set(Mutex::new(file))
+        .expect("log file already initialized");
+    Ok(())
+}
+
+pub fn discard() {
+    DISCARD.store(true, Ordering::Relaxed);
+}
+
+pub fn write(args: Arguments) {
+    if DISCARD.load(Ordering::Relaxed) {
+        return;
+    }
+    match LOG_FILE.get() {
+        Some(file) => writeln!(file.lock().unwrap(), "{args}").unwrap(),
+        None => eprintln!("{args}"),
+    }
+}
+
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        $crate::logging::write(format_args!($($arg)*))
+    };
+}
+
+pub use crate::log;
@@ -1,381 +1,683 @@
 use {
-    clap::Parser,
-    dashmap::{DashMap, DashSet},
-    indicatif::{ParallelProgressIterator, ProgressBar, ProgressFinish, ProgressStyle},
+    indicatif::{ProgressBar, ProgressFinish, ProgressStyle},
     memmap2::Mmap,
-    rayon::iter::{IntoParallelIterator, ParallelIterator},
-    regex::bytes::Regex,
+    rayon::{
+        iter::{IntoParallelRefIterator, ParallelIterator},
+        ThreadPoolBuilder,
+    },
+    rbase::{
+        android, arch, archive,
+        args::Args,
+        banner,
+        base::{get_base_address, Found, Hint},
+        cpe::Database,
+        decompress, esp, format, gpu, harness, header, hexdump,
+        logging::{self, log},
+        mapping, memdump, nand, numa, page_offset,
+        progress::{self, ParallelProgressIterator, Progress, ProgressSink, Stage},
+        remote,
+        script::Script,
+        sections,
+        svd::Svd,
+        timeout, u24_from_be_bytes, u24_from_le_bytes,
+        weight::StringWeigher,
+        Endian, Size, PAGE_SIZE,
+    },
     std::{
-        fmt::{Display, Formatter, LowerHex, Result},
-        fs::File,
-        hash::Hash,
-        mem::size_of,
-        num::TryFromIntError,
-        ops::{BitAnd, Sub},
+        fmt::{Display, Formatter},
+        fs::{read_dir, File},
+        path::Path,
+        process::ExitCode,
         slice::from_raw_parts,
-        thread,
         time::Instant,
     },
 };
 
-const PAGE_OFFSET_MASK: usize = 0xFFF;
-
-enum Size {
-    Bits32,
-    Bits64,
+/* Progress is shown on the terminal by indicatif */
+struct IndicatifSink;
+
+impl ProgressSink for IndicatifSink {
+    fn stage(&self, msg: &'static str, length: usize) -> Box<dyn Stage> {
+        let progress_bar = ProgressBar::new(length as u64)
+            .with_message(format!("{msg:<50}"))
+            .with_finish(ProgressFinish::AndLeave);
+        progress_bar.set_style(
+            ProgressStyle::default_bar()
+                .template(
+                    "{spinner:.green} [{elapsed_precise:.green}] [{eta_precise:.cyan}] {msg:.magenta} ({percent:.bold}%) [{bar:30.cyan/blue}]",
+                )
+                .unwrap()
+                .progress_chars("█░")
+        );
+        Box::new(IndicatifStage(progress_bar))
+    }
 }
 
-impl Display for Size {
-    fn fmt(&self, f: &mut Formatter) -> Result {
-        match self {
-            Size::Bits32 => write!(f, "32-bit"),
-            Size::Bits64 => write!(f, "64-bit"),
-        }
+struct IndicatifStage(ProgressBar);
+
+impl Stage for IndicatifStage {
+    fn inc(&self, delta: u64) {
+        self.0.inc(delta);
     }
-}
 
-enum Endian {
-    Little,
-    Big,
-}
+    fn finish(&self) {
+        self.0.finish();
+    }
 
-impl Display for Endian {
-    fn fmt(&self, f: &mut Formatter) -> Result {
-        match self {
-            Endian::Little => write!(f, "little"),
-            Endian::Big => write!(f, "big"),
-        }
+    fn clear(&self) {
+        self.0.finish_and_clear();
     }
 }
 
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {
-    #[arg(help = "Name of the file to process")]
-    pub filename: String,
-
-    #[arg(
-        long = "32",
-        help = "File is 32-bit (default)",
-        conflicts_with = "is_64bit"
-    )]
-    is_32bit: bool,
-
-    #[arg(long = "64", help = "File is 64-bit", conflicts_with = "is_32bit")]
-    is_64bit: bool,
-
-    #[arg(
-        long = "little",
-        help = "File is little-endian (default)",
-        conflicts_with = "is_big_endian"
-    )]
-    is_little_endian: bool,
-
-    #[arg(
-        long = "big",
-        help = "File is big-endian",
-        conflicts_with = "is_little_endian"
-    )]
-    is_big_endian: bool,
-
-    #[arg(long = "max", help = "Maximum string length", default_value = "1024")]
-    pub max_string_length: usize,
-
-    #[arg(long = "min", help = "Minimum string length", default_value = "10")]
-    pub min_string_length: usize,
-
-    #[arg(
-        short = 's',
-        long = "max-strings",
-        help = "Maximum number of strings to sample",
-        default_value = "100000"
-    )]
-    pub max_strings: usize,
-
-    #[arg(
-        short = 'a',
-        long = "max-addresses",
-        help = "Maximum number of addresses to sample",
-        default_value = "1000000"
-    )]
-    pub max_addresses: usize,
+/* Find the base address of the bytes, with the size and endianness of the arguments */
+fn find_base(
+    args: &Args,
+    bytes: &[u8],
+    weigher: &StringWeigher,
+    script: Option<&Script>,
+    svd: Option<&Svd>,
+    hints: &[Hint],
+) -> Result<Option<Found>, String> {
+    let found = match args.size() {
+        Size::Bits24 => get_base_address(
+            args,
+            bytes,
+            weigher,
+            script,
+            svd,
+            hints,
+            match args.endian() {
+                Endian::Little => u24_from_le_bytes,
+                Endian::Big => u24_from_be_bytes,
+            },
+        )?
+        .map(|base| base.found(bytes.len(), 24)),
+        Size::Bits32 => get_base_address(
+            args,
+            bytes,
+            weigher,
+            script,
+            svd,
+            hints,
+            match args.endian() {
+                Endian::Little => u32::from_le_bytes,
+                Endian::Big => u32::from_be_bytes,
+            },
+        )?
+        .map(|base| base.found(bytes.len(), u32::BITS)),
+        Size::Bits64 => get_base_address(
+            args,
+            bytes,
+            weigher,
+            script,
+            svd,
+            hints,
+            match args.endian() {
+                Endian::Little => u64::from_le_bytes,
+                Endian::Big => u64::from_be_bytes,
+            },
+        )?
+        .map(|base| base.found(bytes.len(), u64::BITS)),
+    };
+    Ok(found)
 }
 
-impl Args {
-    pub fn size(&self) -> Size {
-        if self.is_64bit {
-            Size::Bits64
-        } else {
-            Size::Bits32
-        }
-    }
-
-    pub fn endian(&self) -> Endian {
-        if self.is_big_endian {
-            Endian::Big
-        } else {
-            Endian::Little
-        }
-    }
+/* A part of the file loaded at a known address (e.g. a segment of an ESP32 image, or a region of a
+memory dump), which is analysed on its own */
+struct Segment {
+    source: String,
+    offset: usize,
+    len: usize,
+    address: u64,
 }
 
-impl Display for Args {
-    fn fmt(&self, f: &mut Formatter) -> Result {
-        writeln!(f, "ARGS")?;
-        writeln!(f, "\tfile: {}", self.filename)?;
-        writeln!(f, "\tsize: {:}", self.size())?;
-        writeln!(f, "\tendian: {:}", self.endian())?;
-        writeln!(f, "\tmax: {}", self.max_string_length)?;
-        writeln!(f, "\tmin: {}", self.min_string_length)?;
-        writeln!(f, "\tmax strings: {}", self.max_strings)?;
-        writeln!(f, "\tmax addresses: {}", self.max_addresses)?;
-        Ok(())
+/* Segments smaller than this are skipped, being unlikely to hold enough strings */
+const MIN_SEGMENT_SIZE: usize = PAGE_SIZE;
+
+/* Find the base address of each segment, checking it against the address at which the segment is
+loaded. The result is that of the largest segment, being the most reliable. */
+fn find_segment_bases(
+    args: &Args,
+    bytes: &[u8],
+    weigher: &StringWeigher,
+    script: Option<&Script>,
+    svd: Option<&Svd>,
+    hints: &[Hint],
+    segments: &[Segment],
+) -> Result<Option<Found>, String> {
+    let (segments, small): (Vec<&Segment>, Vec<&Segment>) = segments
+        .iter()
+        .partition(|segment| segment.len >= MIN_SEGMENT_SIZE);
+    if !small.is_empty() {
+        log!(
+            "Skipping: {} segments smaller than 0x{:x} bytes",
+            small.len(),
+            MIN_SEGMENT_SIZE
+        );
     }
+    let mut results = Vec::new();
+    for segment in segments {
+        let end = segment.offset + segment.len;
+        log!(
+            "Analysing: {} at 0x{:x}-0x{:x} loaded at 0x{:x}",
+            segment.source,
+            segment.offset,
+            end,
+            segment.address
+        );
+        /* Base addresses are page aligned, so the segment is preceded by padding up to the page
+        offset of its address */
+        let padding = page_offset(segment.address);
+        let mut padded = vec![0; padding];
+        padded.extend_from_slice(&bytes[segment.offset..end]);
+        let declared = segment.address - padding as u64;
+        let mut hints = hints.to_vec();
+        hints.push(Hint {
+            source: format!("{} address", segment.source),
+            address: declared,
+        });
+        let base = find_base(args, &padded, weigher, script, svd, &hints)?;
+        results.push((segment, declared, base));
+    }
+    for (segment, declared, base) in results.iter() {
+        let found = match base {
+            Some(found) if found.base == *declared => format!("0x{:x} (matches)", found.base),
+            Some(found) => format!("0x{:x} (differs)", found.base),
+            None => "none".to_string(),
+        };
+        log!(
+            "Segment: 0x{:x} loaded at 0x{:x}, found {found}",
+            segment.offset,
+            segment.address
+        );
+    }
+    Ok(results
+        .into_iter()
+        .max_by_key(|(segment, _declared, _base)| segment.len)
+        .and_then(|(_segment, _declared, base)| base))
 }
 
-/* Progress */
-fn get_progress_bar(msg: &'static str, length: usize) -> indicatif::ProgressBar {
-    let progress_bar = ProgressBar::new(length as u64)
-        .with_message(format!("{msg:<50}"))
-        .with_finish(ProgressFinish::AndLeave);
-    progress_bar.set_style(
-        ProgressStyle::default_bar()
-            .template(
-                "{spinner:.green} [{elapsed_precise:.green}] [{eta_precise:.cyan}] {msg:.magenta} ({percent:.bold}%) [{bar:30.cyan/blue}]",
-            )
-            .unwrap()
-            .progress_chars("█░")
-    );
-    progress_bar
+/* A file to scan, or a member of an archive */
+struct Input {
+    path: String,
+    member: Option<archive::Member>,
 }
 
-trait RBaseTraits<T, const N: usize>:
-    Copy
-    + Send
-    + Sync
-    + Default
-    + PartialEq
-    + Eq
-    + Hash
-    + BitAnd<Output = T>
-    + Sub<Output = T>
-    + PartialOrd
-    + LowerHex
-    + TryFrom<usize, Error = TryFromIntError>
-{
+impl Display for Input {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match &self.member {
+            Some(member) => write!(f, "{}:{}", self.path, member.name),
+            None => write!(f, "{}", self.path),
+        }
+    }
 }
 
-impl RBaseTraits<u32, { size_of::<u32>() }> for u32 {}
-impl RBaseTraits<u64, { size_of::<u64>() }> for u64 {}
-
-fn get_strings_by_page_offset<T: RBaseTraits<T, N>, const N: usize>(
-    bytes: &[u8],
-    min_string_length: usize,
-    max_string_length: usize,
-    max_strings: usize,
-) -> DashMap<T, Vec<T>> {
-    /* Split the input into a number chunks which overlap by the maximum string length - 1 */
-    let chunk_size = bytes.len() / thread::available_parallelism().unwrap();
-    let limit = bytes.len();
-    let chunks: Vec<(usize, &[u8])> = (0..limit)
-        .step_by(chunk_size)
-        .map(|chunk_offset| {
-            (
-                chunk_offset,
-                &bytes
-                    [chunk_offset..(chunk_offset + chunk_size + max_string_length - 1).min(limit)],
-            )
-        })
-        .collect();
+/* Find the base address of a single file */
+fn scan(
+    mut args: Args,
+    input: &Input,
+    weigher: &StringWeigher,
+    script: Option<&Script>,
+    svd: Option<&Svd>,
+    database: Option<&Database>,
+) -> Result<Option<Found>, String> {
+    let path = input.path.as_str();
+    let name = input.to_string();
+    args.filenames = vec![name.clone()];
+
+    /* Download an image given by URL, analysing the copy */
+    let download;
+    let local = match remote::is_url(path) {
+        true => {
+            download = remote::fetch(path)?;
+            log!("Downloaded: {path} to {}", download.path().display());
+            download.path()
+        }
+        false => Path::new(path),
+    };
 
-    /* Search each chunk for strings and collect them in a hash set */
-    let regex = format!(
-        "([[:print:][:space:]]{{{},{}}})\0",
-        min_string_length, max_string_length
-    );
-    let re = Regex::new(&regex).unwrap();
-    let offsets = DashSet::<T>::new();
-    let progress_bar = get_progress_bar("Finding strings", chunks.len());
-    chunks
-        .into_par_iter()
-        .progress_with(progress_bar)
-        .for_each(|(chunk_offset, chunk)| {
-            re.find_iter(chunk).for_each(|m| {
-                let file_offset = T::try_from(chunk_offset + m.start()).unwrap();
-                offsets.insert(file_offset);
+    let (map, read);
+    let mut bytes: &[u8] = if let Some(member) = &input.member {
+        read = archive::read(&local.to_string_lossy(), member)?;
+        &read
+    } else if args.no_mmap {
+        read = std::fs::read(local).map_err(|e| format!("{path}: {e}"))?;
+        &read
+    } else {
+        let file = File::open(local).map_err(|e| format!("{path}: {e}"))?;
+        map = unsafe { Mmap::map(&file) }.map_err(|e| format!("{path}: {e}"))?;
+        mapping::advise(&map);
+        unsafe { from_raw_parts(map.as_ptr(), map.len()) }
+    };
+    let file = bytes;
+
+    /* Reassemble a hexdump, whose first address (unless it is just a file offset) is likely to be where
+    the image is loaded */
+    let mut hints = Vec::new();
+    let dumped;
+    if let Some(hexdump) = hexdump::parse(bytes) {
+        log!(
+            "Found: hexdump of 0x{:x} bytes at 0x{:x}",
+            hexdump.bytes.len(),
+            hexdump.address
+        );
+        if hexdump.address != 0 {
+            hints.push(Hint {
+                source: "hexdump address".to_string(),
+                address: hexdump.address,
             });
-        });
-    println!("Found: {:?} strings", offsets.len());
-
-    /* Index each string by its page offset */
-    let index = DashMap::<T, Vec<T>>::new();
-    let progress_bar = get_progress_bar("Indexing strings", offsets.len());
-    let page_offset_mask = T::try_from(PAGE_OFFSET_MASK).unwrap();
-    offsets
-        .into_par_iter()
-        .take_any(max_strings)
-        .progress_with(progress_bar)
-        .for_each(|file_offset| {
-            let page_offset = file_offset & page_offset_mask;
-            if let Some(mut file_offsets) = index.get_mut(&page_offset) {
-                file_offsets.push(file_offset);
-            } else {
-                index.insert(page_offset, vec![file_offset]);
+        }
+        dumped = hexdump.bytes;
+        bytes = &dumped;
+    }
+
+    /* Strip the out-of-band bytes from a raw NAND dump */
+    let stripped;
+    if let Some(layout) = args.nand_layout {
+        stripped = nand::strip_oob(bytes, layout);
+        log!(
+            "Stripping: 0x{:x} out-of-band bytes",
+            bytes.len() - stripped.len()
+        );
+        bytes = &stripped;
+    }
+
+    /* Analyse just the kernel of an Android boot image */
+    if !args.keep_headers {
+        if let Some(boot_image) = android::parse(bytes) {
+            log!(
+                "Found: Android boot image (version {}), kernel at 0x{:x}-0x{:x}, ramdisk at 0x{:x}-0x{:x}",
+                boot_image.version,
+                boot_image.kernel.start,
+                boot_image.kernel.end,
+                boot_image.ramdisk.start,
+                boot_image.ramdisk.end
+            );
+            if let Some(address) = boot_image.kernel_address {
+                hints.push(Hint {
+                    source: "Android boot image kernel address".to_string(),
+                    address,
+                });
             }
-        });
-    index
-}
+            bytes = &bytes[boot_image.kernel];
+        }
+    }
 
-fn get_addresses_by_page_offset<T: RBaseTraits<T, N>, const N: usize>(
-    bytes: &[u8],
-    read_address_bytes: fn([u8; N]) -> T,
-    max_addresses: usize,
-) -> DashMap<T, Vec<T>> {
-    let chunks = bytes
-        .chunks(size_of::<T>())
-        .map(|c| c.try_into().unwrap())
-        .collect::<Vec<[u8; N]>>();
-
-    /* Search each chunk for addresses and collect them in a hash set */
-    let progress_bar = get_progress_bar("Finding addresses", chunks.len());
-    let addresses = DashSet::<T>::new();
-    chunks
-        .into_par_iter()
-        .progress_with(progress_bar)
-        .map(|bytes| read_address_bytes(bytes))
-        .filter(|&address| address != T::default())
-        .for_each(|address| {
-            addresses.insert(address);
-        });
-    println!("Found: {:?} addresses", addresses.len());
-
-    /* Index each address by its page offset */
-    let index = DashMap::<T, Vec<T>>::new();
-    let progress_bar = get_progress_bar("Indexing addresses", addresses.len());
-    let page_offset_mask = T::try_from(PAGE_OFFSET_MASK).unwrap();
-    addresses
-        .into_par_iter()
-        .take_any(max_addresses)
-        .progress_with(progress_bar)
-        .for_each(|address| {
-            let page_offset = address & page_offset_mask;
-            if let Some(mut v) = index.get_mut(&page_offset) {
-                v.push(address);
-            } else {
-                index.insert(page_offset, vec![address]);
+    /* Skip any vendor headers, so file offsets are relative to the payload */
+    if !args.keep_headers {
+        let (headers, payload) = header::skip(bytes);
+        for (offset, header) in headers {
+            log!("Found: {} header at 0x{:x}", header.name, offset);
+            if let Some(address) = header.load_address {
+                hints.push(Hint {
+                    source: format!("{} load address", header.name),
+                    address,
+                });
             }
-        });
-    index
-}
+        }
+        if payload != 0 {
+            log!("Skipping: 0x{:x} bytes of headers", payload);
+            bytes = &bytes[payload..];
+        }
+    }
 
-fn get_base_address<T: RBaseTraits<T, N>, const N: usize>(
-    args: &Args,
-    bytes: &[u8],
-    read_address_bytes: fn([u8; N]) -> T,
-) -> Option<T> {
-    let strings_index = get_strings_by_page_offset(
-        bytes,
-        args.min_string_length,
-        args.max_string_length,
-        args.max_strings,
-    );
-    let addresses_index =
-        get_addresses_by_page_offset(bytes, read_address_bytes, args.max_addresses);
-
-    /* Subtract the string offsets from the addresses to determine candidate base addresses.
-    Update a hashtable with the frequency of each candidate base address.*/
-    let progress_bar = get_progress_bar("Collecting candidate base addresses", strings_index.len());
-    let base_addresses = DashMap::<T, usize>::new();
-    strings_index
-        .into_par_iter()
-        .progress_with(progress_bar)
-        .for_each(|(string_page_offset, string_file_offsets)| {
-            if let Some(addresses) = addresses_index.get(&string_page_offset) {
-                for &string_file_offset in string_file_offsets.iter() {
-                    for &address in addresses
-                        .iter()
-                        .filter(|&&address| address >= string_file_offset)
-                    {
-                        *base_addresses
-                            .entry(address - string_file_offset)
-                            .or_insert(0) += 1;
-                    }
-                }
+    /* Decompress a compressed kernel, so file offsets are relative to the decompressed data */
+    let decompressed;
+    if let Some((compression, data)) = decompress::decompress(bytes) {
+        log!(
+            "Decompressed: 0x{:x} bytes of {compression} to 0x{:x} bytes",
+            bytes.len(),
+            data.len()
+        );
+        decompressed = data;
+        bytes = &decompressed;
+    }
+
+    /* Lay out a linked ELF or PE file as it is loaded */
+    let mapped;
+    if args.sections {
+        match sections::map(bytes) {
+            Some(sections) => {
+                log!(
+                    "Mapping: {} sections of {} file to 0x{:x} bytes, linked at 0x{:x}",
+                    sections.sections,
+                    sections.format,
+                    sections.bytes.len(),
+                    sections.base
+                );
+                args.apply_layout(sections.layout.0, sections.layout.1);
+                hints.push(Hint {
+                    source: format!("{} link address", sections.format),
+                    address: sections.base,
+                });
+                mapped = sections.bytes;
+                bytes = &mapped;
             }
-        });
+            None => log!("Mapping: not an ELF executable or PE file"),
+        }
+    }
 
-    let num_candidates = base_addresses.len();
-    println!("Found: {:?} candidate base addresses", num_candidates);
+    /* Analyse each region of a memory dump, or each segment of an Espressif image, on its own since
+    they are loaded at unrelated addresses (unless headers are to be kept) */
+    let mut segments = Vec::new();
+    if let Some(dump) = memdump::parse(bytes).filter(|_dump| !args.keep_headers) {
+        log!(
+            "Found: {} with {} memory regions",
+            dump.format,
+            dump.regions.len()
+        );
+        if let Some((size, endian)) = dump.layout {
+            args.apply_layout(size, endian);
+        }
+        segments.extend(dump.regions.into_iter().map(|region| Segment {
+            source: format!("{} region", dump.format),
+            offset: region.offset,
+            len: region.len,
+            address: region.address,
+        }));
+    } else if !args.keep_headers {
+        let partitions = esp::partitions(bytes);
+        for partition in partitions.iter() {
+            log!(
+                "ESP partition: {} ({}) at 0x{:x}-0x{:x}",
+                partition.label,
+                if partition.app { "app" } else { "data" },
+                partition.offset,
+                partition.offset + partition.size
+            );
+        }
+        for image in esp::find(bytes, &partitions) {
+            log!(
+                "Found: {} image at 0x{:x} with {} segments, entry 0x{:x}",
+                image.chip,
+                image.offset,
+                image.segments.len(),
+                image.entry
+            );
+            segments.extend(image.segments.into_iter().map(|segment| Segment {
+                source: format!("{} segment", image.chip),
+                offset: segment.offset,
+                len: segment.len,
+                address: segment.load_address as u64,
+            }));
+        }
+    }
 
-    /* Filter out any candidates which don't appear more than once */
-    let recurring: DashMap<T, usize> = base_addresses
-        .into_par_iter()
-        .filter(|&(_k, v)| v > 1)
-        .collect();
-    println!(
-        "Found: {:?} recurring candidate base addresses",
-        recurring.len()
-    );
+    let mut arch = args.refine;
+    if args.detect {
+        match arch::detect(bytes) {
+            Some(detected) => {
+                log!("Detected: {}", detected);
+                args.apply_detected(&detected);
+                arch = harness::detected_arch(detected.name);
+            }
+            None => log!("Detected: unknown architecture"),
+        }
+    }
+    log!("{:}", args);
 
-    /* Sort the recurring candidates by frequency */
-    let mut sorted: Vec<(T, usize)> = recurring.into_iter().collect();
-    sorted.sort_by(|(_a1, v1), (_a2, v2)| v2.cmp(v1));
-
-    /* Print the top 10 candidates */
-    for (idx, (base, frequency)) in sorted.iter().take(10).enumerate() {
-        let pct = 100.0 * (*frequency as f64) / (num_candidates as f64);
-        println!(
-            "{:2}: 0x{base:0width$x}: {frequency} ({pct:.2}%)",
-            idx + 1,
-            width = N * 2
+    let start = Instant::now();
+    let banners = banner::find(bytes);
+
+    let base = match segments.is_empty() {
+        true => find_base(&args, bytes, weigher, script, svd, &hints),
+        false => find_segment_bases(&args, bytes, weigher, script, svd, &hints, &segments),
+    }?;
+    let end = start.elapsed();
+    log!("Took: {:?}", end);
+
+    /* Optionally write configurations for dynamic analysis of the image, which is the file as is (or
+    a part of it) unless it had to be transformed */
+    let emit = args.emit_qiling.is_some() || args.emit_avatar2.is_some();
+    match &base {
+        Some(_found) if emit && !segments.is_empty() => {
+            log!("Not writing dynamic analysis configurations, the image has several segments")
+        }
+        Some(found) if emit => {
+            let target = harness::Target {
+                path: &name,
+                file_offset: (bytes.as_ptr() as usize)
+                    .checked_sub(file.as_ptr() as usize)
+                    .filter(|&file_offset| file_offset + bytes.len() <= file.len()),
+                arch,
+                endian: args.endian(),
+                base: found.base,
+                size: found.size,
+            };
+            if let Some(output) = &args.emit_qiling {
+                harness::qiling(output, &target).map_err(|e| format!("{output}: {e}"))?;
+                log!("Wrote: Qiling profile to {output}");
+            }
+            if let Some(output) = &args.emit_avatar2 {
+                harness::avatar2(output, &target).map_err(|e| format!("{output}: {e}"))?;
+                log!("Wrote: avatar2 script to {output}");
+            }
+        }
+        _ => {}
+    }
+    for banner in &banners {
+        log!(
+            "Banner: {} at 0x{:x}: {}",
+            banner.kind,
+            banner.offset,
+            banner.text
         );
     }
+    if let Some(database) = database {
+        for cpe in database.enrich(&banners) {
+            log!(
+                "CPE: {} (from {} banner at 0x{:x})",
+                cpe.id,
+                cpe.banner.kind,
+                cpe.banner.offset
+            );
+        }
+    }
 
-    /* Return the most frequent candidate base address */
-    let (base, _frequency) = sorted.first().cloned()?;
-    Some(base)
+    Ok(base)
+}
+
+/* The files to scan, with each directory replaced by the files in it and each archive by its members
+matching the glob. A directory or archive which can't be read is an error in place of its files. */
+fn expand(paths: &[String], glob: &str) -> Vec<Result<Input, String>> {
+    let mut files: Vec<Result<String, String>> = Vec::new();
+    for path in paths {
+        if Path::new(path).is_dir() {
+            let entries = match read_dir(path) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    files.push(Err(format!("{path}: {e}")));
+                    continue;
+                }
+            };
+            let mut entries: Vec<Result<String, String>> = entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .filter(|entry| entry.as_ref().map_or(true, |path| path.is_file()))
+                .map(|entry| match entry {
+                    Ok(path) => Ok(path.to_string_lossy().into_owned()),
+                    Err(e) => Err(format!("{path}: {e}")),
+                })
+                .collect();
+            entries.sort_unstable();
+            files.extend(entries);
+        } else {
+            files.push(Ok(path.clone()));
+        }
+    }
+    files
+        .into_iter()
+        .flat_map(|file| match file {
+            Ok(path) if archive::is_archive(&path) && !remote::is_url(&path) => {
+                match archive::members(&path, glob) {
+                    Ok(members) => members
+                        .into_iter()
+                        .map(|member| {
+                            Ok(Input {
+                                path: path.clone(),
+                                member: Some(member),
+                            })
+                        })
+                        .collect(),
+                    Err(e) => vec![Err(e)],
+                }
+            }
+            Ok(path) => vec![Ok(Input { path, member: None })],
+            Err(e) => vec![Err(e)],
+        })
+        .collect()
 }
 
-fn main() {
-    let args = Args::parse();
-    println!("{:}", args);
+fn main() -> ExitCode {
+    let args = Args::parse_valid();
+    format::init(&args.format);
+    if let Some(timeout) = args.timeout {
+        timeout::init(timeout);
+    }
+    if let Some(path) = &args.log_file {
+        if let Err(e) = logging::init(path) {
+            eprintln!("error: {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
 
-    let file = File::open(&args.filename).unwrap();
-    let map = unsafe { Mmap::map(&file).unwrap() };
-    let bytes = unsafe { from_raw_parts(map.as_ptr(), map.len()) };
+    /* With a single thread, rayon runs each parallel iterator in order */
+    let nodes = match args.numa {
+        true => numa::nodes(),
+        false => Vec::new(),
+    };
+    if nodes.len() > 1 {
+        log!("NUMA: binding threads to {} nodes", nodes.len());
+        numa::bind(ThreadPoolBuilder::new(), nodes, args.threads())
+            .build_global()
+            .unwrap();
+    } else if let Some(threads) = args.threads() {
+        ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .unwrap();
+    }
 
-    let start = Instant::now();
+    let weigher = StringWeigher::new(
+        args.weight_words,
+        args.wordlist.as_deref(),
+        args.weight_format_strings,
+        args.weight_paths,
+        args.string_include.as_deref(),
+        args.string_exclude.as_deref(),
+    );
+    let weigher = match weigher {
+        Ok(weigher) => weigher,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let script = match args.script.as_deref().map(Script::load).transpose() {
+        Ok(script) => script,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let database = match args.enrich.as_deref().map(Database::load).transpose() {
+        Ok(database) => database,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let svd = match args.svd.as_deref().map(Svd::load).transpose() {
+        Ok(svd) => svd,
+        Err(e) => {
+            eprintln!("error: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
 
-    match args.size() {
-        Size::Bits32 => {
-            if let Some(base) = get_base_address(
-                &args,
-                bytes,
-                match args.endian() {
-                    Endian::Little => u32::from_le_bytes,
-                    Endian::Big => u32::from_be_bytes,
-                },
-            ) {
-                println!("Found base: {:0x}", base);
-            } else {
-                println!("No base found");
-            }
+    if args.gpu {
+        match gpu::init() {
+            Some(gpu) => log!("GPU: {}", gpu.name),
+            None => log!("GPU: no adapter found, using the CPU"),
         }
-        Size::Bits64 => {
-            if let Some(base) = get_base_address(
-                &args,
-                bytes,
-                match args.endian() {
-                    Endian::Little => u64::from_le_bytes,
-                    Endian::Big => u64::from_be_bytes,
-                },
-            ) {
-                println!("Found base: {:x}", base);
-            } else {
-                println!("No base found");
+    }
+
+    let files = expand(&args.filenames, &args.member);
+    if files.is_empty() {
+        eprintln!("error: no files to scan");
+        return ExitCode::FAILURE;
+    }
+    if files.len() == 1 {
+        progress::set_sink(Box::new(IndicatifSink));
+        let found = files.into_iter().next().unwrap().and_then(|input| {
+            scan(
+                args,
+                &input,
+                &weigher,
+                script.as_ref(),
+                svd.as_ref(),
+                database.as_ref(),
+            )
+        });
+        return match found {
+            Ok(Some(found)) => {
+                log!("{}", found);
+                println!("{}", found.address());
+                match timeout::has_expired() {
+                    true => ExitCode::from(timeout::EXIT_CODE),
+                    false => ExitCode::SUCCESS,
+                }
             }
+            Ok(None) => {
+                eprintln!("No base found");
+                ExitCode::FAILURE
+            }
+            Err(e) => {
+                eprintln!("error: {e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    /* In batch mode, whole files are scanned in parallel, so only their overall progress is shown
+    and their diagnostic output (which would be interleaved) is discarded. A file which can't be
+    scanned is reported, without stopping the others. */
+    if let Some(option) = args.batch_conflict() {
+        eprintln!("error: {option} can't be used when scanning more than one file");
+        return ExitCode::FAILURE;
+    }
+    let progress_bar = Progress::new(IndicatifSink.stage("Scanning files", files.len()));
+    logging::discard();
+    let bases: Vec<Result<(String, Option<Found>), String>> = files
+        .par_iter()
+        .map(|input| {
+            let input = input.as_ref().map_err(Clone::clone)?;
+            let found = scan(
+                args.clone(),
+                input,
+                &weigher,
+                script.as_ref(),
+                svd.as_ref(),
+                database.as_ref(),
+            )?;
+            Ok((input.to_string(), found))
+        })
+        .progress_with(progress_bar)
+        .collect();
+    for base in bases.iter() {
+        match base {
+            Ok((input, Some(found))) => println!("{input}: {}", found.address()),
+            Ok((input, None)) => println!("{input}: No base found"),
+            Err(e) => eprintln!("error: {e}"),
         }
-    };
-    let end = start.elapsed();
-    println!("Took: {:?}", end);
+    }
+    let failed = bases.iter().any(|base| base.is_err());
+    match bases
+        .iter()
+        .any(|base| base.as_ref().is_ok_and(|(_input, found)| found.is_some()))
+    {
+        _ if timeout::has_expired() => ExitCode::from(timeout::EXIT_CODE),
+        _ if failed => ExitCode::FAILURE,
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    }
 }
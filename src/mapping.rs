@@ -0,0 +1,38 @@
+/* Hints to the OS as to how the mapped file will be read. Each stage scans the file from start to
+end (split between threads), so a cold file is read far faster with aggressive readahead than by
+faulting in each page as it is reached, particularly from a network filesystem. The hints are only
+advisory, so failures are ignored. */
+use memmap2::Mmap;
+
+#[cfg(unix)]
+pub fn advise(map: &Mmap) {
+    use memmap2::Advice;
+    if map.is_empty() {
+        return;
+    }
+    /* Widens the kernel's readahead, so each thread's chunk is prefetched ahead of its scan */
+    map.advise(Advice::Sequential).ok();
+    /* Starts reading the whole file in the background */
+    map.advise(Advice::WillNeed).ok();
+}
+
+#[cfg(windows)]
+pub fn advise(map: &Mmap) {
+    use windows_sys::Win32::System::{
+        Memory::{PrefetchVirtualMemory, WIN32_MEMORY_RANGE_ENTRY},
+        Threading::GetCurrentProcess,
+    };
+    if map.is_empty() {
+        return;
+    }
+    let range = WIN32_MEMORY_RANGE_ENTRY {
+        VirtualAddress: map.as_ptr() as *mut _,
+        NumberOfBytes: map.len(),
+    };
+    unsafe {
+        PrefetchVirtualMemory(GetCurrentProcess(), 1, &range, 0);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn advise(_map: &Mmap) {}
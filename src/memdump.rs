@@ -0,0 +1,182 @@
+/* Memory dumps (Windows minidumps and ELF core files) record the address at which each region of
+memory they hold was mapped, so rather than searching for a single base address, each region is
+analysed on its own and the result checked against the address it was mapped at. */
+use crate::{
+    args::{Endian, Size},
+    elf,
+};
+
+pub struct Region {
+    /* File offset of its data */
+    pub offset: usize,
+    pub len: usize,
+    pub address: u64,
+}
+
+pub struct MemoryDump {
+    pub format: &'static str,
+    /* The size and endianness of pointers, if the dump gives them */
+    pub layout: Option<(Size, Endian)>,
+    pub regions: Vec<Region>,
+}
+
+/* Minidumps are little-endian */
+fn read(bytes: &[u8], offset: usize, len: usize) -> Option<u64> {
+    let field = bytes.get(offset..offset.checked_add(len)?)?;
+    let mut word = [0u8; 8];
+    word[..len].copy_from_slice(field);
+    Some(u64::from_le_bytes(word))
+}
+
+/* The PT_LOAD segments of an ELF core file */
+fn elf_core(bytes: &[u8]) -> Option<MemoryDump> {
+    let elf = elf::parse(bytes).filter(|elf| elf.kind == elf::ET_CORE)?;
+    Some(MemoryDump {
+        format: "ELF core file",
+        layout: Some((elf.size, elf.endian)),
+        regions: elf.segments,
+    })
+}
+
+/* The memory ranges of a minidump, from either its memory list (of a mini dump) or its 64-bit
+memory list (of a full dump), with the pointer size given by the processor in its system info */
+fn minidump(bytes: &[u8]) -> Option<MemoryDump> {
+    const MAGIC: &[u8] = b"MDMP";
+    const MEMORY_LIST_STREAM: u64 = 5;
+    const SYSTEM_INFO_STREAM: u64 = 7;
+    const MEMORY64_LIST_STREAM: u64 = 9;
+    /* Processor architectures of the system info stream */
+    const X86: u64 = 0;
+    const ARM: u64 = 5;
+    const AMD64: u64 = 9;
+    const ARM64: u64 = 12;
+    if !bytes.starts_with(MAGIC) {
+        return None;
+    }
+    let read = |offset, len| read(bytes, offset, len);
+    let streams = read(8, 4)? as usize;
+    let directory = read(12, 4)? as usize;
+    let mut regions = Vec::new();
+    let mut layout = None;
+    for idx in 0..streams {
+        let entry = directory.checked_add(idx.checked_mul(12)?)?;
+        let rva = read(entry + 8, 4)? as usize;
+        match read(entry, 4)? {
+            MEMORY_LIST_STREAM => {
+                for range in 0..read(rva, 4)? as usize {
+                    let descriptor = rva + 4 + range * 16;
+                    regions.push(Region {
+                        address: read(descriptor, 8)?,
+                        len: read(descriptor + 8, 4)? as usize,
+                        offset: read(descriptor + 12, 4)? as usize,
+                    });
+                }
+            }
+            MEMORY64_LIST_STREAM => {
+                let mut offset = read(rva + 8, 8)? as usize;
+                for range in 0..read(rva, 8)? as usize {
+                    let descriptor = rva + 16 + range * 16;
+                    let len = read(descriptor + 8, 8)? as usize;
+                    regions.push(Region {
+                        address: read(descriptor, 8)?,
+                        len,
+                        offset,
+                    });
+                    offset = offset.checked_add(len)?;
+                }
+            }
+            SYSTEM_INFO_STREAM => {
+                layout = match read(rva, 2)? {
+                    X86 | ARM => Some((Size::Bits32, Endian::Little)),
+                    AMD64 | ARM64 => Some((Size::Bits64, Endian::Little)),
+                    _ => None,
+                };
+            }
+            _ => {}
+        }
+    }
+    regions.retain(|region| {
+        region.len != 0
+            && region
+                .offset
+                .checked_add(region.len)
+                .is_some_and(|end| end <= bytes.len())
+    });
+    Some(MemoryDump {
+        format: "minidump",
+        layout,
+        regions,
+    })
+}
+
+pub fn parse(bytes: &[u8]) -> Option<MemoryDump> {
+    elf_core(bytes).or_else(|| minidump(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* A minidump of an ARM64 process, with a 64-bit memory list of the given addresses and lengths */
+    fn minidump(ranges: &[(u64, u64)]) -> Vec<u8> {
+        const DIRECTORY: u32 = 0x20;
+        const SYSTEM_INFO: u32 = 0x40;
+        const MEMORY64_LIST: u32 = 0x80;
+        const DATA: u64 = 0x100;
+        let mut bytes = vec![0; DATA as usize];
+        bytes[..4].copy_from_slice(b"MDMP");
+        bytes[8..12].copy_from_slice(&2u32.to_le_bytes());
+        bytes[12..16].copy_from_slice(&DIRECTORY.to_le_bytes());
+        for (idx, (kind, rva)) in [(7u32, SYSTEM_INFO), (9, MEMORY64_LIST)]
+            .into_iter()
+            .enumerate()
+        {
+            let entry = DIRECTORY as usize + idx * 12;
+            bytes[entry..entry + 4].copy_from_slice(&kind.to_le_bytes());
+            bytes[entry + 8..entry + 12].copy_from_slice(&rva.to_le_bytes());
+        }
+        let system_info = SYSTEM_INFO as usize;
+        bytes[system_info..system_info + 2].copy_from_slice(&12u16.to_le_bytes());
+        let list = MEMORY64_LIST as usize;
+        bytes[list..list + 8].copy_from_slice(&(ranges.len() as u64).to_le_bytes());
+        bytes[list + 8..list + 16].copy_from_slice(&DATA.to_le_bytes());
+        for (idx, &(address, len)) in ranges.iter().enumerate() {
+            let descriptor = list + 16 + idx * 16;
+            bytes[descriptor..descriptor + 8].copy_from_slice(&address.to_le_bytes());
+            bytes[descriptor + 8..descriptor + 16].copy_from_slice(&len.to_le_bytes());
+        }
+        bytes.extend(vec![0xa5; 0x3000]);
+        bytes
+    }
+
+    #[test]
+    fn parses_the_memory64_list_of_a_minidump() {
+        let dump = parse(&minidump(&[
+            (0x7ff6_0000_0000, 0x1000),
+            (0x7ff6_0001_0000, 0x2000),
+        ]))
+        .unwrap();
+        assert_eq!(dump.format, "minidump");
+        assert!(matches!(dump.layout, Some((Size::Bits64, Endian::Little))));
+        let regions: Vec<(usize, usize, u64)> = dump
+            .regions
+            .iter()
+            .map(|region| (region.offset, region.len, region.address))
+            .collect();
+        assert_eq!(
+            regions,
+            vec![
+                (0x100, 0x1000, 0x7ff6_0000_0000),
+                (0x1100, 0x2000, 0x7ff6_0001_0000)
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_regions_beyond_the_end_of_the_file() {
+        let dump = parse(&minidump(&[(0x1_0000, 0x3000), (0x2_0000, 0x1000)])).unwrap();
+        assert_eq!(dump.regions.len(), 1);
+        assert_eq!(dump.regions[0].address, 0x1_0000);
+        assert!(parse(b"not a dump").is_none());
+    }
+}
@@ -0,0 +1,34 @@
+use crate::args::NandLayout;
+
+/* Raw NAND dumps interleave each page of data with its out-of-band (spare) bytes, which hold ECC
+and bad block markers. These shift every following offset, so they must be removed to recover the
+data as it appears in memory. A partial page at the end of the dump is kept, less any spare bytes. */
+pub fn strip_oob(bytes: &[u8], layout: NandLayout) -> Vec<u8> {
+    let mut data =
+        Vec::with_capacity(bytes.len() / (layout.page_size + layout.oob_size) * layout.page_size);
+    for page in bytes.chunks(layout.page_size + layout.oob_size) {
+        data.extend_from_slice(&page[..page.len().min(layout.page_size)]);
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_the_spare_bytes_of_each_page() {
+        let layout = NandLayout {
+            page_size: 4,
+            oob_size: 2,
+        };
+        let dump = [
+            1, 2, 3, 4, 0xff, 0xff, 5, 6, 7, 8, 0xff, 0xff, 9, 10, 11, 12, 0xff,
+        ];
+        assert_eq!(strip_oob(&dump, layout), (1..=12).collect::<Vec<u8>>());
+        assert_eq!(
+            strip_oob(&dump[..14], layout),
+            (1..=10).collect::<Vec<u8>>()
+        );
+    }
+}
@@ -0,0 +1,72 @@
+/* On machines with several NUMA nodes, memory is faster to access from the CPUs of the node which
+owns it. Threads migrating between nodes lose their locality to the pages they have touched (the
+parts of the mapped file they are scanning, and their own buckets of the indexes), so each thread
+of the pool is bound to the CPUs of a single node, with the threads divided evenly between them. */
+use rayon::ThreadPoolBuilder;
+
+/* The CPUs of each node which has any, or none if the topology isn't known */
+#[cfg(target_os = "linux")]
+pub fn nodes() -> Vec<Vec<usize>> {
+    let Ok(entries) = std::fs::read_dir("/sys/devices/system/node") else {
+        return Vec::new();
+    };
+    let mut nodes: Vec<(usize, Vec<usize>)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let node = entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("node")?
+                .parse()
+                .ok()?;
+            let cpulist = std::fs::read_to_string(entry.path().join("cpulist")).ok()?;
+            Some((node, parse_cpulist(cpulist.trim())?))
+        })
+        .filter(|(_node, cpus)| !cpus.is_empty())
+        .collect();
+    nodes.sort_unstable();
+    nodes.into_iter().map(|(_node, cpus)| cpus).collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn nodes() -> Vec<Vec<usize>> {
+    Vec::new()
+}
+
+/* A list of CPUs such as 0-3,8-11 */
+#[cfg(target_os = "linux")]
+fn parse_cpulist(cpulist: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for range in cpulist.split(',').filter(|range| !range.is_empty()) {
+        let (first, last) = range.split_once('-').unwrap_or((range, range));
+        cpus.extend(first.parse::<usize>().ok()?..=last.parse().ok()?);
+    }
+    Some(cpus)
+}
+
+#[cfg(target_os = "linux")]
+fn bind_thread(cpus: &[usize]) {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        libc::sched_setaffinity(0, size_of::<libc::cpu_set_t>(), &set);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bind_thread(_cpus: &[usize]) {}
+
+/* Bind the threads of the pool to the nodes, consecutive threads sharing a node. By default there
+is a thread for each CPU. */
+pub fn bind(
+    builder: ThreadPoolBuilder,
+    nodes: Vec<Vec<usize>>,
+    threads: Option<usize>,
+) -> ThreadPoolBuilder {
+    let threads = threads.unwrap_or_else(|| nodes.iter().map(|cpus| cpus.len()).sum());
+    builder
+        .num_threads(threads)
+        .start_handler(move |index| bind_thread(&nodes[index * nodes.len() / threads]))
+}
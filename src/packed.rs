@@ -0,0 +1,182 @@
+use crate::{PAGE_OFFSET_MASK, PAGE_SIZE};
+
+/* Number of bits of a value below its page number */
+const PAGE_BITS: u32 = PAGE_SIZE.trailing_zeros();
+
+/* Number of values between each entry in the skip list */
+const SKIP_INTERVAL: usize = 64;
+
+/* A value which can be stored as its difference from the previous value in the same bucket. Since
+the values in a bucket share their page offset, only the difference in page number is stored. */
+pub trait Pack: Copy + Default + Ord {
+    fn pack(self, prev: Self, bytes: &mut Vec<u8>);
+
+    fn unpack(prev: Self, bytes: &[u8], pos: &mut usize) -> Self;
+}
+
+/* LEB128, so small differences take a single byte */
+fn write_varint(mut value: u64, bytes: &mut Vec<u8>) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> u64 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+impl Pack for u32 {
+    fn pack(self, prev: u32, bytes: &mut Vec<u8>) {
+        let delta = self - prev;
+        debug_assert_eq!(delta as usize & PAGE_OFFSET_MASK, 0);
+        write_varint((delta >> PAGE_BITS) as u64, bytes);
+    }
+
+    fn unpack(prev: u32, bytes: &[u8], pos: &mut usize) -> u32 {
+        prev + ((read_varint(bytes, pos) as u32) << PAGE_BITS)
+    }
+}
+
+impl Pack for u64 {
+    fn pack(self, prev: u64, bytes: &mut Vec<u8>) {
+        let delta = self - prev;
+        debug_assert_eq!(delta as usize & PAGE_OFFSET_MASK, 0);
+        write_varint(delta >> PAGE_BITS, bytes);
+    }
+
+    fn unpack(prev: u64, bytes: &[u8], pos: &mut usize) -> u64 {
+        prev + (read_varint(bytes, pos) << PAGE_BITS)
+    }
+}
+
+/* Targets are stored along with their weight */
+impl<T: Pack> Pack for (T, usize) {
+    fn pack(self, prev: (T, usize), bytes: &mut Vec<u8>) {
+        self.0.pack(prev.0, bytes);
+        write_varint(self.1 as u64, bytes);
+    }
+
+    fn unpack(prev: (T, usize), bytes: &[u8], pos: &mut usize) -> (T, usize) {
+        let value = T::unpack(prev.0, bytes, pos);
+        (value, read_varint(bytes, pos) as usize)
+    }
+}
+
+/* A sorted list of values, stored as the differences between consecutive values. Every so often a
+value is stored in full in a skip list, so we can seek without decoding everything before it. */
+pub struct PackedVec<V> {
+    skips: Vec<(V, usize)>,
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl<V: Pack> PackedVec<V> {
+    /* The values must already be sorted */
+    pub fn from_sorted(values: Vec<V>) -> PackedVec<V> {
+        let mut skips = Vec::with_capacity(values.len().div_ceil(SKIP_INTERVAL));
+        let mut bytes = Vec::new();
+        for block in values.chunks(SKIP_INTERVAL) {
+            skips.push((block[0], bytes.len()));
+            for pair in block.windows(2) {
+                pair[1].pack(pair[0], &mut bytes);
+            }
+        }
+        bytes.shrink_to_fit();
+        PackedVec {
+            skips,
+            bytes,
+            len: values.len(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> Iter<'_, V> {
+        self.iter_from_block(0)
+    }
+
+    fn iter_from_block(&self, block: usize) -> Iter<'_, V> {
+        Iter {
+            packed: self,
+            block,
+            remaining: 0,
+            pos: 0,
+            prev: V::default(),
+        }
+    }
+
+    /* Iterate from the first value which is not less than the given one */
+    pub fn seek(&self, value: V) -> Iter<'_, V> {
+        let block = self
+            .skips
+            .partition_point(|&(first, _pos)| first < value)
+            .saturating_sub(1);
+        let mut iter = self.iter_from_block(block);
+        loop {
+            let mut next = iter.clone();
+            match next.next() {
+                Some(skipped) if skipped < value => iter = next,
+                _ => return iter,
+            }
+        }
+    }
+
+    pub fn contains(&self, value: V) -> bool {
+        self.seek(value).next() == Some(value)
+    }
+}
+
+#[derive(Clone)]
+pub struct Iter<'a, V> {
+    packed: &'a PackedVec<V>,
+    block: usize,
+    remaining: usize,
+    pos: usize,
+    prev: V,
+}
+
+impl<V: Pack> Iterator for Iter<'_, V> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        if self.remaining == 0 {
+            let &(first, pos) = self.packed.skips.get(self.block)?;
+            let start = self.block * SKIP_INTERVAL;
+            self.remaining = SKIP_INTERVAL.min(self.packed.len - start) - 1;
+            self.block += 1;
+            self.pos = pos;
+            self.prev = first;
+            return Some(first);
+        }
+        self.remaining -= 1;
+        self.prev = V::unpack(self.prev, &self.packed.bytes, &mut self.pos);
+        Some(self.prev)
+    }
+}
+
+impl<'a, V: Pack> IntoIterator for &'a PackedVec<V> {
+    type Item = V;
+    type IntoIter = Iter<'a, V>;
+
+    fn into_iter(self) -> Iter<'a, V> {
+        self.iter()
+    }
+}
@@ -0,0 +1,224 @@
+/* Named bundles of options suited to common kinds of firmware (e.g. the size and endianness of their
+addresses, the range those addresses lie in and the heuristics which work best). Options given on the
+command line take precedence over those of the profile, as do any they conflict with (e.g. --64 over
+a profile's --32). Profiles can also be defined in a config file (given by RBASE_CONFIG, or rbase/config
+in the user's config directory), in which each is a line with its name in brackets followed by lines
+of its options, and may redefine the built-in profiles:
+
+    # MIPS routers with RAM above 0x80000000
+    [my-router]
+    --32 --big
+    --require-prefix 0x80000000..0x84000000
+*/
+use {
+    clap::{Arg, Command},
+    std::{env, ffi::OsString, fs::read_to_string, path::PathBuf},
+};
+
+const BUILT_IN: &[(&str, &str)] = &[
+    /* MIPS routers, running from KSEG0 or KSEG1 */
+    (
+        "router-mips-be",
+        "--32 --big --align 4 --page-size 0x1000 --require-prefix 0x80000000..0xC0000000",
+    ),
+    /* STM32 microcontrollers, running from flash at 0x08000000, whose images are small enough that
+    the pointers heuristic helps */
+    (
+        "stm32",
+        "--32 --little --align 4 --page-size 0x1000 --require-prefix 0x08 --heuristics strings,pointers",
+    ),
+    /* AArch64 Linux kernels, linked in the top of the address space and loaded at a 2 MiB boundary
+    plus a text offset which is a multiple of 64 KiB */
+    (
+        "aarch64-kernel",
+        "--64 --little --align 8 --page-size 0x10000 --require-prefix 0xffff --heuristics strings,pointers",
+    ),
+];
+
+fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("RBASE_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(dir.join("rbase").join("config"))
+}
+
+/* The profiles defined in the config file, if there is one, as their names and options */
+fn user_profiles() -> Result<Vec<(String, Vec<String>)>, String> {
+    let Some(path) = config_path().filter(|path| path.exists()) else {
+        return Ok(Vec::new());
+    };
+    let source = read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let mut profiles: Vec<(String, Vec<String>)> = Vec::new();
+    for (idx, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            profiles.push((name.trim().to_string(), Vec::new()));
+            continue;
+        }
+        match profiles.last_mut() {
+            Some((_name, options)) => options.extend(line.split_whitespace().map(str::to_string)),
+            None => {
+                return Err(format!(
+                    "{}:{}: options before the name of a profile",
+                    path.display(),
+                    idx + 1
+                ))
+            }
+        }
+    }
+    Ok(profiles)
+}
+
+/* The options of the named profile, preferring one defined in the config file */
+fn options(name: &str) -> Result<Vec<String>, String> {
+    let user = user_profiles()?;
+    if let Some((_name, options)) = user.iter().find(|(other, _options)| other == name) {
+        return Ok(options.clone());
+    }
+    if let Some((_name, options)) = BUILT_IN.iter().find(|(other, _options)| *other == name) {
+        return Ok(options.split_whitespace().map(str::to_string).collect());
+    }
+    let names: Vec<&str> = BUILT_IN
+        .iter()
+        .map(|(name, _options)| *name)
+        .chain(user.iter().map(|(name, _options)| name.as_str()))
+        .collect();
+    Err(format!(
+        "unknown profile {name} (expected one of {})",
+        names.join(", ")
+    ))
+}
+
+/* The argument named by a long or short option, which may have its value given inline */
+fn find<'a>(command: &'a Command, option: &str) -> Option<&'a Arg> {
+    if let Some(long) = option.strip_prefix("--") {
+        let long = long.split('=').next().unwrap_or_default();
+        command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(long))
+    } else {
+        let short = option.strip_prefix('-')?.chars().next()?;
+        command
+            .get_arguments()
+            .find(|arg| arg.get_short() == Some(short))
+    }
+}
+
+/* The command line with the options of the named profile inserted before those given, leaving out
+any overridden by them */
+pub fn expand(
+    name: &str,
+    argv: &[OsString],
+    command: &mut Command,
+) -> Result<Vec<OsString>, String> {
+    command.build();
+    let given: Vec<&Arg> = argv
+        .iter()
+        .skip(1)
+        .map(|token| token.to_string_lossy())
+        .take_while(|token| token != "--")
+        .filter_map(|token| find(command, &token))
+        .collect();
+    let overridden: Vec<&Arg> = given
+        .iter()
+        .flat_map(|arg| {
+            command
+                .get_arg_conflicts_with(arg)
+                .into_iter()
+                .chain([*arg])
+        })
+        .collect();
+
+    let mut expanded: Vec<OsString> = argv.iter().take(1).cloned().collect();
+    let mut tokens = options(name)?.into_iter();
+    while let Some(token) = tokens.next() {
+        let arg = token
+            .starts_with("--")
+            .then(|| find(command, &token))
+            .flatten()
+            .ok_or_else(|| format!("profile {name}: unknown option {token}"))?;
+        let mut option = vec![token.clone()];
+        if arg.get_action().takes_values() && !token.contains('=') {
+            option.extend(tokens.next());
+        }
+        if !overridden
+            .iter()
+            .any(|other| other.get_id() == arg.get_id())
+        {
+            expanded.extend(option.into_iter().map(OsString::from));
+        }
+    }
+    expanded.extend(argv.iter().skip(1).cloned());
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::args::Args, clap::CommandFactory, std::fs::write};
+
+    fn expand_args(name: &str, args: &[&str]) -> Result<Vec<String>, String> {
+        let argv: Vec<OsString> = ["rbase"].iter().chain(args).map(OsString::from).collect();
+        Ok(expand(name, &argv, &mut Args::command())?
+            .into_iter()
+            .map(|token| token.to_string_lossy().into_owned())
+            .collect())
+    }
+
+    /* The config file is given by the environment, so it is only set by this one test */
+    #[test]
+    fn expands_profiles_leaving_out_overridden_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config");
+        write(
+            &config,
+            "# A router\n[my-router]\n--64 --big\n--require-prefix 0xffff\n",
+        )
+        .unwrap();
+        env::set_var("RBASE_CONFIG", &config);
+
+        assert_eq!(
+            expand_args("router-mips-be", &["--little", "--align=8", "image.bin"]).unwrap(),
+            [
+                "rbase",
+                "--32",
+                "--page-size",
+                "0x1000",
+                "--require-prefix",
+                "0x80000000..0xC0000000",
+                "--little",
+                "--align=8",
+                "image.bin"
+            ]
+        );
+        assert_eq!(
+            expand_args("my-router", &["--32", "image.bin"]).unwrap(),
+            [
+                "rbase",
+                "--big",
+                "--require-prefix",
+                "0xffff",
+                "--32",
+                "image.bin"
+            ]
+        );
+        let e = expand_args("missing", &["image.bin"]).unwrap_err();
+        assert!(e.starts_with("unknown profile missing"));
+        assert!(e.ends_with("aarch64-kernel, my-router)"));
+
+        write(&config, "--32\n[late]\n").unwrap();
+        assert!(expand_args("late", &["image.bin"])
+            .unwrap_err()
+            .ends_with("config:1: options before the name of a profile"));
+        env::remove_var("RBASE_CONFIG");
+    }
+}
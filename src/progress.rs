@@ -0,0 +1,96 @@
+use {
+    rayon::iter::ParallelIterator,
+    std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+/* Receives the progress of each stage of a scan (e.g. finding strings), so it can be shown however
+suits the caller. Without a sink, progress isn't reported at all. */
+pub trait ProgressSink: Send + Sync {
+    /* Start a stage with the given number of steps */
+    fn stage(&self, msg: &'static str, length: usize) -> Box<dyn Stage>;
+}
+
+pub trait Stage: Send + Sync {
+    fn inc(&self, delta: u64);
+
+    /* The stage is complete */
+    fn finish(&self);
+
+    /* The stage was abandoned part way through, so its progress needn't be kept */
+    fn clear(&self);
+}
+
+static SINK: OnceLock<Box<dyn ProgressSink>> = OnceLock::new();
+
+pub fn set_sink(sink: Box<dyn ProgressSink>) {
+    if SINK.set(sink).is_err() {
+        panic!("progress sink already set");
+    }
+}
+
+struct NoProgress;
+
+impl Stage for NoProgress {
+    fn inc(&self, _delta: u64) {}
+    fn finish(&self) {}
+    fn clear(&self) {}
+}
+
+struct Inner {
+    stage: Box<dyn Stage>,
+    finished: AtomicBool,
+}
+
+/* A stage is finished when the last handle to it is dropped, unless it was already finished */
+impl Drop for Inner {
+    fn drop(&mut self) {
+        if !self.finished.load(Ordering::Relaxed) {
+            self.stage.finish();
+        }
+    }
+}
+
+/* A handle to the progress of a stage, which may be shared between threads */
+#[derive(Clone)]
+pub struct Progress(Arc<Inner>);
+
+impl Progress {
+    pub fn new(stage: Box<dyn Stage>) -> Progress {
+        Progress(Arc::new(Inner {
+            stage,
+            finished: AtomicBool::new(false),
+        }))
+    }
+
+    pub fn inc(&self, delta: u64) {
+        self.0.stage.inc(delta);
+    }
+
+    pub fn finish_and_clear(&self) {
+        if !self.0.finished.swap(true, Ordering::Relaxed) {
+            self.0.stage.clear();
+        }
+    }
+}
+
+pub fn get_progress_bar(msg: &'static str, length: usize) -> Progress {
+    Progress::new(match SINK.get() {
+        Some(sink) => sink.stage(msg, length),
+        None => Box::new(NoProgress),
+    })
+}
+
+/* Report a step of progress for each item of a parallel iterator */
+pub trait ParallelProgressIterator: ParallelIterator {
+    fn progress_with(self, progress: Progress) -> impl ParallelIterator<Item = Self::Item> {
+        self.map(move |item| {
+            progress.inc(1);
+            item
+        })
+    }
+}
+
+impl<I: ParallelIterator> ParallelProgressIterator for I {}
@@ -0,0 +1,421 @@
+use {
+    crate::{Endian, RBaseTraits},
+    clap::{builder::PossibleValue, ValueEnum},
+    std::fmt::{Display, Formatter, Result},
+};
+
+#[cfg(feature = "disasm")]
+use {
+    crate::progress::{get_progress_bar, ParallelProgressIterator},
+    capstone::{
+        arch::{
+            arm::ArmOperandType, arm64::Arm64OperandType, mips::MipsOperand, ppc::PpcOperand,
+            x86::X86OperandType, ArchOperand,
+        },
+        Arch, Capstone, CsResult, Mode,
+    },
+    rayon::{
+        iter::{
+            IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator,
+            ParallelIterator,
+        },
+        slice::{ParallelSlice, ParallelSliceMut},
+    },
+};
+
+/* Number of bytes disassembled by each worker */
+#[cfg(feature = "disasm")]
+const CHUNK_SIZE: usize = 0x10000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DisasmArch {
+    Arm,
+    Thumb,
+    Arm64,
+    Mips,
+    Mips64,
+    Ppc,
+    Ppc64,
+    X86,
+    #[value(name = "x86-64")]
+    X86_64,
+}
+
+impl Display for DisasmArch {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let value = self
+            .to_possible_value()
+            .unwrap_or(PossibleValue::new("unknown"));
+        write!(f, "{}", value.get_name())
+    }
+}
+
+pub struct Refined<T> {
+    pub base: T,
+    pub frequency: usize,
+    pub references: usize,
+}
+
+#[cfg(feature = "disasm")]
+impl DisasmArch {
+    fn capstone(&self, endian: Endian) -> CsResult<Capstone> {
+        let endian = match endian {
+            Endian::Little => capstone::Endian::Little,
+            Endian::Big => capstone::Endian::Big,
+        };
+        let (arch, mode, endian) = match self {
+            DisasmArch::Arm => (Arch::ARM, Mode::Arm, Some(endian)),
+            DisasmArch::Thumb => (Arch::ARM, Mode::Thumb, Some(endian)),
+            /* AArch64 instructions are always little-endian, even when its data isn't */
+            DisasmArch::Arm64 => (Arch::ARM64, Mode::Arm, None),
+            DisasmArch::Mips => (Arch::MIPS, Mode::Mips32, Some(endian)),
+            DisasmArch::Mips64 => (Arch::MIPS, Mode::Mips64, Some(endian)),
+            DisasmArch::Ppc => (Arch::PPC, Mode::Mode32, Some(endian)),
+            DisasmArch::Ppc64 => (Arch::PPC, Mode::Mode64, Some(endian)),
+            DisasmArch::X86 => (Arch::X86, Mode::Mode32, None),
+            DisasmArch::X86_64 => (Arch::X86, Mode::Mode64, None),
+        };
+        let mut capstone = Capstone::new_raw(arch, mode, std::iter::empty(), endian)?;
+        capstone.set_detail(true)?;
+        capstone.set_skipdata(true)?;
+        Ok(capstone)
+    }
+
+    /* Calls relative to the address of the instruction, whose targets are the starts of functions
+    wherever the image is loaded */
+    fn is_relative_call(&self, mnemonic: &str) -> bool {
+        match self {
+            DisasmArch::Arm | DisasmArch::Thumb => mnemonic == "bl" || mnemonic == "blx",
+            DisasmArch::Arm64 | DisasmArch::Ppc | DisasmArch::Ppc64 => mnemonic == "bl",
+            DisasmArch::Mips | DisasmArch::Mips64 => mnemonic == "bal",
+            DisasmArch::X86 | DisasmArch::X86_64 => mnemonic == "call",
+        }
+    }
+
+    /* Jumps and calls to an absolute address, whose targets depend on where the image is loaded */
+    fn is_absolute_jump(&self, mnemonic: &str) -> bool {
+        match self {
+            DisasmArch::Mips | DisasmArch::Mips64 => mnemonic == "j" || mnemonic == "jal",
+            DisasmArch::Ppc | DisasmArch::Ppc64 => mnemonic == "ba" || mnemonic == "bla",
+            _ => false,
+        }
+    }
+
+    /* Whether the bytes look like the start of a function */
+    fn is_prologue(&self, bytes: &[u8], endian: Endian) -> bool {
+        let Some(&word) = bytes.first_chunk::<4>() else {
+            return false;
+        };
+        let word = match (self, endian) {
+            (DisasmArch::Arm64, _) | (_, Endian::Little) => u32::from_le_bytes(word),
+            (_, Endian::Big) => u32::from_be_bytes(word),
+        };
+        match self {
+            DisasmArch::Arm => {
+                word & 0xffff4000 == 0xe92d4000 /* push {..., lr} */
+                    || word & 0xfffff000 == 0xe24dd000 /* sub sp, sp, #N */
+                    || word == 0xe1a0c00d /* mov ip, sp */
+            }
+            DisasmArch::Thumb => {
+                let half = match endian {
+                    Endian::Little => word & 0xffff,
+                    Endian::Big => word >> 16,
+                };
+                half & 0xff00 == 0xb500 /* push {..., lr} */ || half == 0xe92d /* push.w */
+            }
+            DisasmArch::Arm64 => {
+                word == 0xd503233f /* paciasp */
+                    || word == 0xd503245f /* bti c */
+                    || word & 0xffc07fff == 0xa9807bfd /* stp x29, x30, [sp, #-N]! */
+                    || word & 0xff0003ff == 0xd10003ff /* sub sp, sp, #N */
+            }
+            DisasmArch::Mips | DisasmArch::Mips64 => {
+                word & 0xffff8000 == 0x27bd8000 /* addiu sp, sp, -N */
+                    || word & 0xffff8000 == 0x67bd8000 /* daddiu sp, sp, -N */
+                    || word & 0xffff0000 == 0x3c1c0000 /* lui gp, N */
+            }
+            DisasmArch::Ppc | DisasmArch::Ppc64 => {
+                word & 0xffff8000 == 0x94218000 /* stwu r1, -N(r1) */
+                    || word & 0xffff8003 == 0xf8218001 /* stdu r1, -N(r1) */
+                    || word == 0x7c0802a6 /* mflr r0 */
+            }
+            DisasmArch::X86 | DisasmArch::X86_64 => {
+                bytes[0] == 0x55 /* push ebp/rbp */
+                    || bytes.starts_with(&[0xf3, 0x0f, 0x1e]) /* endbr32/endbr64 */
+                    || bytes.starts_with(&[0x48, 0x83, 0xec]) /* sub rsp, N */
+            }
+        }
+    }
+
+    fn alignment(&self) -> u64 {
+        match self {
+            DisasmArch::Thumb => 2,
+            DisasmArch::X86 | DisasmArch::X86_64 => 1,
+            _ => 4,
+        }
+    }
+
+    /* Alignment of the functions found by their prologues, compilers pad x86 functions to 16 bytes */
+    fn function_alignment(&self) -> usize {
+        match self {
+            DisasmArch::X86 | DisasmArch::X86_64 => 16,
+            arch => arch.alignment() as usize,
+        }
+    }
+
+    /* Size of a pointer in the image's data */
+    fn pointer_size(&self) -> usize {
+        match self.address_mask() {
+            u64::MAX => 8,
+            _ => 4,
+        }
+    }
+
+    /* Bits set in a pointer to a function besides its address (Thumb functions are called with the
+    low bit set) */
+    fn pointer_tag(&self) -> u64 {
+        match self {
+            DisasmArch::Thumb => 1,
+            _ => 0,
+        }
+    }
+
+    fn address_mask(&self) -> u64 {
+        match self {
+            DisasmArch::Arm64 | DisasmArch::Mips64 | DisasmArch::Ppc64 | DisasmArch::X86_64 => {
+                u64::MAX
+            }
+            _ => u32::MAX as u64,
+        }
+    }
+}
+
+/* Immediate operand of a branch or call instruction, i.e. its target */
+#[cfg(feature = "disasm")]
+fn immediate(operand: &ArchOperand) -> Option<u64> {
+    match operand {
+        ArchOperand::ArmOperand(op) => match op.op_type {
+            ArmOperandType::Imm(imm) => Some(imm as u32 as u64),
+            _ => None,
+        },
+        ArchOperand::Arm64Operand(op) => match op.op_type {
+            Arm64OperandType::Imm(imm) => Some(imm as u64),
+            _ => None,
+        },
+        ArchOperand::MipsOperand(MipsOperand::Imm(imm)) => Some(*imm as u64),
+        ArchOperand::PpcOperand(PpcOperand::Imm(imm)) => Some(*imm as u64),
+        ArchOperand::X86Operand(op) => match op.op_type {
+            X86OperandType::Imm(imm) => Some(imm as u64),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/* A jump or call to an absolute address */
+#[cfg(feature = "disasm")]
+enum Jump {
+    /* The target replaces the low 28 bits of the address of the delay slot (MIPS j and jal), so it
+    depends on the offset of the instruction */
+    Region { offset: u64, low: u64 },
+    /* The target is the immediate itself (PowerPC ba and bla) */
+    Absolute(u64),
+}
+
+#[cfg(feature = "disasm")]
+impl Jump {
+    fn target(&self, base: u64) -> u64 {
+        match *self {
+            Jump::Region { offset, low } => {
+                (base.wrapping_add(offset).wrapping_add(4) & !0x0FFF_FFFF) | low
+            }
+            Jump::Absolute(target) => target,
+        }
+    }
+}
+
+/* What the image tells about its code regardless of where it is loaded. Relative branches land in
+the same place at every base address, so only references by absolute address can tell the
+candidates apart. */
+#[cfg(feature = "disasm")]
+struct Code {
+    /* Sorted offsets of the likely starts of functions: the targets of relative calls, and aligned
+    prologues */
+    starts: Vec<u64>,
+    /* Sorted values of the aligned words of the image which land inside it at some candidate, i.e.
+    its literal pools and data pointers */
+    pointers: Vec<u64>,
+    jumps: Vec<Jump>,
+}
+
+#[cfg(feature = "disasm")]
+impl Code {
+    fn new(bytes: &[u8], extent: (u64, u64), arch: DisasmArch, endian: Endian) -> Code {
+        let len = bytes.len() as u64;
+
+        /* Disassemble the image at offset zero, so the targets of relative calls are offsets */
+        let progress_bar =
+            get_progress_bar("Disassembling image", bytes.len().div_ceil(CHUNK_SIZE));
+        let (calls, jumps): (Vec<Vec<u64>>, Vec<Vec<Jump>>) = bytes
+            .par_chunks(CHUNK_SIZE)
+            .enumerate()
+            .progress_with(progress_bar)
+            .map(|(idx, chunk)| {
+                let mut calls = Vec::new();
+                let mut jumps = Vec::new();
+                let Ok(capstone) = arch.capstone(endian) else {
+                    return (calls, jumps);
+                };
+                let Ok(instructions) = capstone.disasm_all(chunk, (idx * CHUNK_SIZE) as u64) else {
+                    return (calls, jumps);
+                };
+                for instruction in instructions.iter() {
+                    let mnemonic = instruction.mnemonic().unwrap_or_default();
+                    let is_call = arch.is_relative_call(mnemonic);
+                    let is_jump = arch.is_absolute_jump(mnemonic);
+                    if !is_call && !is_jump {
+                        continue;
+                    }
+                    let Ok(detail) = capstone.insn_detail(&instruction) else {
+                        continue;
+                    };
+                    for target in detail.arch_detail().operands().iter().filter_map(immediate) {
+                        let target = target & arch.address_mask();
+                        match arch {
+                            _ if is_call => calls.push(target),
+                            DisasmArch::Mips | DisasmArch::Mips64 => jumps.push(Jump::Region {
+                                offset: instruction.address(),
+                                low: target & 0x0FFF_FFFF,
+                            }),
+                            _ => jumps.push(Jump::Absolute(target)),
+                        }
+                    }
+                }
+                (calls, jumps)
+            })
+            .unzip();
+
+        let function_alignment = arch.function_alignment();
+        let mut starts: Vec<u64> = calls
+            .into_iter()
+            .flatten()
+            .filter(|&target| target < len && target.is_multiple_of(arch.alignment()))
+            .chain(
+                (0..bytes.len().div_ceil(function_alignment))
+                    .into_par_iter()
+                    .map(|idx| idx * function_alignment)
+                    .filter(|&offset| arch.is_prologue(&bytes[offset..], endian))
+                    .map(|offset| offset as u64)
+                    .collect::<Vec<u64>>(),
+            )
+            .collect();
+        starts.par_sort_unstable();
+        starts.dedup();
+
+        let (lowest, highest) = extent;
+        let mut pointers: Vec<u64> = bytes
+            .par_chunks_exact(arch.pointer_size())
+            .map(|word| {
+                let mut value = [0u8; 8];
+                match endian {
+                    Endian::Little => {
+                        value[..word.len()].copy_from_slice(word);
+                        u64::from_le_bytes(value)
+                    }
+                    Endian::Big => {
+                        value[8 - word.len()..].copy_from_slice(word);
+                        u64::from_be_bytes(value)
+                    }
+                }
+            })
+            .filter(|&pointer| pointer >= lowest && pointer < highest)
+            .collect();
+        pointers.par_sort_unstable();
+
+        Code {
+            starts,
+            pointers,
+            jumps: jumps.into_iter().flatten().collect(),
+        }
+    }
+
+    /* Count the pointers and absolute jumps which land on the start of a function when the image
+    is loaded at the given base address */
+    fn references(&self, base: u64, arch: DisasmArch) -> usize {
+        let pointers: usize = self
+            .starts
+            .par_iter()
+            .filter_map(|&start| base.checked_add(start))
+            .map(|address| {
+                let address = address | arch.pointer_tag();
+                let first = self.pointers.partition_point(|&pointer| pointer < address);
+                self.pointers[first..].partition_point(|&pointer| pointer == address)
+            })
+            .sum();
+        let jumps = self
+            .jumps
+            .par_iter()
+            .filter_map(|jump| (jump.target(base) & arch.address_mask()).checked_sub(base))
+            .filter(|offset| self.starts.binary_search(offset).is_ok())
+            .count();
+        pointers + jumps
+    }
+}
+
+/* Re-rank the candidates by the number of pointers and absolute jumps which land on a function at
+each */
+#[cfg(feature = "disasm")]
+pub fn refine<T: RBaseTraits<T, N>, const N: usize>(
+    bytes: &[u8],
+    candidates: &[(T, usize)],
+    arch: DisasmArch,
+    endian: Endian,
+) -> Vec<Refined<T>> {
+    let bases = candidates.iter().map(|&(base, _frequency)| base.into());
+    let extent = (
+        bases.clone().min().unwrap_or_default(),
+        bases
+            .max()
+            .unwrap_or_default()
+            .saturating_add(bytes.len() as u64),
+    );
+    let code = Code::new(bytes, extent, arch, endian);
+    let progress_bar = get_progress_bar("Checking candidates", candidates.len());
+    let mut refined: Vec<Refined<T>> = candidates
+        .iter()
+        .map(|&(base, frequency)| {
+            let references = code.references(base.into(), arch);
+            progress_bar.inc(1);
+            Refined {
+                base,
+                frequency,
+                references,
+            }
+        })
+        .collect();
+    drop(progress_bar);
+    refined.sort_by(|a, b| {
+        b.references
+            .cmp(&a.references)
+            .then(b.frequency.cmp(&a.frequency))
+    });
+    refined
+}
+
+/* Arguments are rejected if they ask for refinement without the disasm feature */
+#[cfg(not(feature = "disasm"))]
+pub fn refine<T: RBaseTraits<T, N>, const N: usize>(
+    _bytes: &[u8],
+    candidates: &[(T, usize)],
+    _arch: DisasmArch,
+    _endian: Endian,
+) -> Vec<Refined<T>> {
+    candidates
+        .iter()
+        .map(|&(base, frequency)| Refined {
+            base,
+            frequency,
+            references: 0,
+        })
+        .collect()
+}
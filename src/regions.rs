@@ -0,0 +1,154 @@
+use {
+    crate::{
+        progress::{get_progress_bar, ParallelProgressIterator},
+        RBaseTraits,
+    },
+    rayon::{iter::ParallelIterator, slice::ParallelSlice},
+};
+
+/* Size of the windows into which the image is divided when looking for regions */
+const WINDOW_SIZE: usize = 0x1000;
+
+/* A window is dense with pointers if at least this fraction of its words point into the same
+16 MiB region (or 64 KiB region for 24-bit images) */
+const POINTER_DENSITY: f64 = 0.25;
+
+/* A window is dense with strings if at least this fraction of its bytes could form part of one */
+const STRING_DENSITY: f64 = 0.75;
+
+/* The windows of the image which look like a particular kind of region */
+#[derive(Clone)]
+pub struct Regions {
+    windows: Vec<bool>,
+}
+
+impl Regions {
+    pub fn contains(&self, file_offset: usize) -> bool {
+        self.windows
+            .get(file_offset / WINDOW_SIZE)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /* The windows in both regions */
+    pub fn intersect(&self, other: &Regions) -> Regions {
+        Regions {
+            windows: self
+                .windows
+                .iter()
+                .zip(other.windows.iter())
+                .map(|(&a, &b)| a && b)
+                .collect(),
+        }
+    }
+
+    /* Number of windows in the region, and in the whole image */
+    pub fn count(&self) -> (usize, usize) {
+        let count = self.windows.iter().filter(|&&window| window).count();
+        (count, self.windows.len())
+    }
+}
+
+/* Find the windows which look like data, because many of their words are pointers into a consistent
+range of addresses. Code and compressed data rarely look like this. Words below the minimum are more
+likely to be small integers than pointers, so don't count. */
+pub fn pointer_dense<T: RBaseTraits<T, N>, const N: usize>(
+    bytes: &[u8],
+    read_address_bytes: fn([u8; N]) -> T,
+    pointer_mask: Option<T>,
+    pointer_min: u64,
+) -> Regions {
+    let shift = T::BITS.min(u32::BITS) - u8::BITS;
+    let progress_bar = get_progress_bar("Finding data regions", bytes.len().div_ceil(WINDOW_SIZE));
+    let windows = bytes
+        .par_chunks(WINDOW_SIZE)
+        .progress_with(progress_bar)
+        .map(|window| {
+            let mut ranges: Vec<u64> = window
+                .chunks_exact(N)
+                .map(|word| read_address_bytes(word.try_into().unwrap()))
+                .map(|pointer| match pointer_mask {
+                    Some(mask) => pointer & mask,
+                    None => pointer,
+                })
+                .map(|pointer| pointer.into())
+                .filter(|&pointer| pointer >= pointer_min)
+                .map(|pointer| pointer >> shift)
+                .collect();
+            ranges.sort_unstable();
+            let consistent = ranges
+                .chunk_by(|a, b| a == b)
+                .map(|range| range.len())
+                .max()
+                .unwrap_or(0);
+            consistent as f64 >= POINTER_DENSITY * (window.len() / N) as f64
+        })
+        .collect();
+    Regions { windows }
+}
+
+/* Find the windows which look like string tables (e.g. .rodata), because most of their bytes are
+characters which could form part of a string */
+pub fn string_dense(bytes: &[u8], charset_bytes: [bool; 256]) -> Regions {
+    let progress_bar =
+        get_progress_bar("Finding string regions", bytes.len().div_ceil(WINDOW_SIZE));
+    let windows = bytes
+        .par_chunks(WINDOW_SIZE)
+        .progress_with(progress_bar)
+        .map(|window| {
+            let characters = window
+                .iter()
+                .filter(|&&b| charset_bytes[b as usize])
+                .count();
+            characters as f64 >= STRING_DENSITY * window.len() as f64
+        })
+        .collect();
+    Regions { windows }
+}
+
+/* Find the windows which aren't padding, i.e. entirely 0x00 or 0xff (as in erased flash) */
+pub fn unpadded(bytes: &[u8]) -> Regions {
+    let windows = bytes
+        .par_chunks(WINDOW_SIZE)
+        .map(|window| {
+            let fill = window[0];
+            !matches!(fill, 0x00 | 0xff) || window.iter().any(|&b| b != fill)
+        })
+        .collect();
+    Regions { windows }
+}
+
+/* Size of each of the evenly spaced slices of an image searched when only searching part of it */
+const SLICE_SIZE: usize = 0x100000;
+
+/* Find the windows in evenly spaced slices of the image, amounting to about the given number of
+bytes, so a quick look at part of a huge image sees a little of all of it */
+pub fn slices(len: usize, max_bytes: usize) -> Regions {
+    let count = max_bytes.div_ceil(SLICE_SIZE).max(1);
+    inside(
+        len,
+        (0..count).map(|idx| {
+            let start = (idx * (len / count)) / WINDOW_SIZE * WINDOW_SIZE;
+            (start, start + SLICE_SIZE.min(max_bytes))
+        }),
+    )
+}
+
+/* Find the windows which don't overlap any of the given ranges of file offsets */
+pub fn outside(len: usize, ranges: impl Iterator<Item = (usize, usize)>) -> Regions {
+    let mut windows = vec![true; len.div_ceil(WINDOW_SIZE)];
+    for (start, end) in ranges.map(|(start, end)| (start, end.min(len))) {
+        if start < end {
+            windows[start / WINDOW_SIZE..end.div_ceil(WINDOW_SIZE)].fill(false);
+        }
+    }
+    Regions { windows }
+}
+
+/* Find the windows which overlap any of the given ranges of file offsets */
+pub fn inside(len: usize, ranges: impl Iterator<Item = (usize, usize)>) -> Regions {
+    let outside = outside(len, ranges);
+    Regions {
+        windows: outside.windows.iter().map(|&window| !window).collect(),
+    }
+}
@@ -0,0 +1,147 @@
+/* Images can be given as http(s):// or s3:// URLs (e.g. of an artifact repository), rather than having
+to be staged by hand. Each is streamed to a temporary file as it downloads, which is deleted once it
+has been scanned. An s3:// URL is fetched from the virtual-hosted endpoint of its bucket, in the
+region given by AWS_REGION (or AWS_DEFAULT_REGION), so only public objects can be fetched that way;
+private ones can be fetched by a presigned https:// URL instead. */
+use std::path::Path;
+
+#[cfg(feature = "remote")]
+use {
+    crate::progress::get_progress_bar,
+    std::{
+        env,
+        io::{Read, Write},
+    },
+    tempfile::{Builder, NamedTempFile},
+};
+
+/* Region of S3 buckets, if not given by the environment */
+#[cfg(feature = "remote")]
+const DEFAULT_S3_REGION: &str = "us-east-1";
+
+/* Size of each chunk written to the temporary file as the download progresses */
+#[cfg(feature = "remote")]
+const CHUNK_SIZE: usize = 0x10000;
+
+/* Longest part of the URL kept in the name of the temporary file */
+#[cfg(feature = "remote")]
+const MAX_NAME_LENGTH: usize = 64;
+
+pub fn is_url(path: &str) -> bool {
+    ["http://", "https://", "s3://"]
+        .iter()
+        .any(|scheme| path.starts_with(scheme))
+}
+
+/* The HTTPS URL of an object given by an s3:// URL, or the URL itself if it isn't one */
+#[cfg(feature = "remote")]
+fn http_url(url: &str) -> Result<String, String> {
+    let Some(object) = url.strip_prefix("s3://") else {
+        return Ok(url.to_string());
+    };
+    let Some((bucket, key)) = object
+        .split_once('/')
+        .filter(|(_bucket, key)| !key.is_empty())
+    else {
+        return Err(format!("{url}: expected s3://BUCKET/KEY"));
+    };
+    let region = env::var("AWS_REGION")
+        .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+        .unwrap_or(DEFAULT_S3_REGION.to_string());
+    Ok(format!("https://{bucket}.s3.{region}.amazonaws.com/{key}"))
+}
+
+/* The last segment of the URL's path, as part of a file name. The query and fragment are dropped (a
+presigned URL's signature would make the name far too long, and '?' isn't allowed on Windows), any
+character other than an ASCII letter, digit, '.', '-' or '_' is replaced and the rest truncated. */
+#[cfg(feature = "remote")]
+fn file_name(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    path.rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || ".-_".contains(c) {
+            true => c,
+            false => '_',
+        })
+        .take(MAX_NAME_LENGTH)
+        .collect()
+}
+
+/* A downloaded image, deleted when dropped */
+#[cfg(feature = "remote")]
+pub struct Download(NamedTempFile);
+
+#[cfg(feature = "remote")]
+impl Download {
+    pub fn path(&self) -> &Path {
+        self.0.path()
+    }
+}
+
+/* Download the image at the URL to a temporary file, named after it so it is recognisable */
+#[cfg(feature = "remote")]
+pub fn fetch(url: &str) -> Result<Download, String> {
+    let response = ureq::get(&http_url(url)?)
+        .call()
+        .map_err(|e| e.to_string())?;
+    let length: usize = response
+        .header("Content-Length")
+        .and_then(|length| length.parse().ok())
+        .unwrap_or(0);
+    let mut file = Builder::new()
+        .prefix("rbase-")
+        .suffix(&format!("-{}", file_name(url)))
+        .tempfile()
+        .map_err(|e| format!("{url}: {e}"))?;
+    let progress_bar = get_progress_bar("Downloading", length);
+    let mut reader = response.into_reader();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        let read = reader.read(&mut chunk).map_err(|e| format!("{url}: {e}"))?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&chunk[..read])
+            .map_err(|e| format!("{url}: {e}"))?;
+        progress_bar.inc(read as u64);
+    }
+    file.flush().map_err(|e| format!("{url}: {e}"))?;
+    Ok(Download(file))
+}
+
+#[cfg(not(feature = "remote"))]
+pub struct Download(std::convert::Infallible);
+
+#[cfg(not(feature = "remote"))]
+impl Download {
+    pub fn path(&self) -> &Path {
+        match self.0 {}
+    }
+}
+
+#[cfg(not(feature = "remote"))]
+pub fn fetch(url: &str) -> Result<Download, String> {
+    Err(format!("{url}: rbase was built without the remote feature"))
+}
+
+#[cfg(all(test, feature = "remote"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_name_drops_the_query_of_a_presigned_url() {
+        let url = format!(
+            "https://bucket.s3.amazonaws.com/fw/router%201.bin?X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Credential={}&X-Amz-Signature={}#top",
+            "A".repeat(200),
+            "0".repeat(64)
+        );
+        assert_eq!(file_name(&url), "router_201.bin");
+        assert_eq!(file_name("s3://bucket/fw.bin"), "fw.bin");
+        assert_eq!(
+            file_name(&format!("http://host/{}", "x".repeat(300))).len(),
+            64
+        );
+    }
+}
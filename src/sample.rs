@@ -0,0 +1,138 @@
+use {
+    crate::logging::log,
+    clap::{builder::PossibleValue, ValueEnum},
+    rand::{rngs::StdRng, seq::index, SeedableRng},
+    rayon::{
+        iter::{IntoParallelIterator, ParallelIterator},
+        slice::ParallelSliceMut,
+    },
+    std::{
+        cmp::Reverse,
+        fmt::{Display, Formatter, Result},
+    },
+};
+
+/* A page offset is an outlier if it has more than this many times the median number of values, and
+at least the minimum */
+const OUTLIER_FACTOR: usize = 10;
+const MIN_OUTLIER: usize = 32;
+
+/* Limit on the number of outlying page offsets listed */
+const MAX_REPORTED_OUTLIERS: usize = 8;
+
+/* How to choose which targets or addresses to keep when there are more than the maximum. They are
+first sorted (by file offset or address), so the choice doesn't depend on the order in which they
+happened to be found. */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SampleStrategy {
+    /* The lowest */
+    Head,
+    /* Evenly spaced throughout */
+    Stride,
+    /* Chosen at random (with the given seed), each equally likely */
+    Reservoir,
+}
+
+impl Display for SampleStrategy {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        let value = self
+            .to_possible_value()
+            .unwrap_or(PossibleValue::new("unknown"));
+        write!(f, "{}", value.get_name())
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Sampling {
+    pub strategy: SampleStrategy,
+    pub seed: u64,
+    pub max_per_page: Option<usize>,
+    pub keep_outlier_pages: bool,
+}
+
+/* The number of values above which a page offset is an outlier, being many times the median of those
+with any (and at least the minimum, since most page offsets of a small image have just one or two) */
+fn outlier_limit<V>(buckets: &[Vec<V>]) -> Option<usize> {
+    let mut lens: Vec<usize> = buckets
+        .iter()
+        .map(|bucket| bucket.len())
+        .filter(|&len| len != 0)
+        .collect();
+    lens.sort_unstable();
+    let median = *lens.get(lens.len() / 2)?;
+    Some(median.saturating_mul(OUTLIER_FACTOR).max(MIN_OUTLIER))
+}
+
+impl Sampling {
+    /* At most the given number of the values */
+    pub fn sample<V: Ord + Send>(&self, mut values: Vec<V>, max: usize) -> Vec<V> {
+        let len = values.len();
+        if len <= max {
+            return values;
+        }
+        values.par_sort_unstable();
+        let keep: Vec<bool> = match self.strategy {
+            SampleStrategy::Head => (0..len).map(|idx| idx < max).collect(),
+            SampleStrategy::Stride => {
+                let mut keep = vec![false; len];
+                for k in 0..max {
+                    keep[k * len / max] = true;
+                }
+                keep
+            }
+            SampleStrategy::Reservoir => {
+                let mut rng = StdRng::seed_from_u64(self.seed);
+                let mut keep = vec![false; len];
+                for idx in index::sample(&mut rng, len, max) {
+                    keep[idx] = true;
+                }
+                keep
+            }
+        };
+        values
+            .into_iter()
+            .zip(keep)
+            .filter_map(|(value, keep)| keep.then_some(value))
+            .collect()
+    }
+
+    /* Sample the values of each page offset down to the maximum per page, if any, so a page offset
+    with a large number of values (e.g. from a table of fixed size records) can't dominate. Unless
+    kept, page offsets with far more values than is typical are also sampled down to the limit
+    above which they are outliers. */
+    pub fn cap_per_page<V: Ord + Send>(&self, buckets: Vec<Vec<V>>, name: &str) -> Vec<Vec<V>> {
+        let outlier = match self.keep_outlier_pages {
+            true => None,
+            false => outlier_limit(&buckets),
+        };
+        let Some(max) = self.max_per_page.into_iter().chain(outlier).min() else {
+            return buckets;
+        };
+        if let Some(outlier) = outlier {
+            let mut outliers: Vec<(usize, usize)> = buckets
+                .iter()
+                .enumerate()
+                .filter(|(_page_offset, bucket)| bucket.len() > outlier)
+                .map(|(page_offset, bucket)| (page_offset, bucket.len()))
+                .collect();
+            outliers.sort_unstable_by_key(|&(_page_offset, len)| Reverse(len));
+            for &(page_offset, len) in outliers.iter().take(MAX_REPORTED_OUTLIERS) {
+                log!("Suppressing: {len} {name} at page offset 0x{page_offset:03x}, down to {outlier}");
+            }
+            if outliers.len() > MAX_REPORTED_OUTLIERS {
+                log!(
+                    "Suppressing: {name} at {} more page offsets",
+                    outliers.len() - MAX_REPORTED_OUTLIERS
+                );
+            }
+        }
+        let capped = buckets.iter().filter(|bucket| bucket.len() > max).count();
+        if self.max_per_page == Some(max) && capped != 0 {
+            log!("Sampling: {name} at {capped} page offsets down to {max} each");
+        }
+        buckets
+            .into_par_iter()
+            .map(|bucket| self.sample(bucket, max))
+            .collect()
+    }
+}
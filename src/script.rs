@@ -0,0 +1,115 @@
+/* User scripts can define any of the following functions to express target-specific knowledge:
+
+    fn score_string(s) { ... }              weight of a string (0 to ignore it)
+    fn accept_pointer(p) { ... }            whether a pointer should be considered
+    fn score_candidate(base, stats) { ... } score of a candidate base address, where stats is a
+                                            map of its frequency and whether it is a known base
+
+Addresses are passed as 64-bit signed integers, so may be negative on 64-bit images. */
+
+#[cfg(feature = "script")]
+use {
+    rhai::{CallFnOptions, Dynamic, Engine, FuncArgs, Map, Scope, AST},
+    std::fs::read_to_string,
+};
+
+pub fn parse_script(s: &str) -> std::result::Result<String, String> {
+    if cfg!(feature = "script") {
+        Ok(s.to_string())
+    } else {
+        Err("rbase was built without the script feature".to_string())
+    }
+}
+
+#[cfg(feature = "script")]
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    score_string: bool,
+    accept_pointer: bool,
+    score_candidate: bool,
+}
+
+#[cfg(feature = "script")]
+impl Script {
+    pub fn load(path: &str) -> std::result::Result<Script, String> {
+        let source = read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        let engine = Engine::new();
+        let ast = engine.compile(source).map_err(|e| format!("{path}: {e}"))?;
+        let defines = |name: &str| ast.iter_functions().any(|f| f.name == name);
+        Ok(Script {
+            score_string: defines("score_string"),
+            accept_pointer: defines("accept_pointer"),
+            score_candidate: defines("score_candidate"),
+            engine,
+            ast,
+        })
+    }
+
+    fn call(&self, name: &str, args: impl FuncArgs) -> Dynamic {
+        self.engine
+            .call_fn_with_options(
+                CallFnOptions::new().eval_ast(false),
+                &mut Scope::new(),
+                &self.ast,
+                name,
+                args,
+            )
+            .unwrap_or_else(|e| panic!("{name}: {e}"))
+    }
+
+    /* Weight of the string, if the script scores strings */
+    pub fn score_string(&self, text: &[u8]) -> Option<usize> {
+        if !self.score_string {
+            return None;
+        }
+        let text = String::from_utf8_lossy(text).into_owned();
+        let score = self.call("score_string", (text,)).as_int().unwrap();
+        Some(score.max(0) as usize)
+    }
+
+    pub fn accept_pointer(&self, pointer: u64) -> bool {
+        !self.accept_pointer
+            || self
+                .call("accept_pointer", (pointer as i64,))
+                .as_bool()
+                .unwrap()
+    }
+
+    /* Score of the candidate base address, if the script scores candidates */
+    pub fn score_candidate(&self, base: u64, frequency: usize, known: bool) -> Option<usize> {
+        if !self.score_candidate {
+            return None;
+        }
+        let mut stats = Map::new();
+        stats.insert("frequency".into(), Dynamic::from(frequency as i64));
+        stats.insert("known".into(), Dynamic::from(known));
+        let score = self
+            .call("score_candidate", (base as i64, stats))
+            .as_int()
+            .unwrap();
+        Some(score.max(0) as usize)
+    }
+}
+
+#[cfg(not(feature = "script"))]
+pub struct Script;
+
+#[cfg(not(feature = "script"))]
+impl Script {
+    pub fn load(_path: &str) -> std::result::Result<Script, String> {
+        unreachable!("rbase was built without the script feature")
+    }
+
+    pub fn score_string(&self, _text: &[u8]) -> Option<usize> {
+        unreachable!("rbase was built without the script feature")
+    }
+
+    pub fn accept_pointer(&self, _pointer: u64) -> bool {
+        unreachable!("rbase was built without the script feature")
+    }
+
+    pub fn score_candidate(&self, _base: u64, _frequency: usize, _known: bool) -> Option<usize> {
+        unreachable!("rbase was built without the script feature")
+    }
+}
@@ -0,0 +1,190 @@
+/* The file offsets of a linked ELF or PE file aren't the offsets at which its contents are loaded
+(sections are aligned differently in memory, and uninitialised data takes no space in the file), so
+correlating them with pointers finds no meaningful base. Instead the file is laid out as it would be
+loaded, so offsets are relative virtual addresses and the base found is the address it is linked at
+(or zero, for position independent code). */
+use crate::{
+    args::{Endian, Size},
+    elf, PAGE_OFFSET_MASK,
+};
+
+/* Limit on the size of the loaded image, in case the headers are corrupt */
+const MAX_MAPPED_SIZE: u64 = 1 << 30;
+
+pub struct Mapped {
+    pub format: &'static str,
+    /* The address at which the file is linked, to which the offsets are relative */
+    pub base: u64,
+    pub layout: (Size, Endian),
+    pub sections: usize,
+    pub bytes: Vec<u8>,
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    let half = bytes.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes(half.try_into().unwrap()))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let word = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(word.try_into().unwrap()))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    let word = bytes.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(word.try_into().unwrap()))
+}
+
+/* Copy the ranges of the file (as file offset, length and offset in memory) into an image of the
+given size */
+fn load(bytes: &[u8], size: u64, ranges: &[(usize, usize, usize)]) -> Option<Vec<u8>> {
+    if size > MAX_MAPPED_SIZE {
+        return None;
+    }
+    let size = size as usize;
+    let mut image = vec![0; size];
+    /* Anything beyond the end of the image isn't loaded */
+    for &(offset, len, address) in ranges
+        .iter()
+        .filter(|&&(_offset, len, address)| len != 0 && address < size)
+    {
+        let len = len.min(size - address);
+        image[address..address + len].copy_from_slice(bytes.get(offset..offset + len)?);
+    }
+    Some(image)
+}
+
+/* The PT_LOAD segments of an executable or shared object, relative to the page containing the
+lowest */
+fn elf(bytes: &[u8]) -> Option<Mapped> {
+    let elf = elf::parse(bytes).filter(|elf| matches!(elf.kind, elf::ET_EXEC | elf::ET_DYN))?;
+    let base =
+        elf.segments.iter().map(|segment| segment.address).min()? & !(PAGE_OFFSET_MASK as u64);
+    let end = elf
+        .segments
+        .iter()
+        .map(|segment| segment.address.checked_add(segment.len as u64))
+        .max()??;
+    let ranges: Vec<(usize, usize, usize)> = elf
+        .segments
+        .iter()
+        .map(|segment| {
+            (
+                segment.offset,
+                segment.len,
+                (segment.address - base) as usize,
+            )
+        })
+        .collect();
+    Some(Mapped {
+        format: "ELF",
+        base,
+        layout: (elf.size, elf.endian),
+        sections: ranges.len(),
+        bytes: load(bytes, end - base, &ranges)?,
+    })
+}
+
+/* The headers and sections of a PE file, relative to its image base */
+fn pe(bytes: &[u8]) -> Option<Mapped> {
+    const PE32: u16 = 0x10b;
+    const PE32_PLUS: u16 = 0x20b;
+    const SECTION_HEADER_SIZE: usize = 40;
+    if !bytes.starts_with(b"MZ") {
+        return None;
+    }
+    let pe = read_u32(bytes, 0x3c)? as usize;
+    if bytes.get(pe..pe.checked_add(4)?)? != b"PE\0\0" {
+        return None;
+    }
+    let coff = pe + 4;
+    let sections = read_u16(bytes, coff + 2)? as usize;
+    let optional = coff + 20;
+    let section_headers = optional + read_u16(bytes, coff + 16)? as usize;
+    let (size, image_base) = match read_u16(bytes, optional)? {
+        PE32 => (Size::Bits32, read_u32(bytes, optional + 28)? as u64),
+        PE32_PLUS => (Size::Bits64, read_u64(bytes, optional + 24)?),
+        _ => return None,
+    };
+    let image_size = read_u32(bytes, optional + 56)? as u64;
+    let headers_size = read_u32(bytes, optional + 60)? as usize;
+    let mut ranges = vec![(0, headers_size.min(bytes.len()), 0)];
+    for idx in 0..sections {
+        let header = section_headers + idx * SECTION_HEADER_SIZE;
+        let virtual_size = read_u32(bytes, header + 8)? as usize;
+        let address = read_u32(bytes, header + 12)? as usize;
+        let raw_size = read_u32(bytes, header + 16)? as usize;
+        let offset = read_u32(bytes, header + 20)? as usize;
+        let len = match virtual_size {
+            0 => raw_size,
+            _ => raw_size.min(virtual_size),
+        };
+        ranges.push((offset, len.min(bytes.len().saturating_sub(offset)), address));
+    }
+    Some(Mapped {
+        format: "PE",
+        base: image_base,
+        layout: (size, Endian::Little),
+        sections,
+        bytes: load(bytes, image_size, &ranges)?,
+    })
+}
+
+pub fn map(bytes: &[u8]) -> Option<Mapped> {
+    elf(bytes).or_else(|| pe(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* A PE32 file linked at the image base, with a single section of the given virtual size whose
+    raw data is the given bytes */
+    fn pe32(image_base: u32, virtual_size: u32, data: &[u8]) -> Vec<u8> {
+        const PE: usize = 0x80;
+        const OPTIONAL: usize = PE + 24;
+        const OPTIONAL_SIZE: u16 = 0xe0;
+        const SECTION_HEADER: usize = OPTIONAL + OPTIONAL_SIZE as usize;
+        let mut bytes = vec![0; 0x200];
+        let mut put = |offset: usize, field: &[u8]| {
+            bytes[offset..offset + field.len()].copy_from_slice(field)
+        };
+        put(0, b"MZ");
+        put(0x3c, &(PE as u32).to_le_bytes());
+        put(PE, b"PE\0\0");
+        put(PE + 6, &1u16.to_le_bytes());
+        put(PE + 20, &OPTIONAL_SIZE.to_le_bytes());
+        put(OPTIONAL, &0x10bu16.to_le_bytes());
+        put(OPTIONAL + 28, &image_base.to_le_bytes());
+        put(OPTIONAL + 56, &0x2000u32.to_le_bytes());
+        put(OPTIONAL + 60, &0x200u32.to_le_bytes());
+        put(SECTION_HEADER, b".text\0\0\0");
+        put(SECTION_HEADER + 8, &virtual_size.to_le_bytes());
+        put(SECTION_HEADER + 12, &0x1000u32.to_le_bytes());
+        put(SECTION_HEADER + 16, &(data.len() as u32).to_le_bytes());
+        put(SECTION_HEADER + 20, &0x200u32.to_le_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn lays_out_a_pe_file_as_loaded() {
+        let mapped = map(&pe32(0x40_0000, 0x100, &[0xa5; 0x200])).unwrap();
+        assert_eq!(mapped.format, "PE");
+        assert_eq!(mapped.base, 0x40_0000);
+        assert!(matches!(mapped.layout, (Size::Bits32, Endian::Little)));
+        assert_eq!(mapped.sections, 1);
+        assert_eq!(mapped.bytes.len(), 0x2000);
+        assert_eq!(&mapped.bytes[..2], b"MZ");
+        /* Only the virtual size of the section is loaded */
+        assert!(mapped.bytes[0x1000..0x1100].iter().all(|&b| b == 0xa5));
+        assert!(mapped.bytes[0x1100..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn ignores_files_which_arent_pe_or_elf() {
+        let mut bytes = pe32(0x40_0000, 0, &[0xa5; 0x200]);
+        bytes[0x80] = b'X';
+        assert!(map(&bytes).is_none());
+    }
+}
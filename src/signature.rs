@@ -0,0 +1,223 @@
+/* Multi-component images often contain compressed data, filesystems and certificates alongside the
+code. Their bytes are effectively random, so they only contribute noise to the strings and pointers
+found. They are recognised by their magic numbers, then validated using their headers. */
+use regex::bytes::Regex;
+
+const MAGICS: &str = concat!(
+    r"(?s-u)",
+    r"\x1f\x8b\x08",
+    r"|\x5d\x00\x00",
+    r"|hsqs|sqsh|UBI#",
+    r"|\x85\x19|\x19\x85",
+    r"|\x45\x3d\xcd\x28|\x28\xcd\x3d\x45",
+    r"|-----BEGIN CERTIFICATE-----",
+    r"|\x30\x82..\x30\x82",
+);
+
+const PEM_END: &[u8] = b"-----END CERTIFICATE-----";
+
+/* Limits on the size of a single JFFS2 node, and of an LZMA dictionary */
+const MAX_JFFS2_NODE: u32 = 0x100000;
+const MIN_LZMA_DICTIONARY: u32 = 1 << 16;
+const MAX_LZMA_DICTIONARY: u32 = 1 << 26;
+
+/* A region of the image holding something other than code or data */
+pub struct Signature {
+    pub name: &'static str,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Signature {
+    pub fn is_filesystem(&self) -> bool {
+        is_filesystem(self.name)
+    }
+}
+
+fn is_filesystem(name: &str) -> bool {
+    matches!(name, "SquashFS" | "JFFS2" | "UBI" | "cramfs")
+}
+
+fn read_u16(bytes: &[u8], offset: usize, big: bool) -> Option<u16> {
+    let word = bytes
+        .get(offset..offset.checked_add(2)?)?
+        .try_into()
+        .unwrap();
+    Some(if big {
+        u16::from_be_bytes(word)
+    } else {
+        u16::from_le_bytes(word)
+    })
+}
+
+fn read_u32(bytes: &[u8], offset: usize, big: bool) -> Option<u32> {
+    let word = bytes
+        .get(offset..offset.checked_add(4)?)?
+        .try_into()
+        .unwrap();
+    Some(if big {
+        u32::from_be_bytes(word)
+    } else {
+        u32::from_le_bytes(word)
+    })
+}
+
+fn read_u64(bytes: &[u8], offset: usize, big: bool) -> Option<u64> {
+    let word = bytes
+        .get(offset..offset.checked_add(8)?)?
+        .try_into()
+        .unwrap();
+    Some(if big {
+        u64::from_be_bytes(word)
+    } else {
+        u64::from_le_bytes(word)
+    })
+}
+
+/* The gzip header has reserved flags, and a limited set of values for the extra flags and OS. The
+length of the compressed data isn't recorded, so its size is unknown. */
+fn gzip(bytes: &[u8]) -> Option<Option<usize>> {
+    let (flags, xfl, os) = (*bytes.get(3)?, *bytes.get(8)?, *bytes.get(9)?);
+    (flags & 0xe0 == 0 && matches!(xfl, 0 | 2 | 4) && (os <= 13 || os == 255)).then_some(None)
+}
+
+/* The LZMA header gives the dictionary size (usually a power of two) and the uncompressed size (or
+all ones if unknown), but not the compressed size */
+fn lzma(bytes: &[u8]) -> Option<Option<usize>> {
+    let dictionary = read_u32(bytes, 1, false)?;
+    let size = read_u64(bytes, 5, false)?;
+    (dictionary.is_power_of_two()
+        && (MIN_LZMA_DICTIONARY..=MAX_LZMA_DICTIONARY).contains(&dictionary)
+        && (size == u64::MAX || size < 1 << 32))
+        .then_some(None)
+}
+
+/* The SquashFS superblock gives the version and the number of bytes used */
+fn squashfs(bytes: &[u8], big: bool) -> Option<Option<usize>> {
+    let major = read_u16(bytes, 28, big)?;
+    let bytes_used = read_u64(bytes, 40, big)?;
+    (major == 4 && bytes_used <= bytes.len() as u64).then_some(Some(bytes_used as usize))
+}
+
+/* JFFS2 has no superblock, just a sequence of nodes, each giving its type and length. Erase blocks
+may be padded with 0xff, and nodes are 4-byte aligned. */
+fn jffs2(bytes: &[u8], big: bool) -> Option<Option<usize>> {
+    let mut pos = 0;
+    let mut nodes = 0;
+    while read_u16(bytes, pos, big) == Some(0x1985) {
+        let node_type = read_u16(bytes, pos + 2, big)?;
+        let len = read_u32(bytes, pos + 4, big)?;
+        let known_type = matches!(
+            node_type,
+            0xe001 | 0xe002 | 0x2003 | 0x2004 | 0xe006 | 0xe008 | 0xe009
+        );
+        if !known_type || !(12..=MAX_JFFS2_NODE).contains(&len) {
+            break;
+        }
+        nodes += 1;
+        pos += (len as usize + 3) & !3;
+        while bytes.get(pos..pos + 4) == Some(&[0xff; 4]) {
+            pos += 4;
+        }
+    }
+    (nodes >= 2).then_some(Some(pos.min(bytes.len())))
+}
+
+/* UBI has an erase counter header at the start of each physical eraseblock, so its extent is the run
+of eraseblocks, the size of which is the distance between the first two headers */
+fn ubi(bytes: &[u8]) -> Option<Option<usize>> {
+    const MAGIC: &[u8] = b"UBI#";
+    const MIN_ERASEBLOCK: usize = 0x4000;
+    const MAX_ERASEBLOCK: usize = 0x100000;
+    let eraseblock = (MIN_ERASEBLOCK..=MAX_ERASEBLOCK)
+        .step_by(MIN_ERASEBLOCK)
+        .find(|&offset| bytes.get(offset..offset + 4) == Some(MAGIC))?;
+    if !eraseblock.is_power_of_two() {
+        return None;
+    }
+    let mut end = 0;
+    while bytes.get(end..end + 4) == Some(MAGIC) {
+        end += eraseblock;
+    }
+    Some(Some(end.min(bytes.len())))
+}
+
+/* The cramfs superblock gives its size, followed by a signature */
+fn cramfs(bytes: &[u8], big: bool) -> Option<Option<usize>> {
+    let size = read_u32(bytes, 4, big)? as usize;
+    (bytes.get(16..32)? == b"Compressed ROMFS" && size <= bytes.len()).then_some(Some(size))
+}
+
+fn pem(bytes: &[u8]) -> Option<Option<usize>> {
+    let end = bytes
+        .windows(PEM_END.len())
+        .take(0x4000)
+        .position(|window| window == PEM_END)?;
+    Some(Some(end + PEM_END.len()))
+}
+
+/* A DER certificate is a SEQUENCE containing a SEQUENCE (the signed part) */
+fn der(bytes: &[u8]) -> Option<Option<usize>> {
+    let outer = read_u16(bytes, 2, true)? as usize;
+    let inner = read_u16(bytes, 6, true)? as usize;
+    ((0x100..0x2000).contains(&outer) && inner + 4 <= outer && outer + 4 <= bytes.len())
+        .then_some(Some(outer + 4))
+}
+
+/* The type and size (if known) of the region starting at each magic number */
+fn classify(bytes: &[u8]) -> Option<(&'static str, Option<usize>)> {
+    match bytes.get(..4)? {
+        [0x1f, 0x8b, 0x08, _] => Some(("gzip", gzip(bytes)?)),
+        [0x5d, 0x00, 0x00, _] => Some(("LZMA", lzma(bytes)?)),
+        b"hsqs" => Some(("SquashFS", squashfs(bytes, false)?)),
+        b"sqsh" => Some(("SquashFS", squashfs(bytes, true)?)),
+        b"UBI#" => Some(("UBI", ubi(bytes)?)),
+        [0x85, 0x19, ..] => Some(("JFFS2", jffs2(bytes, false)?)),
+        [0x19, 0x85, ..] => Some(("JFFS2", jffs2(bytes, true)?)),
+        [0x45, 0x3d, 0xcd, 0x28] => Some(("cramfs", cramfs(bytes, false)?)),
+        [0x28, 0xcd, 0x3d, 0x45] => Some(("cramfs", cramfs(bytes, true)?)),
+        b"----" => Some(("certificate", pem(bytes)?)),
+        [0x30, 0x82, ..] => Some(("certificate", der(bytes)?)),
+        _ => None,
+    }
+}
+
+/* Find the regions of the image recognised by their signatures. Compressed data is assumed to extend
+to the next filesystem, or the end of the image, since its size is unknown and anything else found
+within it is likely to be a coincidence. */
+pub fn scan(bytes: &[u8]) -> Vec<Signature> {
+    let regex = Regex::new(MAGICS).unwrap();
+    let mut found: Vec<(&'static str, usize, Option<usize>)> = Vec::new();
+    let mut next = 0;
+    let mut compressed = false;
+    for m in regex.find_iter(bytes) {
+        if m.start() < next {
+            continue;
+        }
+        let Some((name, size)) = classify(&bytes[m.start()..]) else {
+            continue;
+        };
+        if compressed && !is_filesystem(name) {
+            continue;
+        }
+        found.push((name, m.start(), size));
+        next = m.start() + size.unwrap_or(0);
+        compressed = size.is_none();
+    }
+
+    let starts: Vec<usize> = found
+        .iter()
+        .map(|&(_name, start, _size)| start)
+        .skip(1)
+        .chain([bytes.len()])
+        .collect();
+    found
+        .into_iter()
+        .zip(starts)
+        .map(|((name, start, size), next)| Signature {
+            name,
+            start,
+            end: size.map_or(next, |size| start + size),
+        })
+        .collect()
+}
@@ -0,0 +1,53 @@
+/* Number of rows (each with its own hash function) and counters in each row of a sketch */
+const DEPTH: usize = 4;
+const WIDTH_BITS: u32 = 18;
+const WIDTH: usize = 1 << WIDTH_BITS;
+
+/* Odd multipliers for the hash function of each row */
+const MULTIPLIERS: [u64; DEPTH] = [
+    0x9e3779b97f4a7c15,
+    0xc2b2ae3d27d4eb4f,
+    0x165667b19e3779f9,
+    0xd6e8feb86659fd93,
+];
+
+/* A count-min sketch, which estimates the frequency of each value using a fixed amount of memory.
+Estimates are never too low, but may be too high when other values collide with it in every row. */
+pub struct CountMinSketch {
+    counters: Vec<usize>,
+}
+
+impl Default for CountMinSketch {
+    fn default() -> Self {
+        CountMinSketch {
+            counters: vec![0; DEPTH * WIDTH],
+        }
+    }
+}
+
+impl CountMinSketch {
+    fn index(row: usize, value: u64) -> usize {
+        let hash = (value ^ (value >> 29)).wrapping_mul(MULTIPLIERS[row]);
+        row * WIDTH + (hash >> (u64::BITS - WIDTH_BITS)) as usize
+    }
+
+    pub fn add(&mut self, value: u64, count: usize) {
+        for row in 0..DEPTH {
+            self.counters[Self::index(row, value)] += count;
+        }
+    }
+
+    pub fn estimate(&self, value: u64) -> usize {
+        (0..DEPTH)
+            .map(|row| self.counters[Self::index(row, value)])
+            .min()
+            .unwrap_or(0)
+    }
+
+    pub fn merge(mut self, other: CountMinSketch) -> CountMinSketch {
+        for (counter, other) in self.counters.iter_mut().zip(other.counters) {
+            *counter += other;
+        }
+        self
+    }
+}
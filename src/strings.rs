@@ -0,0 +1,376 @@
+use {
+    crate::{
+        collect_by_key,
+        gpu::{Gpu, BATCH_BYTES},
+        logging::log,
+        progress::{get_progress_bar, ParallelProgressIterator},
+        regions::Regions,
+        script::Script,
+        weight::StringWeigher,
+        RBaseTraits,
+    },
+    rayon::iter::{IntoParallelIterator, ParallelIterator},
+    regex::bytes::Regex,
+    regex_syntax::{
+        hir::{Class, Hir, HirKind},
+        ParserBuilder,
+    },
+    rustc_hash::FxHashMap,
+};
+
+/* Strings matched by the default regex, being runs of characters in the charset terminated by a NUL,
+which can also be found without a regex */
+#[cfg_attr(not(feature = "gpu"), allow(dead_code))]
+pub struct Terminated {
+    pub charset: [bool; 256],
+    pub min: usize,
+    pub max: usize,
+    pub allow_mid_string: bool,
+}
+
+/* Smallest chunk searched for strings, so small inputs aren't split into lots of tiny chunks */
+const MIN_CHUNK_SIZE: usize = 0x10000;
+
+/* A chunk of the input searched for strings, starting at the given file offset. Only the strings
+starting in its first len bytes belong to it, any bytes after those overlapping the next chunk. The
+byte before it tells whether a string at its start is mid-string. */
+pub struct Chunk<'a> {
+    pub offset: usize,
+    pub bytes: &'a [u8],
+    pub len: usize,
+    pub prev: Option<u8>,
+}
+
+/* Whether the regex can match a NUL byte */
+fn matches_nul(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => false,
+        HirKind::Literal(literal) => literal.0.contains(&0),
+        HirKind::Class(Class::Unicode(class)) => class
+            .ranges()
+            .first()
+            .is_some_and(|range| range.start() == '\0'),
+        HirKind::Class(Class::Bytes(class)) => class
+            .ranges()
+            .first()
+            .is_some_and(|range| range.start() == 0),
+        HirKind::Repetition(repetition) => {
+            repetition.max != Some(0) && matches_nul(&repetition.sub)
+        }
+        HirKind::Capture(capture) => matches_nul(&capture.sub),
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => hirs.iter().any(matches_nul),
+    }
+}
+
+/* Whether the regex can match a NUL byte followed by more of the match, i.e. one which doesn't end
+the match (as the NUL terminating a string does) */
+fn matches_inner_nul(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) | HirKind::Class(_) => false,
+        HirKind::Literal(literal) => literal.0.iter().rev().skip(1).any(|&b| b == 0),
+        HirKind::Repetition(repetition) => match repetition.max {
+            Some(0) => false,
+            Some(1) => matches_inner_nul(&repetition.sub),
+            _ => matches_nul(&repetition.sub),
+        },
+        HirKind::Capture(capture) => matches_inner_nul(&capture.sub),
+        HirKind::Alternation(hirs) => hirs.iter().any(matches_inner_nul),
+        HirKind::Concat(hirs) => hirs.iter().enumerate().any(|(idx, hir)| {
+            matches_inner_nul(hir)
+                || (matches_nul(hir)
+                    && hirs[idx + 1..]
+                        .iter()
+                        .any(|next| next.properties().maximum_len() != Some(0)))
+        }),
+    }
+}
+
+/* How far each chunk must overlap the next for searching the chunks separately to find the same
+strings as searching the whole input. None if the regex can only match a NUL at the end of a match
+(e.g. that terminating a string), since the chunks can then be split just after one instead. A
+regex which can match a NUL elsewhere and has no longest match can only be searched in one chunk. */
+pub fn chunk_overlap(regex: &str) -> Option<usize> {
+    let hir = ParserBuilder::new().utf8(false).build().parse(regex).ok()?;
+    matches_inner_nul(&hir).then(|| hir.properties().maximum_len().unwrap_or(usize::MAX))
+}
+
+/* Split the input into chunks of the given size, or roughly a chunk for each thread (but none smaller
+than the minimum). Without an overlap, each chunk ends just after a NUL byte, which can then only
+end a string, so no string spans two chunks. Otherwise each chunk runs on into the next by the
+overlap, so the strings starting in it are found whole. Either way, searching each chunk separately
+finds the same strings as searching the whole input. An empty input has no chunks. */
+pub fn get_chunks(
+    bytes: &[u8],
+    chunk_size: Option<usize>,
+    overlap: Option<usize>,
+) -> Vec<Chunk<'_>> {
+    let threads = rayon::current_num_threads();
+    let chunk_size =
+        chunk_size.unwrap_or_else(|| bytes.len().div_ceil(threads).max(MIN_CHUNK_SIZE));
+    let mut chunks = Vec::new();
+    let mut chunk_offset = 0;
+    while chunk_offset < bytes.len() {
+        let end = chunk_offset.saturating_add(chunk_size).min(bytes.len());
+        let (end, search_end) = match overlap {
+            None => {
+                let end = match bytes[end..].iter().position(|&b| b == 0) {
+                    Some(nul) => end + nul + 1,
+                    None => bytes.len(),
+                };
+                (end, end)
+            }
+            /* A chunk running on to the end of the input may as well take the rest of it */
+            Some(overlap) => match end.saturating_add(overlap).min(bytes.len()) {
+                search_end if search_end == bytes.len() => (search_end, search_end),
+                search_end => (end, search_end),
+            },
+        };
+        chunks.push(Chunk {
+            offset: chunk_offset,
+            bytes: &bytes[chunk_offset..search_end],
+            len: end - chunk_offset,
+            prev: chunk_offset.checked_sub(1).map(|prev| bytes[prev]),
+        });
+        chunk_offset = end;
+    }
+    chunks
+}
+
+/* Find the file offset of each string in the chunks (only in the given regions), along with its
+weight */
+pub fn get_strings<T: RBaseTraits<T, N>, const N: usize>(
+    chunks: Vec<Chunk<'_>>,
+    regex: &str,
+    string_bytes: Option<[bool; 256]>,
+    weigher: &StringWeigher,
+    script: Option<&Script>,
+    regions: Option<&Regions>,
+    offset_mask: Option<T>,
+) -> FxHashMap<T, usize> {
+    /* Search each chunk for strings and collect them (and their weights) in a hash map */
+    let re = Regex::new(regex).unwrap();
+    let progress_bar = get_progress_bar("Finding strings", chunks.len());
+    let strings = chunks
+        .into_par_iter()
+        .progress_with(progress_bar)
+        .flat_map_iter(|chunk| {
+            let Chunk {
+                offset: chunk_offset,
+                bytes,
+                len,
+                prev,
+            } = chunk;
+            re.find_iter(bytes)
+                .filter(move |m| m.start() < len)
+                .filter(move |m| {
+                    let prev = match m.start() {
+                        0 => prev,
+                        start => Some(bytes[start - 1]),
+                    };
+                    match (string_bytes, prev) {
+                        (Some(string_bytes), Some(prev)) => !string_bytes[prev as usize],
+                        _ => true,
+                    }
+                })
+                .filter(move |m| {
+                    regions.is_none_or(|regions| regions.contains(chunk_offset + m.start()))
+                })
+                .filter_map(move |m| {
+                    weigh(
+                        chunk_offset + m.start(),
+                        m.as_bytes(),
+                        weigher,
+                        script,
+                        offset_mask,
+                    )
+                })
+        });
+    let offsets = collect_by_key(strings, |weight, other| *weight = (*weight).max(other));
+    log!("Found: {:?} strings", offsets.len());
+
+    offsets
+}
+
+/* The (masked) file offset and weight of a string, unless its weight is zero or it is filtered out.
+Offsets too large for an address can't be referenced, so are skipped. */
+fn weigh<T: RBaseTraits<T, N>, const N: usize>(
+    file_offset: usize,
+    text: &[u8],
+    weigher: &StringWeigher,
+    script: Option<&Script>,
+    offset_mask: Option<T>,
+) -> Option<(T, usize)> {
+    if !weigher.accepts(text) {
+        return None;
+    }
+    let file_offset = T::checked_from(file_offset)?;
+    let file_offset = match offset_mask {
+        Some(mask) => file_offset & mask,
+        None => file_offset,
+    };
+    let weight = script
+        .and_then(|script| script.score_string(text))
+        .unwrap_or_else(|| weigher.weight(text));
+    (weight != 0).then_some((file_offset, weight))
+}
+
+/* Find the strings matched by the default regex on the GPU, as get_strings would on the CPU. Each
+batch is preceded by enough of the input to find where a string ending in it starts. */
+pub fn get_terminated_strings<T: RBaseTraits<T, N>, const N: usize>(
+    gpu: &Gpu,
+    bytes: &[u8],
+    terminated: &Terminated,
+    weigher: &StringWeigher,
+    script: Option<&Script>,
+    regions: Option<&Regions>,
+    offset_mask: Option<T>,
+) -> FxHashMap<T, usize> {
+    let starts: Vec<usize> = (0..bytes.len()).step_by(BATCH_BYTES).collect();
+    let progress_bar = get_progress_bar("Finding strings", starts.len());
+    let mut found = Vec::new();
+    for start in starts {
+        let from = start.min(terminated.max + 1);
+        let end = start.saturating_add(BATCH_BYTES).min(bytes.len());
+        found.extend(
+            gpu.strings(&bytes[start - from..end], from, terminated)
+                .into_iter()
+                .map(|(string, nul)| (start - from + string, start - from + nul)),
+        );
+        progress_bar.inc(1);
+    }
+    let strings = found
+        .into_par_iter()
+        .filter(|&(string, _nul)| regions.is_none_or(|regions| regions.contains(string)))
+        .filter_map(|(string, nul)| {
+            weigh(string, &bytes[string..=nul], weigher, script, offset_mask)
+        });
+    let offsets = collect_by_key(strings, |weight, other| *weight = (*weight).max(other));
+    log!("Found: {:?} strings", offsets.len());
+
+    offsets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFAULT_REGEX: &str = "([[:print:]]{4,8})\\x00";
+
+    /* The sorted file offsets of the strings found by searching the chunks of the input */
+    fn find(bytes: &[u8], regex: &str, chunk_size: Option<usize>) -> Vec<u32> {
+        let weigher = StringWeigher::new(false, None, false, false, None, None).unwrap();
+        let chunks = get_chunks(bytes, chunk_size, chunk_overlap(regex));
+        let mut offsets: Vec<u32> = get_strings::<u32, 4>(
+            chunks,
+            regex,
+            Some(std::array::from_fn(|b| (0x20..0x7f).contains(&b))),
+            &weigher,
+            None,
+            None,
+            None,
+        )
+        .into_keys()
+        .collect();
+        offsets.sort_unstable();
+        offsets
+    }
+
+    #[test]
+    fn offsets_beyond_the_reach_of_an_address_are_skipped() {
+        let weigher = StringWeigher::new(false, None, false, false, None, None).unwrap();
+        let weigh_24 = |offset| weigh::<u32, 3>(offset, b"hello\0", &weigher, None, None);
+        assert!(weigh_24(0xFF_FFFF).is_some());
+        assert!(weigh_24(0x100_0000).is_none());
+        let weigh_32 = |offset| weigh::<u32, 4>(offset, b"hello\0", &weigher, None, None);
+        assert!(weigh_32(0x100_0000).is_some());
+        assert!(weigh_32(0x1_0000_0000).is_none());
+    }
+
+    #[test]
+    fn empty_input_has_no_chunks() {
+        assert!(get_chunks(b"", None, None).is_empty());
+        assert!(get_chunks(b"", Some(1), Some(4)).is_empty());
+    }
+
+    #[test]
+    fn input_smaller_than_a_chunk_is_one_chunk() {
+        let bytes = b"hello\0world\0";
+        for overlap in [None, Some(4)] {
+            let chunks = get_chunks(bytes, None, overlap);
+            assert_eq!(chunks.len(), 1);
+            assert_eq!(chunks[0].offset, 0);
+            assert_eq!(chunks[0].bytes, bytes);
+            assert_eq!(chunks[0].len, bytes.len());
+            assert_eq!(chunks[0].prev, None);
+        }
+    }
+
+    #[test]
+    fn chunks_split_after_a_nul() {
+        let bytes = b"abc\0defgh\0ij";
+        let chunks = get_chunks(bytes, Some(1), None);
+        let splits: Vec<(usize, &[u8])> = chunks
+            .iter()
+            .map(|chunk| (chunk.offset, chunk.bytes))
+            .collect();
+        assert_eq!(
+            splits,
+            vec![(0, &b"abc\0"[..]), (4, b"defgh\0"), (10, b"ij")]
+        );
+        assert_eq!(chunks[1].prev, Some(0));
+    }
+
+    #[test]
+    fn chunks_overlap_by_the_longest_match() {
+        let chunks = get_chunks(b"abcdefgh", Some(2), Some(3));
+        let splits: Vec<(usize, &[u8], usize)> = chunks
+            .iter()
+            .map(|chunk| (chunk.offset, chunk.bytes, chunk.len))
+            .collect();
+        assert_eq!(
+            splits,
+            vec![(0, &b"abcde"[..], 2), (2, b"cdefg", 2), (4, b"efgh", 4)]
+        );
+    }
+
+    #[test]
+    fn overlap_is_only_needed_for_a_nul_inside_a_match() {
+        assert_eq!(chunk_overlap(DEFAULT_REGEX), None);
+        assert_eq!(chunk_overlap("[[:print:]]{4,8}"), None);
+        assert_eq!(chunk_overlap("[\\x00-\\x7f]{4,8}\\x00"), Some(9));
+        assert_eq!(chunk_overlap("ab\\x00cd"), Some(5));
+        assert_eq!(chunk_overlap("(\\x00|a)+b"), Some(usize::MAX));
+    }
+
+    #[test]
+    fn strings_straddling_a_chunk_boundary_are_found_whole() {
+        let bytes = b"\xffhello\0\xffworld!\0\xffabc\0de\0fghij\0\xff";
+        let whole = find(bytes, DEFAULT_REGEX, Some(bytes.len()));
+        assert_eq!(whole, vec![1, 8, 23]);
+        for chunk_size in [None, Some(1), Some(3), Some(7)] {
+            assert_eq!(find(bytes, DEFAULT_REGEX, chunk_size), whole);
+        }
+    }
+
+    #[test]
+    fn strings_containing_a_nul_are_found_across_chunks() {
+        let regex = "[a-z]{2}\\x00[a-z]{2}";
+        let bytes = b"\xffab\0cd\xffxy\0zw";
+        let whole = find(bytes, regex, Some(bytes.len()));
+        assert_eq!(whole, vec![1, 7]);
+        for chunk_size in [None, Some(1), Some(2), Some(3), Some(5)] {
+            assert_eq!(find(bytes, regex, chunk_size), whole);
+        }
+    }
+
+    #[test]
+    fn strings_at_a_chunk_boundary_are_checked_for_being_mid_string() {
+        let regex = "[a-z]{3}\\x00?[a-z]?";
+        let bytes = b"\xffabcdefg";
+        let whole = find(bytes, regex, Some(bytes.len()));
+        assert_eq!(whole, vec![1]);
+        for chunk_size in [Some(1), Some(2), Some(4)] {
+            assert_eq!(find(bytes, regex, chunk_size), whole);
+        }
+    }
+}
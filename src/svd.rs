@@ -0,0 +1,95 @@
+use {
+    crate::args::parse_number,
+    roxmltree::{Document, Node},
+    std::fs::read_to_string,
+};
+
+/* A range of memory-mapped peripheral registers */
+pub struct Peripheral {
+    pub name: String,
+    pub start: u64,
+    pub end: u64,
+}
+
+/* The peripherals of an MCU, as described by its CMSIS-SVD file */
+pub struct Svd {
+    peripherals: Vec<Peripheral>,
+}
+
+fn child<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+        .map(|text| text.trim())
+}
+
+fn number(node: Node, name: &str) -> Option<u64> {
+    parse_number(child(node, name)?).ok()
+}
+
+/* The offset and size of each address block of the peripheral */
+fn address_blocks(peripheral: Node) -> Vec<(u64, u64)> {
+    peripheral
+        .children()
+        .filter(|child| child.has_tag_name("addressBlock"))
+        .filter_map(|block| Some((number(block, "offset")?, number(block, "size")?)))
+        .collect()
+}
+
+impl Svd {
+    pub fn load(path: &str) -> Result<Svd, String> {
+        let text = read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        let document = Document::parse(&text).map_err(|e| format!("{path}: {e}"))?;
+        let nodes: Vec<Node> = document
+            .descendants()
+            .filter(|node| node.has_tag_name("peripheral"))
+            .collect();
+
+        /* Derived peripherals inherit the address blocks of the one they are derived from */
+        let mut peripherals = Vec::new();
+        for node in nodes.iter() {
+            let (Some(name), Some(base)) = (child(*node, "name"), number(*node, "baseAddress"))
+            else {
+                continue;
+            };
+            let mut blocks = address_blocks(*node);
+            if blocks.is_empty() {
+                if let Some(parent) = node.attribute("derivedFrom").and_then(|parent| {
+                    nodes
+                        .iter()
+                        .find(|other| child(**other, "name") == Some(parent))
+                }) {
+                    blocks = address_blocks(*parent);
+                }
+            }
+            for (offset, size) in blocks.into_iter().filter(|&(_, size)| size != 0) {
+                let start = base.saturating_add(offset);
+                peripherals.push(Peripheral {
+                    name: name.to_string(),
+                    start,
+                    end: start.saturating_add(size - 1),
+                });
+            }
+        }
+        peripherals.sort_unstable_by_key(|peripheral| peripheral.start);
+        Ok(Svd { peripherals })
+    }
+
+    pub fn len(&self) -> usize {
+        self.peripherals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peripherals.is_empty()
+    }
+
+    /* The peripheral whose registers contain the address, if any */
+    pub fn lookup(&self, address: u64) -> Option<&Peripheral> {
+        let idx = self
+            .peripherals
+            .partition_point(|peripheral| peripheral.start <= address);
+        self.peripherals[..idx]
+            .last()
+            .filter(|peripheral| address <= peripheral.end)
+    }
+}
@@ -0,0 +1,106 @@
+/* The symbols of a related build (e.g. a System.map, the output of nm, or a linker map) will mostly
+be at the same addresses in the image being analysed, so the right base address puts most of them on
+plausible structures: code on aligned bytes which are neither padding nor text, and data within the
+image. Candidates are scored by how many do, which is a strong check on vendor-derived firmware. */
+use {
+    crate::args::parse_number,
+    std::{collections::HashSet, fs::read_to_string},
+};
+
+/* Number of bytes at a code symbol which must be neither all padding nor all text */
+const SYMBOL_BYTES: usize = 8;
+
+/* Minimum alignment of code */
+const CODE_ALIGN: u64 = 2;
+
+pub struct Symbol {
+    pub address: u64,
+    pub code: bool,
+}
+
+pub struct Symbols {
+    symbols: Vec<Symbol>,
+}
+
+/* A line of nm output (or a System.map), such as c0008000 T stext. Symbols which aren't in the image
+(undefined, absolute or uninitialised) are ignored. */
+fn nm_symbol(tokens: &[&str]) -> Option<Symbol> {
+    let [address, kind, _name] = tokens else {
+        return None;
+    };
+    let address = u64::from_str_radix(address, 16).ok()?;
+    match *kind {
+        "T" | "t" | "W" | "w" => Some(Symbol {
+            address,
+            code: true,
+        }),
+        "D" | "d" | "R" | "r" | "G" | "g" => Some(Symbol {
+            address,
+            code: false,
+        }),
+        _ => None,
+    }
+}
+
+/* A symbol of a GNU linker map, such as 0x0000000008000100    Reset_Handler, whose kind is unknown */
+fn map_symbol(tokens: &[&str]) -> Option<Symbol> {
+    let [address, name] = tokens else {
+        return None;
+    };
+    if !address.starts_with("0x")
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$')
+    {
+        return None;
+    }
+    Some(Symbol {
+        address: parse_number(address).ok()?,
+        code: false,
+    })
+}
+
+impl Symbols {
+    pub fn load(path: &str) -> Result<Symbols, String> {
+        let source = read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        let mut seen = HashSet::new();
+        let symbols = source
+            .lines()
+            .filter_map(|line| {
+                let tokens: Vec<&str> = line.split_whitespace().collect();
+                nm_symbol(&tokens).or_else(|| map_symbol(&tokens))
+            })
+            .filter(|symbol| seen.insert(symbol.address))
+            .collect();
+        Ok(Symbols { symbols })
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /* The number of symbols which the base address puts on plausible structures in the image */
+    pub fn landing(&self, bytes: &[u8], base: u64) -> usize {
+        self.symbols
+            .iter()
+            .filter(|symbol| {
+                let Some(offset) = symbol.address.checked_sub(base) else {
+                    return false;
+                };
+                let Some(data) = usize::try_from(offset)
+                    .ok()
+                    .and_then(|offset| bytes.get(offset..offset.saturating_add(SYMBOL_BYTES)))
+                else {
+                    return false;
+                };
+                let padding = data.iter().all(|&b| b == 0x00) || data.iter().all(|&b| b == 0xff);
+                let text = data.iter().all(|b| (0x20..0x7f).contains(b));
+                !symbol.code || (offset % CODE_ALIGN == 0 && !padding && !text)
+            })
+            .count()
+    }
+}
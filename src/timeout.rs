@@ -0,0 +1,55 @@
+/* Unattended scans can be given a time limit, so a pathological input (e.g. a huge blob of high
+entropy data) can't hold them up for hours. Once it expires, correlation stops after the batch in
+progress and the leading candidates so far are reported as partial results. Phases which can't stop
+early are given a quarter as long again to finish, after which the scan is abandoned. Either way,
+rbase exits with a distinct exit code. */
+use std::{
+    process::exit,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/* Exit code when the time limit expires, whether with partial results or none */
+pub const EXIT_CODE: u8 = 2;
+
+static DEADLINE: OnceLock<Instant> = OnceLock::new();
+
+static EXPIRED: AtomicBool = AtomicBool::new(false);
+
+/* Start the clock, and a watchdog to abandon the scan if it overruns by too much */
+pub fn init(timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    DEADLINE.set(deadline).expect("timeout already initialized");
+    thread::spawn(move || {
+        thread::sleep(timeout + timeout / 4);
+        eprintln!(
+            "Timeout: abandoning the scan after {:?}",
+            timeout + timeout / 4
+        );
+        exit(EXIT_CODE.into());
+    });
+}
+
+pub fn is_set() -> bool {
+    DEADLINE.get().is_some()
+}
+
+/* Whether the time limit has expired, remembering if so */
+pub fn expired() -> bool {
+    let expired = DEADLINE
+        .get()
+        .is_some_and(|deadline| Instant::now() >= *deadline);
+    if expired {
+        EXPIRED.store(true, Ordering::Relaxed);
+    }
+    expired
+}
+
+/* Whether the results are partial, because the time limit expired */
+pub fn has_expired() -> bool {
+    EXPIRED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,193 @@
+/* U-Boot keeps its environment in a block which starts with a CRC32 of the data which follows it
+(and, for redundant environments, a flags byte). The data is a list of NUL-terminated name=value
+pairs ending with an empty one, padded to the size of the block. Variables such as loadaddr say
+where images are loaded, which is a useful hint as to the base address. */
+use {crc32fast::Hasher, regex::bytes::Regex};
+
+/* Variables which are set in almost every environment, used to find candidate blocks */
+const COMMON_VARIABLES: &str = r"(?-u)(bootcmd|bootdelay|baudrate|bootargs)=";
+
+/* Limit on the number of candidate blocks checked, and their size */
+const MAX_CANDIDATES: usize = 0x100;
+const MAX_ENV_SIZE: usize = 0x80000;
+const MIN_ENV_SIZE: usize = 0x400;
+
+/* Variables giving addresses at which images are loaded */
+const LOAD_ADDRESS_VARIABLES: [&str; 8] = [
+    "loadaddr",
+    "kernel_addr_r",
+    "kernel_addr",
+    "fdt_addr_r",
+    "ramdisk_addr_r",
+    "bootm_low",
+    "fileaddr",
+    "load_addr",
+];
+
+pub struct Environment {
+    pub offset: usize,
+    pub variables: Vec<(String, String)>,
+}
+
+impl Environment {
+    /* The load addresses given by the environment, as (variable, address). U-Boot parses addresses
+    as hex whether or not they have a 0x prefix. */
+    pub fn hints(&self) -> Vec<(&str, u64)> {
+        self.variables
+            .iter()
+            .filter(|(name, _value)| LOAD_ADDRESS_VARIABLES.contains(&name.as_str()))
+            .filter_map(|(name, value)| {
+                let value = value.trim();
+                let digits = value
+                    .strip_prefix("0x")
+                    .or_else(|| value.strip_prefix("0X"))
+                    .unwrap_or(value);
+                Some((name.as_str(), u64::from_str_radix(digits, 16).ok()?))
+            })
+            .collect()
+    }
+}
+
+fn is_text(b: u8) -> bool {
+    b.is_ascii_graphic() || b == b' ' || b == b'\t' || b == b'\n'
+}
+
+/* The start of the first of the run of variables preceding the one at the given offset. The first
+variable follows the header rather than a NUL, so it may include header bytes which happen to be
+text. */
+fn first_variable(bytes: &[u8], offset: usize) -> usize {
+    let mut start = offset;
+    while start >= 2 && bytes[start - 1] == 0 {
+        let previous = bytes[..start - 1]
+            .iter()
+            .rposition(|&b| !is_text(b))
+            .map_or(0, |end| end + 1);
+        let entry = &bytes[previous..start - 1];
+        if !entry.contains(&b'=') {
+            break;
+        }
+        start = previous;
+    }
+    start
+}
+
+/* Whether the CRC of the data, for some plausible size of environment block, matches the stored one.
+Environment blocks are a power of two in size, and include the header. */
+fn crc_matches(bytes: &[u8], header: usize, data: usize) -> bool {
+    let Some(stored) = bytes.get(header..header + 4) else {
+        return false;
+    };
+    let stored: [u8; 4] = stored.try_into().unwrap();
+    let (le, be) = (u32::from_le_bytes(stored), u32::from_be_bytes(stored));
+
+    let mut hasher = Hasher::new();
+    let mut end = data;
+    let mut size = MIN_ENV_SIZE;
+    while size <= MAX_ENV_SIZE {
+        let Some(block) = bytes.get(end..header + size) else {
+            return false;
+        };
+        hasher.update(block);
+        end = header + size;
+        let crc = hasher.clone().finalize();
+        if crc == le || crc == be {
+            return true;
+        }
+        size *= 2;
+    }
+    false
+}
+
+fn parse(bytes: &[u8], offset: usize) -> Environment {
+    let variables = bytes[offset..]
+        .split(|&b| b == 0)
+        .take_while(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let (name, value) = std::str::from_utf8(entry).ok()?.split_once('=')?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect();
+    Environment { offset, variables }
+}
+
+/* Find the first environment block in the image whose CRC is valid */
+pub fn find(bytes: &[u8]) -> Option<Environment> {
+    let regex = Regex::new(COMMON_VARIABLES).unwrap();
+    let mut checked = Vec::new();
+    for m in regex.find_iter(bytes) {
+        if m.start() != 0 && bytes[m.start() - 1] != 0 {
+            continue;
+        }
+        let first = first_variable(bytes, m.start());
+        if checked.contains(&first) {
+            continue;
+        }
+        if checked.len() == MAX_CANDIDATES {
+            break;
+        }
+        checked.push(first);
+
+        /* The header is a CRC, optionally followed by a flags byte, any of which may have been
+        taken for part of the first variable */
+        for data in (first..=first + 5).filter(|&data| data <= m.start()) {
+            for header_len in [4, 5] {
+                let Some(header) = data.checked_sub(header_len) else {
+                    continue;
+                };
+                if crc_matches(bytes, header, data) {
+                    return Some(parse(bytes, data));
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /* An environment block of the given size holding the variables, with a flags byte after the CRC
+    if it is redundant */
+    fn block(variables: &[&str], size: usize, redundant: bool) -> Vec<u8> {
+        let header_len = if redundant { 5 } else { 4 };
+        let mut data: Vec<u8> = variables
+            .iter()
+            .flat_map(|variable| variable.bytes().chain([0]))
+            .collect();
+        data.resize(size - header_len, 0);
+        let mut block = crc32fast::hash(&data).to_le_bytes().to_vec();
+        if redundant {
+            block.push(1);
+        }
+        block.extend(data);
+        block
+    }
+
+    #[test]
+    fn finds_the_load_addresses_in_an_environment() {
+        let variables = [
+            "bootcmd=bootm ${loadaddr}",
+            "bootdelay=3",
+            "loadaddr=0x82000000",
+            "kernel_addr_r=81000000",
+        ];
+        for redundant in [false, true] {
+            let image = [vec![0x5a; 0x200], block(&variables, 0x1000, redundant)].concat();
+            let environment = find(&image).unwrap();
+            assert_eq!(environment.offset, 0x200 + if redundant { 5 } else { 4 });
+            assert_eq!(environment.variables.len(), 4);
+            assert_eq!(
+                environment.hints(),
+                vec![("loadaddr", 0x82000000), ("kernel_addr_r", 0x81000000)]
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_an_environment_with_a_bad_crc() {
+        let mut image = block(&["bootcmd=boot", "loadaddr=0x82000000"], 0x1000, false);
+        image[0] ^= 1;
+        assert!(find(&image).is_none());
+    }
+}
@@ -0,0 +1,132 @@
+use {
+    regex::bytes::Regex,
+    std::{collections::HashSet, fs::read_to_string},
+};
+
+/* A small dictionary of words commonly found in strings within firmware images */
+const WORDS: &str = "\
+abort access address alloc allocate argument assert attach audio bad battery bios block \
+boot buffer bus busy cache calibration call cannot card channel check checksum chip clock \
+close command config configuration connect connection console control controller copy \
+copyright core count cpu crc create current data debug default delete detect detected \
+device disable disabled disk done download driver dump enable enabled end entry erase \
+error event exception exit fail failed failure fatal file firmware flash format found \
+frame free function get handler hardware header host image index info init initialize \
+initialized input install interface internal interrupt invalid irq kernel key length level \
+library limit line link list load loading lock log loop main mode memory message missing \
+module mount name network not null number object offset open option out output overflow \
+packet page panic parameter parse partition password path pending pointer port power \
+process program protocol queue read ready receive record register release remove request \
+reset resource response restart result retry return root running send sensor serial server \
+service set setting setup signal size socket space stack start state status stop storage \
+string success support supported switch system table task thread time timeout timer total \
+transfer type unable unknown update usage user value version wait warning write wrong";
+
+/* Strings containing dictionary words are counted this many times during correlation */
+const WORD_WEIGHT: usize = 2;
+
+/* A printf-style conversion specification (e.g. %s, %d or %08lx), with its flags, width, precision
+and length modifier */
+const FORMAT_SPECIFIER: &str =
+    r"(?-u)%[-+ #0']*(\d+|\*)?(\.(\d+|\*))?(hh|h|ll|l|j|z|t|L|q)?[diouxXeEfFgGaAcspn]";
+
+/* Format strings are counted this many times during correlation, since they are almost always
+passed to printf and so referenced by code */
+const FORMAT_WEIGHT: usize = 3;
+
+/* An absolute Unix path with at least two components (e.g. /etc/passwd), a Windows path (e.g.
+C:\Windows or \\server\share) or a URL (e.g. http://host/) */
+const PATH: &str = concat!(
+    r"(?-u)(^|[\s'\x22=:(])/[\w.-]+/[\w.-]",
+    r"|\b[A-Za-z]:\\[\w.-]|\\\\[\w.-]+\\[\w.$-]",
+    r"|\b[A-Za-z][A-Za-z0-9+.-]*://[\w.-]",
+);
+
+/* Paths and URLs are counted this many times during correlation, since they are nearly always
+referenced by code and rarely coincidental runs of characters */
+const PATH_WEIGHT: usize = 3;
+
+/* Minimum length of a word to look up in the dictionary */
+const MIN_WORD_LENGTH: usize = 3;
+
+/* Weights strings by how plausible they are as genuine (and hence referenced) strings, so that
+random printable runs in data tables count less */
+#[derive(Default)]
+pub struct StringWeigher {
+    words: Option<HashSet<String>>,
+    format: Option<Regex>,
+    path: Option<Regex>,
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+}
+
+impl StringWeigher {
+    pub fn new(
+        use_dictionary: bool,
+        wordlist: Option<&str>,
+        format_strings: bool,
+        paths: bool,
+        include: Option<&str>,
+        exclude: Option<&str>,
+    ) -> Result<Self, String> {
+        let words = match wordlist {
+            Some(wordlist) => Some(
+                read_to_string(wordlist)
+                    .map_err(|e| format!("{wordlist}: {e}"))?
+                    .lines()
+                    .map(|word| word.trim().to_lowercase())
+                    .filter(|word| word.len() >= MIN_WORD_LENGTH)
+                    .collect(),
+            ),
+            None if use_dictionary => Some(WORDS.split_whitespace().map(String::from).collect()),
+            None => None,
+        };
+        let format = format_strings.then(|| Regex::new(FORMAT_SPECIFIER).unwrap());
+        let path = paths.then(|| Regex::new(PATH).unwrap());
+        let include = include.map(|include| Regex::new(include).unwrap());
+        let exclude = exclude.map(|exclude| Regex::new(exclude).unwrap());
+        Ok(StringWeigher {
+            words,
+            format,
+            path,
+            include,
+            exclude,
+        })
+    }
+
+    /* Whether the string (without its terminator) passes the include and exclude filters, so that
+    strings known to be noise (e.g. debug spam or Base64 blocks) aren't used as evidence */
+    pub fn accepts(&self, text: &[u8]) -> bool {
+        let text = text.strip_suffix(b"\0").unwrap_or(text);
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.is_match(text))
+            && !self
+                .exclude
+                .as_ref()
+                .is_some_and(|exclude| exclude.is_match(text))
+    }
+
+    fn contains_word(&self, words: &HashSet<String>, text: &[u8]) -> bool {
+        text.split(|b| !b.is_ascii_alphabetic())
+            .filter(|word| word.len() >= MIN_WORD_LENGTH)
+            .any(|word| words.contains(&String::from_utf8_lossy(word).to_lowercase()))
+    }
+
+    /* The largest weight of any kind of genuine string which the text looks like */
+    pub fn weight(&self, text: &[u8]) -> usize {
+        let word = match &self.words {
+            Some(words) if self.contains_word(words, text) => WORD_WEIGHT,
+            _ => 1,
+        };
+        let format = match &self.format {
+            Some(format) if format.is_match(text) => FORMAT_WEIGHT,
+            _ => 1,
+        };
+        let path = match &self.path {
+            Some(path) if path.is_match(text) => PATH_WEIGHT,
+            _ => 1,
+        };
+        word.max(format).max(path)
+    }
+}
@@ -0,0 +1,77 @@
+/* YARA rules are run over the image by the yara command line tool, so existing rules (e.g. for
+certificates or compressed data) can annotate its regions and select which are searched. The rules
+are run over the bytes being analysed (after any hexdump, NAND or header processing, so they are
+written to a temporary file), so the offsets they match at are file offsets like any other. */
+use {
+    std::{
+        io::{ErrorKind, Write},
+        process::Command,
+    },
+    tempfile::Builder,
+};
+
+pub struct Match {
+    pub rule: String,
+    /* From the start of the first string it matched to the end of the last, if it matched any */
+    pub extent: Option<(usize, usize)>,
+}
+
+pub fn scan(rules: &str, bytes: &[u8]) -> Result<Vec<Match>, String> {
+    /* The temporary file is created exclusively with an unpredictable name, so it can't be
+    pre-empted by another user, and is deleted when dropped */
+    let mut file = Builder::new()
+        .prefix("rbase-")
+        .suffix(".bin")
+        .tempfile()
+        .map_err(|e| format!("temporary file: {e}"))?;
+    file.write_all(bytes)
+        .and_then(|_| file.flush())
+        .map_err(|e| format!("{}: {e}", file.path().display()))?;
+    let output = Command::new("yara")
+        .args(["--no-warnings", "--print-string-length", rules])
+        .arg(file.path())
+        .output();
+    drop(file);
+    let output = output.map_err(|e| match e.kind() {
+        ErrorKind::NotFound => "yara: the yara command line tool isn't installed".to_string(),
+        _ => format!("yara: {e}"),
+    })?;
+    if !output.status.success() {
+        return Err(format!(
+            "yara: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(parse(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/* Each rule which matched is printed as its name followed by the file name, then each string it
+matched as its offset, length and identifier (e.g. 0x1a0:12:$magic) */
+fn parse(output: &str) -> Vec<Match> {
+    let mut matches: Vec<Match> = Vec::new();
+    for line in output.lines() {
+        let Some(string) = line.strip_prefix("0x") else {
+            if let Some(rule) = line.split_whitespace().next() {
+                matches.push(Match {
+                    rule: rule.to_string(),
+                    extent: None,
+                });
+            }
+            continue;
+        };
+        let mut fields = string.split(':');
+        let (Some(Ok(start)), Some(Ok(length)), Some(last)) = (
+            fields.next().map(|start| usize::from_str_radix(start, 16)),
+            fields.next().map(|length| length.parse::<usize>()),
+            matches.last_mut(),
+        ) else {
+            continue;
+        };
+        let end = start + length;
+        last.extent = Some(match last.extent {
+            Some((first, last)) => (first.min(start), last.max(end)),
+            None => (start, end),
+        });
+    }
+    matches
+}
@@ -0,0 +1,87 @@
+/* Runs the rbase binary over small synthetic images, checking what it prints and its exit status */
+use std::{
+    fs::write,
+    path::Path,
+    process::{Command, Output},
+};
+
+const BASE: u32 = 0x8001_0000;
+
+/* A 32-bit little-endian image holding a block of strings followed by a table of pointers to them,
+as loaded at the base address */
+fn image() -> Vec<u8> {
+    let mut bytes = vec![0; 0x20000];
+    let mut offset = 0x1000;
+    let mut strings = Vec::new();
+    for idx in 0..1000 {
+        let text = format!("message {idx} failed to open device\0");
+        bytes[offset..offset + text.len()].copy_from_slice(text.as_bytes());
+        strings.push(offset);
+        offset += text.len() + idx % 3;
+    }
+    for (idx, string) in strings.into_iter().enumerate() {
+        let pointer = 0x10000 + idx * 4;
+        bytes[pointer..pointer + 4].copy_from_slice(&(BASE + string as u32).to_le_bytes());
+    }
+    bytes
+}
+
+fn rbase(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rbase"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn path(dir: &Path, name: &str) -> String {
+    dir.join(name).to_string_lossy().into_owned()
+}
+
+#[test]
+fn finds_the_base_address() {
+    let dir = tempfile::tempdir().unwrap();
+    let image_path = path(dir.path(), "image.bin");
+    write(&image_path, image()).unwrap();
+    let output = rbase(&[&image_path]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "80010000");
+}
+
+#[test]
+fn batch_reports_a_bad_file_and_scans_the_others() {
+    let dir = tempfile::tempdir().unwrap();
+    let first = path(dir.path(), "first.bin");
+    let second = path(dir.path(), "second.bin");
+    let missing = path(dir.path(), "missing.bin");
+    write(&first, image()).unwrap();
+    write(&second, image()).unwrap();
+    let output = rbase(&[&first, &missing, &second]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(&format!("{first}: 80010000")));
+    assert!(stdout.contains(&format!("{second}: 80010000")));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!("error: {missing}: ")));
+}
+
+#[test]
+fn unwritable_output_is_an_error_not_a_panic() {
+    let dir = tempfile::tempdir().unwrap();
+    let image_path = path(dir.path(), "image.bin");
+    write(&image_path, image()).unwrap();
+    let output_path = path(dir.path(), "missing/output");
+    for option in [
+        "--emit-emu-map",
+        "--dump-pointers",
+        "--heatmap",
+        "--plot-data",
+    ] {
+        let output = rbase(&[option, &output_path, &image_path]);
+        assert_eq!(output.status.code(), Some(1), "{option}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains(&format!("error: {output_path}: ")),
+            "{option}: {stderr}"
+        );
+    }
+}